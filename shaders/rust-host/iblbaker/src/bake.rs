@@ -0,0 +1,109 @@
+// CPU versions of the pbribl precomputation passes. The BRDF and GGX sampling come from
+// pbribl/genbrdflut unchanged; the cubemap filters mirror pbribl/irradiancecube and
+// pbribl/prefilterenvmap, with explicit lods where the GPU versions use derivatives.
+
+use core::f32::consts::{PI, TAU};
+use rayon::prelude::*;
+use spirv_std::glam::{vec2, vec3, Vec2, Vec3, Vec4};
+use genbrdflut::{brdf, hammersley2d, importance_sample_ggx};
+use prefilterenvmap::d_ggx;
+
+use crate::cubemap::{Cubemap, Level};
+
+/// BRDF LUT with NoV along x and roughness along y, rows top to bottom.
+pub fn brdf_lut(size: u32, num_samples: u32) -> Vec<Vec2> {
+    (0..size * size)
+        .into_par_iter()
+        .map(|index| {
+            let uv = (vec2((index % size) as f32, (index / size) as f32) + 0.5) / size as f32;
+            brdf(uv.x, uv.y, num_samples)
+        })
+        .collect()
+}
+
+// Solid angle of one texel of the first level of a cubemap
+fn texel_solid_angle(cubemap: &Cubemap) -> f32 {
+    let size = cubemap.size() as f32;
+    4.0 * PI / (6.0 * size * size)
+}
+
+/// Diffuse irradiance divided by pi, with the sample spacing of pbribl.
+pub fn irradiance(environment: &Cubemap, size: u32) -> Cubemap {
+    let delta_phi = TAU / 180.0;
+    let delta_theta = 0.5 * PI / 64.0;
+    let omega_p = texel_solid_angle(environment);
+
+    let level = Level::from_fn(size, |n| {
+        let up = vec3(0.0, 1.0, 0.0);
+        let right = up.cross(n).normalize();
+        let up = n.cross(right);
+
+        let mut color = Vec3::ZERO;
+        let mut sample_count = 0u32;
+        let mut phi = 0.0f32;
+        while phi < TAU {
+            let mut theta = 0.0f32;
+            while theta < 0.5 * PI {
+                let temp_vec = phi.cos() * right + phi.sin() * up;
+                let sample_vector = theta.cos() * n + theta.sin() * temp_vec;
+                let omega_s = delta_phi * delta_theta * theta.sin().max(delta_theta);
+                let lod = (0.5 * (omega_s / omega_p).log2()).max(0.0);
+                color += environment.sample(sample_vector, lod).truncate() * theta.cos() * theta.sin();
+                sample_count += 1;
+                theta += delta_theta;
+            }
+            phi += delta_phi;
+        }
+        (color * PI / sample_count as f32).extend(1.0)
+    });
+
+    let mut cubemap = Cubemap::from_level(level);
+    cubemap.generate_mips();
+    cubemap
+}
+
+// Filtered importance sampling of the environment around the reflection vector r
+fn prefilter(environment: &Cubemap, r: Vec3, roughness: f32, num_samples: u32) -> Vec4 {
+    let n = r;
+    let v = r;
+    let omega_p = texel_solid_angle(environment);
+    let mut color = Vec3::ZERO;
+    let mut total_weight = 0.0;
+
+    for i in 0..num_samples {
+        let xi = hammersley2d(i, num_samples);
+        let h = importance_sample_ggx(xi, roughness, n);
+        let l = 2.0 * v.dot(h) * h - v;
+        let dot_nl = n.dot(l).clamp(0.0, 1.0);
+        if dot_nl > 0.0 {
+            let dot_nh = n.dot(h).clamp(0.0, 1.0);
+            let dot_vh = v.dot(h).clamp(0.0, 1.0);
+            let pdf = d_ggx(dot_nh, roughness) * dot_nh / (4.0 * dot_vh) + 0.0001;
+            let omega_s = 1.0 / (num_samples as f32 * pdf);
+            // Biased (+1.0) mip level as in pbribl/prefilterenvmap
+            let lod = (0.5 * (omega_s / omega_p).log2() + 1.0).max(0.0);
+            color += environment.sample(l, lod).truncate() * dot_nl;
+            total_weight += dot_nl;
+        }
+    }
+    (color / total_weight).extend(1.0)
+}
+
+/// Prefiltered specular cubemap with roughness increasing linearly over the mip chain.
+pub fn prefiltered(environment: &Cubemap, size: u32, num_samples: u32) -> Cubemap {
+    let mip_levels = size.ilog2() + 1;
+    let levels = (0..mip_levels)
+        .map(|mip| {
+            let mip_size = (size >> mip).max(1);
+            if mip == 0 {
+                // The first level is the unfiltered environment, resampled to the output size
+                let lod = (environment.size() as f32 / mip_size as f32).log2().max(0.0);
+                Level::from_fn(mip_size, |dir| environment.sample(dir, lod))
+            } else {
+                let roughness = mip as f32 / (mip_levels - 1) as f32;
+                Level::from_fn(mip_size, |r| prefilter(environment, r, roughness, num_samples))
+            }
+        })
+        .collect();
+    Cubemap { levels }
+}
@@ -0,0 +1,130 @@
+// CPU cubemap with a mip chain, using the same face layout as the compute passes
+// (common::cubemap::face_direction).
+
+use core::f32::consts::PI;
+use rayon::prelude::*;
+use spirv_std::glam::{vec2, Vec2, Vec3, Vec4};
+use common::cubemap::face_direction;
+
+use crate::hdr;
+
+pub struct Level {
+    pub size: u32,
+    // Texels of the six faces in Vulkan face order, rows top to bottom
+    pub faces: [Vec<Vec4>; 6],
+}
+
+pub struct Cubemap {
+    pub levels: Vec<Level>,
+}
+
+// Face and position in [0, 1] on it hit by a direction, the inverse of face_direction
+fn face_uv(dir: Vec3) -> (usize, Vec2) {
+    let a = dir.abs();
+    let (face, u, v) = if a.x >= a.y && a.x >= a.z {
+        if dir.x > 0.0 {
+            (0, -dir.z / a.x, -dir.y / a.x)
+        } else {
+            (1, dir.z / a.x, -dir.y / a.x)
+        }
+    } else if a.y >= a.z {
+        if dir.y > 0.0 {
+            (2, dir.x / a.y, dir.z / a.y)
+        } else {
+            (3, dir.x / a.y, -dir.z / a.y)
+        }
+    } else if dir.z > 0.0 {
+        (4, dir.x / a.z, -dir.y / a.z)
+    } else {
+        (5, -dir.x / a.z, -dir.y / a.z)
+    };
+    (face, vec2(u * 0.5 + 0.5, v * 0.5 + 0.5))
+}
+
+impl Level {
+    /// Evaluates `f` for the direction through the center of every texel, in parallel.
+    pub fn from_fn(size: u32, f: impl Fn(Vec3) -> Vec4 + Sync) -> Self {
+        let faces = core::array::from_fn(|face| {
+            (0..size * size)
+                .into_par_iter()
+                .map(|index| {
+                    let uv = (vec2((index % size) as f32, (index / size) as f32) + 0.5) / size as f32;
+                    f(face_direction(face as u32, uv))
+                })
+                .collect()
+        });
+        Self { size, faces }
+    }
+
+    // Bilinear filtering within a face, clamped at its edges
+    fn sample(&self, face: usize, uv: Vec2) -> Vec4 {
+        let max = self.size as i32 - 1;
+        let p = uv * self.size as f32 - 0.5;
+        let x0 = p.x.floor() as i32;
+        let y0 = p.y.floor() as i32;
+        let fx = p.x - x0 as f32;
+        let fy = p.y - y0 as f32;
+        let texel = |x: i32, y: i32| self.faces[face][(y.clamp(0, max) * (max + 1) + x.clamp(0, max)) as usize];
+        let top = texel(x0, y0).lerp(texel(x0 + 1, y0), fx);
+        let bottom = texel(x0, y0 + 1).lerp(texel(x0 + 1, y0 + 1), fx);
+        top.lerp(bottom, fy)
+    }
+
+    // Average of 2x2 texel blocks
+    fn downsample(&self) -> Self {
+        let size = (self.size / 2).max(1);
+        let src = self.size as usize;
+        let faces = core::array::from_fn(|face| {
+            let texels = &self.faces[face];
+            (0..(size * size) as usize)
+                .map(|index| {
+                    let x = (index % size as usize) * 2;
+                    let y = (index / size as usize) * 2;
+                    let x1 = (x + 1).min(src - 1);
+                    let y1 = (y + 1).min(src - 1);
+                    (texels[y * src + x] + texels[y * src + x1] + texels[y1 * src + x] + texels[y1 * src + x1]) * 0.25
+                })
+                .collect()
+        });
+        Self { size, faces }
+    }
+}
+
+impl Cubemap {
+    pub fn from_level(level: Level) -> Self {
+        Self { levels: vec![level] }
+    }
+
+    /// Resamples an equirectangular panorama, with +y up and the seam along -x.
+    pub fn from_equirectangular(image: &hdr::Image, size: u32) -> Self {
+        let level = Level::from_fn(size, |dir| {
+            let uv = vec2(dir.z.atan2(dir.x) / (2.0 * PI) + 0.5, dir.y.clamp(-1.0, 1.0).acos() / PI);
+            image.sample(uv).extend(1.0)
+        });
+        let mut cubemap = Self::from_level(level);
+        cubemap.generate_mips();
+        cubemap
+    }
+
+    pub fn size(&self) -> u32 {
+        self.levels[0].size
+    }
+
+    /// Fills the mip chain down to 1x1 from the first level.
+    pub fn generate_mips(&mut self) {
+        self.levels.truncate(1);
+        while self.levels.last().unwrap().size > 1 {
+            let next = self.levels.last().unwrap().downsample();
+            self.levels.push(next);
+        }
+    }
+
+    /// Trilinear sample in direction `dir`, like a texture lookup with an explicit lod.
+    pub fn sample(&self, dir: Vec3, lod: f32) -> Vec4 {
+        let (face, uv) = face_uv(dir);
+        let lod = lod.clamp(0.0, (self.levels.len() - 1) as f32);
+        let lower = lod.floor() as usize;
+        let upper = (lower + 1).min(self.levels.len() - 1);
+        self.levels[lower].sample(face, uv).lerp(self.levels[upper].sample(face, uv), lod - lower as f32)
+    }
+}
@@ -0,0 +1,137 @@
+// Reader for Radiance RGBE (.hdr) images, flat or with run length encoded scanlines.
+
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use spirv_std::glam::{Vec2, Vec3};
+
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    // Rows top to bottom
+    pub pixels: Vec<Vec3>,
+}
+
+impl Image {
+    /// Bilinear sample, wrapping horizontally and clamped vertically.
+    pub fn sample(&self, uv: Vec2) -> Vec3 {
+        let w = self.width as i32;
+        let h = self.height as i32;
+        let x = uv.x * w as f32 - 0.5;
+        let y = uv.y * h as f32 - 0.5;
+        let x0 = x.floor() as i32;
+        let y0 = y.floor() as i32;
+        let fx = x - x0 as f32;
+        let fy = y - y0 as f32;
+        let pixel = |px: i32, py: i32| self.pixels[(py.clamp(0, h - 1) * w + px.rem_euclid(w)) as usize];
+        let top = pixel(x0, y0).lerp(pixel(x0 + 1, y0), fx);
+        let bottom = pixel(x0, y0 + 1).lerp(pixel(x0 + 1, y0 + 1), fx);
+        top.lerp(bottom, fy)
+    }
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+fn rgbe_to_rgb(rgbe: [u8; 4]) -> Vec3 {
+    if rgbe[3] == 0 {
+        return Vec3::ZERO;
+    }
+    let scale = 2.0f32.powi(rgbe[3] as i32 - (128 + 8));
+    Vec3::new(rgbe[0] as f32, rgbe[1] as f32, rgbe[2] as f32) * scale
+}
+
+// Decodes one scanline starting at `pos`, returning the position after it
+fn read_scanline(data: &[u8], mut pos: usize, width: usize, out: &mut [[u8; 4]]) -> Result<usize> {
+    let truncated = || invalid("truncated scanline");
+    let is_rle = (8..0x8000).contains(&width)
+        && data.len() >= pos + 4
+        && data[pos] == 2
+        && data[pos + 1] == 2
+        && data[pos + 2] & 0x80 == 0;
+    if !is_rle {
+        for texel in out.iter_mut() {
+            let bytes = data.get(pos..pos + 4).ok_or_else(truncated)?;
+            texel.copy_from_slice(bytes);
+            pos += 4;
+        }
+        return Ok(pos);
+    }
+
+    if ((data[pos + 2] as usize) << 8 | data[pos + 3] as usize) != width {
+        return Err(invalid("scanline width mismatch"));
+    }
+    pos += 4;
+    // Each channel is stored separately as runs and literal spans
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = *data.get(pos).ok_or_else(truncated)? as usize;
+            pos += 1;
+            if count > 128 {
+                let run = count - 128;
+                let value = *data.get(pos).ok_or_else(truncated)?;
+                pos += 1;
+                if x + run > width {
+                    return Err(invalid("run exceeds scanline"));
+                }
+                for texel in &mut out[x..x + run] {
+                    texel[channel] = value;
+                }
+                x += run;
+            } else {
+                if count == 0 || x + count > width {
+                    return Err(invalid("invalid span in scanline"));
+                }
+                let values = data.get(pos..pos + count).ok_or_else(truncated)?;
+                for (texel, value) in out[x..x + count].iter_mut().zip(values) {
+                    texel[channel] = *value;
+                }
+                pos += count;
+                x += count;
+            }
+        }
+    }
+    Ok(pos)
+}
+
+pub fn load(path: &str) -> Result<Image> {
+    let data = fs::read(path)?;
+    let mut pos = 0;
+    let mut next_line = || -> Result<String> {
+        let end = data[pos..].iter().position(|&b| b == b'\n').ok_or_else(|| invalid("truncated header"))?;
+        let line = String::from_utf8_lossy(&data[pos..pos + end]).trim_end().to_string();
+        pos += end + 1;
+        Ok(line)
+    };
+
+    let magic = next_line()?;
+    if !magic.starts_with("#?") {
+        return Err(invalid("not a Radiance HDR file"));
+    }
+    // Header lines up to an empty line, then the resolution
+    loop {
+        let line = next_line()?;
+        if line.is_empty() {
+            break;
+        }
+        if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+            return Err(invalid("only the RGBE pixel format is supported"));
+        }
+    }
+    let resolution = next_line()?;
+    let fields: Vec<&str> = resolution.split_whitespace().collect();
+    let (height, width) = match fields.as_slice() {
+        ["-Y", h, "+X", w] => (h.parse::<u32>(), w.parse::<u32>()),
+        _ => return Err(invalid("only -Y H +X W image orientation is supported")),
+    };
+    let (height, width) = (height.map_err(|_| invalid("invalid height"))?, width.map_err(|_| invalid("invalid width"))?);
+
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    let mut scanline = vec![[0u8; 4]; width as usize];
+    for _ in 0..height {
+        pos = read_scanline(&data, pos, width as usize, &mut scanline)?;
+        pixels.extend(scanline.iter().map(|&rgbe| rgbe_to_rgb(rgbe)));
+    }
+    Ok(Image { width, height, pixels })
+}
@@ -0,0 +1,192 @@
+// KTX cubemap input and KTX2 output. Only uncompressed formats are handled, which covers the
+// HDR environment maps of the examples and everything the baker writes.
+
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use spirv_std::glam::{Vec2, Vec4};
+use common::packing::{f16_to_f32, f32_to_f16};
+
+use crate::cubemap::{Cubemap, Level};
+
+const KTX1_IDENTIFIER: [u8; 12] = [0xAB, b'K', b'T', b'X', b' ', b'1', b'1', 0xBB, b'\r', b'\n', 0x1A, b'\n'];
+const KTX2_IDENTIFIER: [u8; 12] = [0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n'];
+
+const GL_RGBA8: u32 = 0x8058;
+const GL_RGBA16F: u32 = 0x881A;
+const GL_RGBA32F: u32 = 0x8814;
+
+const VK_FORMAT_R16G16_SFLOAT: u32 = 83;
+const VK_FORMAT_R16G16B16A16_SFLOAT: u32 = 97;
+
+// Channel ids of the RGBSDA color model in the data format descriptor
+const CHANNEL_R: u8 = 0;
+const CHANNEL_G: u8 = 1;
+const CHANNEL_B: u8 = 2;
+const CHANNEL_A: u8 = 15;
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| invalid("truncated KTX file".to_string()))
+}
+
+/// Loads the first mip level of a KTX cubemap and generates the rest of the chain.
+pub fn load_cubemap(path: &str) -> Result<Cubemap> {
+    let data = fs::read(path)?;
+    if data.len() < 64 || data[..12] != KTX1_IDENTIFIER {
+        return Err(invalid(format!("{path} is not a KTX file")));
+    }
+    if read_u32(&data, 12)? != 0x0403_0201 {
+        return Err(invalid(format!("{path} is big endian")));
+    }
+    let internal_format = read_u32(&data, 28)?;
+    let width = read_u32(&data, 36)?;
+    let height = read_u32(&data, 40)?;
+    let faces = read_u32(&data, 52)?;
+    let key_value_bytes = read_u32(&data, 60)? as usize;
+    if faces != 6 || width != height {
+        return Err(invalid(format!("{path} is not a cubemap")));
+    }
+
+    let texel_size = match internal_format {
+        GL_RGBA8 => 4,
+        GL_RGBA16F => 8,
+        GL_RGBA32F => 16,
+        _ => return Err(invalid(format!("{path} has unsupported format 0x{internal_format:x}"))),
+    };
+    let decode = |t: &[u8]| -> Vec4 {
+        let channel = |i: usize| match internal_format {
+            GL_RGBA8 => t[i] as f32 / 255.0,
+            GL_RGBA16F => f16_to_f32(u16::from_le_bytes([t[i * 2], t[i * 2 + 1]]) as u32),
+            _ => f32::from_le_bytes([t[i * 4], t[i * 4 + 1], t[i * 4 + 2], t[i * 4 + 3]]),
+        };
+        Vec4::new(channel(0), channel(1), channel(2), channel(3))
+    };
+
+    // imageSize of the first level, followed by the faces each padded to four bytes
+    let mut offset = 64 + key_value_bytes;
+    let face_size = read_u32(&data, offset)? as usize;
+    offset += 4;
+    if face_size != (width * width) as usize * texel_size {
+        return Err(invalid(format!("{path} has an unexpected image size")));
+    }
+    let mut face_texels: [Vec<Vec4>; 6] = Default::default();
+    for texels in face_texels.iter_mut() {
+        let bytes = data.get(offset..offset + face_size).ok_or_else(|| invalid(format!("{path} is truncated")))?;
+        *texels = bytes.chunks_exact(texel_size).map(decode).collect();
+        offset += face_size.next_multiple_of(4);
+    }
+
+    let mut cubemap = Cubemap::from_level(Level { size: width, faces: face_texels });
+    cubemap.generate_mips();
+    Ok(cubemap)
+}
+
+// Basic data format descriptor for a linear float format with 16 bits per channel
+fn data_format_descriptor(channels: &[u8]) -> Vec<u8> {
+    let block_size = 24 + 16 * channels.len() as u32;
+    let mut dfd = Vec::new();
+    dfd.extend_from_slice(&(4 + block_size).to_le_bytes());
+    // Khronos vendor, basic descriptor type
+    dfd.extend_from_slice(&0u32.to_le_bytes());
+    dfd.extend_from_slice(&(2 | block_size << 16).to_le_bytes());
+    // RGBSDA color model, BT.709 primaries, linear transfer, straight alpha
+    dfd.extend_from_slice(&[1, 1, 1, 0]);
+    // 1x1x1x1 texel blocks
+    dfd.extend_from_slice(&[0, 0, 0, 0]);
+    let mut bytes_planes = [0u8; 8];
+    bytes_planes[0] = 2 * channels.len() as u8;
+    dfd.extend_from_slice(&bytes_planes);
+    for (i, &channel) in channels.iter().enumerate() {
+        dfd.extend_from_slice(&(16 * i as u16).to_le_bytes());
+        dfd.push(15);
+        // Float and signed qualifiers
+        dfd.push(channel | 0x80 | 0x40);
+        dfd.extend_from_slice(&[0, 0, 0, 0]);
+        dfd.extend_from_slice(&(-1.0f32).to_bits().to_le_bytes());
+        dfd.extend_from_slice(&1.0f32.to_bits().to_le_bytes());
+    }
+    dfd
+}
+
+// Writes a KTX2 file. Every level holds the texels of all faces, largest level first.
+fn write_ktx2(path: &str, vk_format: u32, channels: &[u8], size: u32, face_count: u32, levels: &[Vec<u8>]) -> Result<()> {
+    let texel_size = 2 * channels.len();
+    let dfd = data_format_descriptor(channels);
+    let level_index_offset = 80;
+    let dfd_offset = level_index_offset + 24 * levels.len();
+
+    // Level data is stored smallest level first, each aligned to the texel size
+    let mut level_offsets = vec![0; levels.len()];
+    let mut offset = dfd_offset + dfd.len();
+    for (i, level) in levels.iter().enumerate().rev() {
+        offset = offset.next_multiple_of(texel_size.max(4));
+        level_offsets[i] = offset;
+        offset += level.len();
+    }
+
+    let mut file = Vec::with_capacity(offset);
+    file.extend_from_slice(&KTX2_IDENTIFIER);
+    for value in [vk_format, 2, size, size, 0, 0, face_count, levels.len() as u32, 0] {
+        file.extend_from_slice(&value.to_le_bytes());
+    }
+    // Data format descriptor, no key/value data and no supercompression global data
+    for value in [dfd_offset as u32, dfd.len() as u32, 0, 0] {
+        file.extend_from_slice(&value.to_le_bytes());
+    }
+    file.extend_from_slice(&[0; 16]);
+    for (level, &level_offset) in levels.iter().zip(&level_offsets) {
+        for value in [level_offset as u64, level.len() as u64, level.len() as u64] {
+            file.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    file.extend_from_slice(&dfd);
+    for (i, level) in levels.iter().enumerate().rev() {
+        file.resize(level_offsets[i], 0);
+        file.extend_from_slice(level);
+    }
+    fs::write(path, file)
+}
+
+fn push_half(bytes: &mut Vec<u8>, value: f32) {
+    bytes.extend_from_slice(&(f32_to_f16(value) as u16).to_le_bytes());
+}
+
+/// Writes the BRDF LUT as a single level R16G16_SFLOAT texture.
+pub fn write_brdf_lut(path: &str, size: u32, lut: &[Vec2]) -> Result<()> {
+    let mut bytes = Vec::with_capacity(lut.len() * 4);
+    for texel in lut {
+        push_half(&mut bytes, texel.x);
+        push_half(&mut bytes, texel.y);
+    }
+    write_ktx2(path, VK_FORMAT_R16G16_SFLOAT, &[CHANNEL_R, CHANNEL_G], size, 1, &[bytes])
+}
+
+/// Writes all levels of a cubemap as R16G16B16A16_SFLOAT.
+pub fn write_cubemap(path: &str, cubemap: &Cubemap) -> Result<()> {
+    let levels: Vec<Vec<u8>> = cubemap
+        .levels
+        .iter()
+        .map(|level| {
+            let mut bytes = Vec::with_capacity(6 * level.faces[0].len() * 8);
+            for texel in level.faces.iter().flatten() {
+                for value in texel.to_array() {
+                    push_half(&mut bytes, value);
+                }
+            }
+            bytes
+        })
+        .collect();
+    write_ktx2(
+        path,
+        VK_FORMAT_R16G16B16A16_SFLOAT,
+        &[CHANNEL_R, CHANNEL_G, CHANNEL_B, CHANNEL_A],
+        cubemap.size(),
+        6,
+        &levels,
+    )
+}
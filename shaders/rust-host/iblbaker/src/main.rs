@@ -0,0 +1,111 @@
+// Offline baker for the image based lighting inputs of pbribl. Runs the BRDF and sampling
+// functions of pbribl/genbrdflut on the CPU and writes KTX2 files the examples can load
+// instead of generating them at startup. Doubles as a CPU reference for the GPU passes.
+//
+// Usage:
+//   iblbaker brdflut <output.ktx2> [--size 512] [--samples 1024]
+//   iblbaker irradiance <input.hdr|input.ktx> <output.ktx2> [--size 64]
+//   iblbaker prefiltered <input.hdr|input.ktx> <output.ktx2> [--size 512] [--samples 32]
+//
+// The tests in validate.rs check the GGX implementations of the shader crates numerically.
+//
+// Inputs are either an equirectangular Radiance HDR image or a KTX cubemap in rgba8,
+// rgba16f or rgba32f.
+
+mod bake;
+mod cubemap;
+mod hdr;
+mod ktx;
+#[cfg(test)]
+mod validate;
+
+use std::error::Error;
+use std::path::Path;
+use std::time::Instant;
+
+use cubemap::Cubemap;
+
+const USAGE: &str = "usage:
+  iblbaker brdflut <output.ktx2> [--size 512] [--samples 1024]
+  iblbaker irradiance <input.hdr|input.ktx> <output.ktx2> [--size 64]
+  iblbaker prefiltered <input.hdr|input.ktx> <output.ktx2> [--size 512] [--samples 32]";
+
+struct Args {
+    paths: Vec<String>,
+    size: Option<u32>,
+    samples: Option<u32>,
+}
+
+fn parse_args(args: &[String]) -> Result<Args, Box<dyn Error>> {
+    let mut parsed = Args { paths: Vec::new(), size: None, samples: None };
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--size" | "--samples" => {
+                let value = iter.next().ok_or_else(|| format!("missing value for {arg}"))?;
+                let value: u32 = value.parse().map_err(|_| format!("invalid value for {arg}: {value}"))?;
+                if value == 0 {
+                    return Err(format!("{arg} must be greater than zero").into());
+                }
+                if arg == "--size" {
+                    parsed.size = Some(value);
+                } else {
+                    parsed.samples = Some(value);
+                }
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}").into()),
+            _ => parsed.paths.push(arg.clone()),
+        }
+    }
+    Ok(parsed)
+}
+
+fn load_environment(path: &str) -> Result<Cubemap, Box<dyn Error>> {
+    let extension = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+    match extension.as_str() {
+        "hdr" => {
+            let image = hdr::load(path)?;
+            // Four faces around the equator cover the width of the panorama
+            let size = (image.width / 4).max(1).next_power_of_two();
+            Ok(Cubemap::from_equirectangular(&image, size))
+        }
+        "ktx" => Ok(ktx::load_cubemap(path)?),
+        _ => Err(format!("unsupported input {path}, expected .hdr or .ktx").into()),
+    }
+}
+
+fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let command = args.first().ok_or(USAGE)?;
+    let parsed = parse_args(&args[1..])?;
+    let start = Instant::now();
+
+    match (command.as_str(), parsed.paths.as_slice()) {
+        ("brdflut", [output]) => {
+            let size = parsed.size.unwrap_or(512);
+            let lut = bake::brdf_lut(size, parsed.samples.unwrap_or(1024));
+            ktx::write_brdf_lut(output, size, &lut)?;
+        }
+        ("irradiance", [input, output]) => {
+            let environment = load_environment(input)?;
+            let irradiance = bake::irradiance(&environment, parsed.size.unwrap_or(64));
+            ktx::write_cubemap(output, &irradiance)?;
+        }
+        ("prefiltered", [input, output]) => {
+            let environment = load_environment(input)?;
+            let prefiltered = bake::prefiltered(&environment, parsed.size.unwrap_or(512), parsed.samples.unwrap_or(32));
+            ktx::write_cubemap(output, &prefiltered)?;
+        }
+        _ => return Err(USAGE.into()),
+    }
+
+    println!("Baking {command} took {} ms", start.elapsed().as_millis());
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(err) = run(&args) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
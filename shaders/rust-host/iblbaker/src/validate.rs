@@ -0,0 +1,266 @@
+// Numerical checks of the GGX implementations in the shader crates, run on the CPU against
+// brute force integration over the hemisphere. Every failure names the crate it checks, so a
+// failing tolerance points straight at the implementation to look at.
+
+use core::f32::consts::PI;
+use rayon::prelude::*;
+use spirv_std::glam::{vec3, Vec3};
+
+// Roughness values to check at. Lower values put the whole lobe into a handful of quadrature
+// cells and would mostly test the integration itself.
+const ROUGHNESS: [f32; 4] = [0.25, 0.5, 0.75, 1.0];
+const NOV: [f32; 4] = [0.1, 0.4, 0.7, 1.0];
+
+// Quadrature resolution over the hemisphere
+const THETA_STEPS: u32 = 1024;
+const PHI_STEPS: u32 = 1024;
+
+type Ndf = fn(f32, f32) -> f32;
+type Geometry = fn(f32, f32, f32) -> f32;
+// BRDF times NoL for light direction, view direction, normal, metallic and roughness, with a
+// white base color
+type Brdf = fn(Vec3, Vec3, Vec3, f32, f32) -> Vec3;
+
+struct Implementation {
+    name: &'static str,
+    d: Option<Ndf>,
+    g: Option<Geometry>,
+    brdf: Option<Brdf>,
+}
+
+fn implementations() -> [Implementation; 4] {
+    [
+        Implementation {
+            name: "pbrbasic-pbr",
+            d: Some(pbrbasic_pbr::d_ggx),
+            g: Some(pbrbasic_pbr::g_schlicksmith_ggx),
+            brdf: Some(|l, v, n, metallic, roughness| {
+                let material = pbrbasic_pbr::FragmentPushConsts {
+                    _padding: [0.0; 3],
+                    roughness,
+                    metallic,
+                    r: 1.0,
+                    g: 1.0,
+                    b: 1.0,
+                };
+                pbrbasic_pbr::brdf(l, v, n, metallic, roughness, &material)
+            }),
+        },
+        Implementation {
+            name: "inlineuniformblocks-pbr",
+            d: Some(inlineuniformblocks_pbr::d_ggx),
+            g: Some(inlineuniformblocks_pbr::g_schlicksmith_ggx),
+            brdf: Some(|l, v, n, metallic, roughness| inlineuniformblocks_pbr::brdf(l, v, n, metallic, roughness, Vec3::ONE)),
+        },
+        Implementation {
+            name: "pbribl",
+            d: Some(pbribl::d_ggx),
+            g: Some(pbribl::g_schlicksmith_ggx),
+            brdf: Some(|l, v, n, metallic, roughness| {
+                let f0 = Vec3::splat(0.04).lerp(Vec3::ONE, metallic);
+                pbribl::specular_contribution(l, v, n, f0, metallic, roughness, Vec3::ONE)
+            }),
+        },
+        // The image based lighting pair, prefiltered with this D and integrated into the LUT with this G
+        Implementation {
+            name: "prefilterenvmap/genbrdflut",
+            d: Some(prefilterenvmap::d_ggx),
+            g: Some(genbrdflut::g_schlicksmith_ggx),
+            brdf: None,
+        },
+    ]
+}
+
+// Integral of f over the directions up to theta_max from +z, by the midpoint rule in spherical
+// coordinates
+fn integrate(theta_max: f32, f: impl Fn(Vec3) -> f32 + Sync) -> f32 {
+    let d_theta = theta_max / THETA_STEPS as f32;
+    let d_phi = 2.0 * PI / PHI_STEPS as f32;
+    let sum: f64 = (0..THETA_STEPS)
+        .into_par_iter()
+        .map(|i| {
+            let theta = (i as f32 + 0.5) * d_theta;
+            let (sin_theta, cos_theta) = theta.sin_cos();
+            let mut row = 0.0f64;
+            for j in 0..PHI_STEPS {
+                let phi = (j as f32 + 0.5) * d_phi;
+                let dir = vec3(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                row += f(dir) as f64;
+            }
+            row * sin_theta as f64
+        })
+        .sum();
+    (sum * (d_theta * d_phi) as f64) as f32
+}
+
+fn integrate_hemisphere(f: impl Fn(Vec3) -> f32 + Sync) -> f32 {
+    integrate(0.5 * PI, f)
+}
+
+fn view_direction(nov: f32) -> Vec3 {
+    vec3((1.0 - nov * nov).sqrt(), 0.0, nov)
+}
+
+// Reference split sum BRDF LUT entry for the model of genbrdflut: GGX with alpha = roughness^2,
+// Smith-Schlick visibility with k = alpha / 2 and Schlick Fresnel on VoH
+fn reference_brdf_lut(nov: f32, roughness: f32) -> (f32, f32) {
+    let v = view_direction(nov);
+    let alpha = roughness * roughness;
+    let alpha2 = alpha * alpha;
+    let k = alpha / 2.0;
+    let term = |l: Vec3| -> (f32, f32) {
+        let h = (v + l).normalize();
+        let nol = l.z;
+        let noh = h.z;
+        let voh = v.dot(h).max(0.0);
+        let denom = noh * noh * (alpha2 - 1.0) + 1.0;
+        let d = alpha2 / (PI * denom * denom);
+        let vis = 1.0 / ((nol * (1.0 - k) + k) * (nov * (1.0 - k) + k));
+        let f = d * vis / 4.0 * nol;
+        let fc = (1.0 - voh).powi(5);
+        (f * (1.0 - fc), f * fc)
+    };
+    (integrate_hemisphere(|l| term(l).0), integrate_hemisphere(|l| term(l).1))
+}
+
+// Failed checks of one test, reported together so a failing tolerance points at every
+// implementation that misses it
+#[derive(Default)]
+struct Failures(Vec<String>);
+
+impl Failures {
+    fn check(&mut self, crate_name: &str, at: &str, value: f32, passed: bool, expected: &str) {
+        if !passed {
+            self.0.push(format!("{crate_name} at {at}: {value:.5}, expected {expected}"));
+        }
+    }
+
+    fn assert_none(self) {
+        assert!(self.0.is_empty(), "{} checks failed:\n{}", self.0.len(), self.0.join("\n"));
+    }
+}
+
+fn at(roughness: f32, nov: f32) -> String {
+    format!("roughness {roughness:.2} NoV {nov:.1}")
+}
+
+// The projected microfacet area has to add up to the macro surface
+#[test]
+fn ndf_normalization() {
+    let mut failures = Failures::default();
+    for implementation in implementations() {
+        let Some(d) = implementation.d else { continue };
+        for roughness in ROUGHNESS {
+            let value = integrate_hemisphere(|h| d(h.z, roughness) * h.z);
+            let at = format!("roughness {roughness:.2}");
+            failures.check(implementation.name, &at, value, (value - 1.0).abs() < 0.01, "1 +- 0.01");
+        }
+    }
+    failures.assert_none();
+}
+
+// Smith masking of GGX for one direction, exact rather than the Schlick approximation the
+// shaders use
+fn smith_g1(nov: f32, roughness: f32) -> f32 {
+    let alpha2 = roughness.powi(4);
+    2.0 * nov / (nov + (alpha2 + (1.0 - alpha2) * nov * nov).sqrt())
+}
+
+// Weak white furnace: microfacets seen from v, masked by G1(v), cover the projected area of the
+// surface. Light reflected below the horizon counts as well, so this integrates over the whole
+// sphere.
+fn weak_furnace(d: Ndf, g1: f32, roughness: f32, nov: f32) -> f32 {
+    let v = view_direction(nov);
+    integrate(PI, |l| {
+        let h = (v + l).normalize();
+        if h.z > 0.0 {
+            d(h.z, roughness) * g1 / (4.0 * nov)
+        } else {
+            0.0
+        }
+    })
+}
+
+// With the exact Smith G1 the furnace is one for a correct D. The Schlick G of the shaders
+// masks more at grazing angles, with k = (roughness + 1)^2 / 8 for analytic lights (Karis 2013)
+// even more so, which loses energy but must never add any. G1 is G with the light along the
+// normal.
+#[test]
+fn white_furnace() {
+    let mut failures = Failures::default();
+    for implementation in implementations() {
+        let Some(d) = implementation.d else { continue };
+        for roughness in ROUGHNESS {
+            for nov in NOV {
+                let value = weak_furnace(d, smith_g1(nov, roughness), roughness, nov);
+                failures.check(implementation.name, &at(roughness, nov), value, (value - 1.0).abs() < 0.05, "1 +- 0.05 with Smith G1");
+                if let Some(g) = implementation.g {
+                    let value = weak_furnace(d, g(1.0, nov, roughness), roughness, nov);
+                    failures.check(implementation.name, &at(roughness, nov), value, value < 1.05, "< 1.05 with Schlick G1");
+                }
+            }
+        }
+    }
+    failures.assert_none();
+}
+
+// A white metal reflects at most all of the incoming light
+#[test]
+fn energy_conservation() {
+    let mut failures = Failures::default();
+    let n = vec3(0.0, 0.0, 1.0);
+    for implementation in implementations() {
+        let Some(brdf) = implementation.brdf else { continue };
+        for roughness in ROUGHNESS {
+            for nov in NOV {
+                let v = view_direction(nov);
+                let albedo = integrate_hemisphere(|l| brdf(l, v, n, 1.0, roughness).x);
+                failures.check(implementation.name, &at(roughness, nov), albedo, albedo <= 1.01, "<= 1.01");
+            }
+        }
+    }
+    failures.assert_none();
+}
+
+// Swapping light and view leaves the BRDF unchanged. All implementations evaluate Fresnel with
+// NoV like the GLSL originals, which isn't reciprocal, so this is checked on a white metal where
+// F is one and the diffuse part vanishes.
+#[test]
+fn reciprocity() {
+    let mut failures = Failures::default();
+    let n = vec3(0.0, 0.0, 1.0);
+    for implementation in implementations() {
+        let Some(brdf) = implementation.brdf else { continue };
+        for roughness in ROUGHNESS {
+            for nov in NOV {
+                let v = view_direction(nov);
+                let mut worst = 0.0f32;
+                for l in NOV.map(|nol| vec3(-(1.0 - nol * nol).sqrt(), 0.3 * nol, nol).normalize()) {
+                    let forward = brdf(l, v, n, 1.0, roughness).x / l.z;
+                    let backward = brdf(v, l, n, 1.0, roughness).x / v.z;
+                    worst = worst.max((forward - backward).abs() / forward.max(backward).max(1e-6));
+                }
+                failures.check(implementation.name, &at(roughness, nov), worst, worst < 0.01, "relative error < 0.01");
+            }
+        }
+    }
+    failures.assert_none();
+}
+
+// The importance sampled LUT against brute force integration of the same model, with the sample
+// count pbribl generates the LUT with
+#[test]
+fn brdf_lut() {
+    let mut failures = Failures::default();
+    for roughness in ROUGHNESS {
+        for nov in NOV {
+            let (scale, bias) = genbrdflut::brdf(nov, roughness, 1024).into();
+            let (ref_scale, ref_bias) = reference_brdf_lut(nov, roughness);
+            let error = (scale - ref_scale).abs().max((bias - ref_bias).abs());
+            failures.check("genbrdflut", &at(roughness, nov), error, error < 0.02, "absolute error < 0.02");
+            // With F = 1 scale and bias add up to the directional albedo of a white metal
+            failures.check("genbrdflut", &at(roughness, nov), scale + bias, scale + bias <= 1.01, "<= 1.01");
+        }
+    }
+    failures.assert_none();
+}
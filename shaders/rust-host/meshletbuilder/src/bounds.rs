@@ -0,0 +1,51 @@
+// Culling bounds of a meshlet: a bounding sphere around its vertices and a cone containing
+// the normals of its triangles, in the form common::meshlet::cone_culled expects.
+
+use spirv_std::glam::{Vec3, Vec4};
+
+// Cones wider than this can't cull anything useful and are stored as never culled
+const MIN_CONE_COS: f32 = 0.1;
+
+/// Ritter's bounding sphere, center in xyz and radius in w. Not minimal, but within a few
+/// percent of it and it always contains every point.
+pub fn bounding_sphere(points: &[Vec3]) -> Vec4 {
+    let Some(&first) = points.first() else {
+        return Vec4::ZERO;
+    };
+    let farthest = |from: Vec3| {
+        points.iter().copied().fold(from, |best, p| if p.distance_squared(from) > best.distance_squared(from) { p } else { best })
+    };
+    let a = farthest(first);
+    let b = farthest(a);
+    let mut center = (a + b) * 0.5;
+    let mut radius = a.distance(b) * 0.5;
+
+    // Grow the sphere to include points outside of it
+    for &p in points {
+        let distance = p.distance(center);
+        if distance > radius {
+            let new_radius = (radius + distance) * 0.5;
+            center += (p - center) * ((new_radius - radius) / distance);
+            radius = new_radius;
+        }
+    }
+    // Guard against rounding leaving a point just outside
+    let radius = points.iter().fold(radius, |r, p| r.max(p.distance(center)));
+    Vec4::from((center, radius))
+}
+
+/// Normal cone of the given face normals, axis in xyz and the sine of the half angle in w.
+/// Degenerate faces have no orientation and are ignored.
+pub fn normal_cone(face_normals: &[Vec3]) -> Vec4 {
+    let normals: Vec<Vec3> = face_normals.iter().filter_map(|n| n.try_normalize()).collect();
+    let axis = normals.iter().sum::<Vec3>().normalize_or_zero();
+    if normals.is_empty() || axis == Vec3::ZERO {
+        return Vec4::new(0.0, 0.0, 1.0, 1.0);
+    }
+
+    let min_cos = normals.iter().fold(1.0f32, |m, n| m.min(n.dot(axis)));
+    if min_cos <= MIN_CONE_COS {
+        return Vec4::from((axis, 1.0));
+    }
+    Vec4::from((axis, (1.0 - min_cos * min_cos).sqrt()))
+}
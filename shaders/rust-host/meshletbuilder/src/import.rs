@@ -0,0 +1,182 @@
+// Wavefront OBJ and glTF import. Only positions, normals and triangle connectivity are kept.
+// Meshes without normals get smooth normals computed from the faces.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use spirv_std::glam::{Mat3, Mat4, Vec3, Vec4};
+use common::meshlet::Vertex;
+
+use crate::mesh::Mesh;
+
+/// Loads an `.obj`, `.gltf` or `.glb` file.
+pub fn load(path: &str) -> Result<Mesh, Box<dyn Error>> {
+    let extension = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+    match extension.as_str() {
+        "obj" => load_obj(path),
+        "gltf" | "glb" => load_gltf(path),
+        _ => Err(format!("unsupported input {path}, expected .obj, .gltf or .glb").into()),
+    }
+}
+
+fn parse_floats<const N: usize>(fields: &[&str], line: usize) -> Result<[f32; N], Box<dyn Error>> {
+    let mut values = [0.0; N];
+    for (i, value) in values.iter_mut().enumerate() {
+        let field = fields.get(i).ok_or_else(|| format!("line {line}: expected {N} values"))?;
+        *value = field.parse().map_err(|_| format!("line {line}: invalid number {field}"))?;
+    }
+    Ok(values)
+}
+
+// OBJ indices are one based, negative ones count back from the end of the list
+fn resolve_index(field: &str, count: usize, line: usize) -> Result<usize, Box<dyn Error>> {
+    let index: i64 = field.parse().map_err(|_| format!("line {line}: invalid index {field}"))?;
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("line {line}: index {field} out of range").into());
+    }
+    Ok(resolved as usize)
+}
+
+pub fn load_obj(path: &str) -> Result<Mesh, Box<dyn Error>> {
+    let text = fs::read_to_string(path)?;
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut mesh = Mesh { vertices: Vec::new(), indices: Vec::new() };
+    // Each distinct position and normal pair becomes one vertex
+    let mut vertex_map: HashMap<(usize, Option<usize>), u32> = HashMap::new();
+    let mut missing_normals = false;
+
+    for (line_index, line) in text.lines().enumerate() {
+        let line_number = line_index + 1;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.first() {
+            Some(&"v") => positions.push(Vec3::from(parse_floats::<3>(&fields[1..], line_number)?)),
+            Some(&"vn") => normals.push(Vec3::from(parse_floats::<3>(&fields[1..], line_number)?)),
+            Some(&"f") => {
+                let mut face = Vec::with_capacity(fields.len() - 1);
+                for corner in &fields[1..] {
+                    // v, v/vt, v//vn or v/vt/vn
+                    let mut parts = corner.split('/');
+                    let position = resolve_index(parts.next().unwrap_or(""), positions.len(), line_number)?;
+                    let normal = match parts.nth(1) {
+                        Some(field) if !field.is_empty() => Some(resolve_index(field, normals.len(), line_number)?),
+                        _ => None,
+                    };
+                    missing_normals |= normal.is_none();
+                    let next_index = mesh.vertices.len() as u32;
+                    let index = *vertex_map.entry((position, normal)).or_insert(next_index);
+                    if index == next_index {
+                        let normal = normal.map_or(Vec3::ZERO, |n| normals[n]);
+                        mesh.vertices.push(Vertex {
+                            position: Vec4::from((positions[position], 1.0)),
+                            normal: Vec4::from((normal, 0.0)),
+                        });
+                    }
+                    face.push(index);
+                }
+                if face.len() < 3 {
+                    return Err(format!("line {line_number}: face with less than three vertices").into());
+                }
+                // Polygons are triangulated as a fan
+                for i in 1..face.len() - 1 {
+                    mesh.indices.extend([face[0], face[i], face[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if missing_normals {
+        mesh.compute_normals();
+    }
+    Ok(mesh)
+}
+
+fn load_buffers(document: &gltf::Document, blob: Option<Vec<u8>>, base: &Path) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+    let mut blob = blob;
+    let mut buffers = Vec::new();
+    for buffer in document.buffers() {
+        let data = match buffer.source() {
+            gltf::buffer::Source::Bin => blob.take().ok_or("missing binary chunk")?,
+            gltf::buffer::Source::Uri(uri) if uri.starts_with("data:") => {
+                return Err("embedded data URIs are not supported".into());
+            }
+            gltf::buffer::Source::Uri(uri) => fs::read(base.join(uri))?,
+        };
+        if data.len() < buffer.length() {
+            return Err(format!("buffer {} is shorter than declared", buffer.index()).into());
+        }
+        buffers.push(data);
+    }
+    Ok(buffers)
+}
+
+fn append_node(mesh: &mut Mesh, node: gltf::Node, parent: Mat4, buffers: &[Vec<u8>]) -> Result<(), Box<dyn Error>> {
+    let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+    if let Some(node_mesh) = node.mesh() {
+        let normal_matrix = Mat3::from_mat4(transform).inverse().transpose();
+        // Negative scale flips the winding
+        let flip = transform.determinant() < 0.0;
+        for primitive in node_mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                continue;
+            }
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| data.as_slice()));
+            let Some(positions) = reader.read_positions() else {
+                continue;
+            };
+            let mut part = Mesh {
+                vertices: positions
+                    .map(|p| Vertex {
+                        position: Vec4::from((transform.transform_point3(Vec3::from(p)), 1.0)),
+                        normal: Vec4::ZERO,
+                    })
+                    .collect(),
+                indices: Vec::new(),
+            };
+            part.indices = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..part.vertices.len() as u32).collect(),
+            };
+            part.indices.truncate(part.indices.len() / 3 * 3);
+            if part.indices.iter().any(|&index| index as usize >= part.vertices.len()) {
+                return Err(format!("mesh {} has out of range indices", node_mesh.index()).into());
+            }
+            if flip {
+                for triangle in part.indices.chunks_exact_mut(3) {
+                    triangle.swap(1, 2);
+                }
+            }
+            match reader.read_normals() {
+                Some(normals) => {
+                    for (vertex, normal) in part.vertices.iter_mut().zip(normals) {
+                        vertex.normal = Vec4::from(((normal_matrix * Vec3::from(normal)).normalize_or_zero(), 0.0));
+                    }
+                }
+                None => part.compute_normals(),
+            }
+            mesh.append(part);
+        }
+    }
+    for child in node.children() {
+        append_node(mesh, child, transform, buffers)?;
+    }
+    Ok(())
+}
+
+/// Loads all triangle primitives of the default scene, or the first scene if there is none,
+/// with node transforms applied.
+pub fn load_gltf(path: &str) -> Result<Mesh, Box<dyn Error>> {
+    let gltf = gltf::Gltf::open(path)?;
+    let base = Path::new(path).parent().unwrap_or(Path::new("."));
+    let buffers = load_buffers(&gltf.document, gltf.blob.clone(), base)?;
+    let scene = gltf.document.default_scene().or_else(|| gltf.document.scenes().next()).ok_or("no scene in file")?;
+
+    let mut mesh = Mesh { vertices: Vec::new(), indices: Vec::new() };
+    for node in scene.nodes() {
+        append_node(&mut mesh, node, Mat4::IDENTITY, &buffers)?;
+    }
+    Ok(mesh)
+}
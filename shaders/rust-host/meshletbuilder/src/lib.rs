@@ -0,0 +1,13 @@
+// Splits triangle meshes into meshlets for the meshshader example. The output uses the
+// layouts of common::meshlet, which the mesh and task shaders read the buffers with.
+
+pub mod bounds;
+pub mod import;
+pub mod mesh;
+pub mod output;
+pub mod partition;
+#[cfg(test)]
+mod validate;
+
+pub use mesh::Mesh;
+pub use partition::{build_meshlets, Meshlets};
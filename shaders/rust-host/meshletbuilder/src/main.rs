@@ -0,0 +1,86 @@
+// Offline meshlet builder for the meshshader example. Loads a mesh, partitions it into meshlets
+// and writes the vertex, meshlet, meshlet vertex and meshlet triangle buffers the mesh and task
+// shaders read, see output.rs for the file layout.
+//
+// Usage:
+//   meshletbuilder build <input.obj|input.gltf|input.glb> <output.meshlets> [--max-vertices 64] [--max-triangles 124]
+//
+// The tests in validate.rs check the partition and the culling bounds on generated meshes.
+
+use std::error::Error;
+use std::time::Instant;
+use common::meshlet::{MAX_MESHLET_TRIANGLES, MAX_MESHLET_VERTICES};
+use meshletbuilder::{build_meshlets, import, output};
+
+const USAGE: &str = "usage:
+  meshletbuilder build <input.obj|input.gltf|input.glb> <output.meshlets> [--max-vertices 64] [--max-triangles 124]";
+
+struct Args {
+    paths: Vec<String>,
+    max_vertices: usize,
+    max_triangles: usize,
+}
+
+fn parse_args(args: &[String]) -> Result<Args, Box<dyn Error>> {
+    let mut parsed = Args { paths: Vec::new(), max_vertices: MAX_MESHLET_VERTICES, max_triangles: MAX_MESHLET_TRIANGLES };
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--max-vertices" | "--max-triangles" => {
+                let value = iter.next().ok_or_else(|| format!("missing value for {arg}"))?;
+                let value: usize = value.parse().map_err(|_| format!("invalid value for {arg}: {value}"))?;
+                // The shaders size their outputs for the limits of common::meshlet
+                if arg == "--max-vertices" {
+                    if !(3..=MAX_MESHLET_VERTICES).contains(&value) {
+                        return Err(format!("{arg} must be between 3 and {MAX_MESHLET_VERTICES}").into());
+                    }
+                    parsed.max_vertices = value;
+                } else {
+                    if !(1..=MAX_MESHLET_TRIANGLES).contains(&value) {
+                        return Err(format!("{arg} must be between 1 and {MAX_MESHLET_TRIANGLES}").into());
+                    }
+                    parsed.max_triangles = value;
+                }
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}").into()),
+            _ => parsed.paths.push(arg.clone()),
+        }
+    }
+    Ok(parsed)
+}
+
+fn run(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let command = args.first().ok_or(USAGE)?;
+    let parsed = parse_args(&args[1..])?;
+    let start = Instant::now();
+
+    let (input, output_path) = match (command.as_str(), parsed.paths.as_slice()) {
+        ("build", [input, output_path]) => (input, output_path),
+        _ => return Err(USAGE.into()),
+    };
+    let mesh = import::load(input)?;
+    if mesh.triangle_count() == 0 {
+        return Err(format!("no triangles in {input}").into());
+    }
+    let meshlets = build_meshlets(&mesh, parsed.max_vertices, parsed.max_triangles);
+
+    let triangles_per_meshlet = mesh.triangle_count() as f32 / meshlets.meshlets.len() as f32;
+    let vertices_per_meshlet = meshlets.vertices.len() as f32 / meshlets.meshlets.len() as f32;
+    println!(
+        "{} triangles in {} meshlets, {triangles_per_meshlet:.1} triangles and {vertices_per_meshlet:.1} vertices per meshlet",
+        mesh.triangle_count(),
+        meshlets.meshlets.len(),
+    );
+
+    output::write(output_path, &mesh, &meshlets)?;
+    println!("Building meshlets took {} ms", start.elapsed().as_millis());
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(err) = run(&args) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
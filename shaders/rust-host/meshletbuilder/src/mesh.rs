@@ -0,0 +1,53 @@
+// Indexed triangle mesh as loaded from OBJ or glTF.
+
+use spirv_std::glam::{Vec3, Vec4};
+use common::meshlet::Vertex;
+
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    // Three indices per triangle, counter clockwise winding for front faces
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn position(&self, index: u32) -> Vec3 {
+        self.vertices[index as usize].position.truncate()
+    }
+
+    /// Geometric normal of a triangle, scaled by twice its area.
+    pub fn face_normal(&self, triangle: usize) -> Vec3 {
+        let [a, b, c] = self.triangle(triangle);
+        let p0 = self.position(a);
+        (self.position(b) - p0).cross(self.position(c) - p0)
+    }
+
+    pub fn triangle(&self, triangle: usize) -> [u32; 3] {
+        let i = triangle * 3;
+        [self.indices[i], self.indices[i + 1], self.indices[i + 2]]
+    }
+
+    /// Replaces the vertex normals with area weighted face normals, for inputs without normals.
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vec3::ZERO; self.vertices.len()];
+        for triangle in 0..self.triangle_count() {
+            let normal = self.face_normal(triangle);
+            for index in self.triangle(triangle) {
+                normals[index as usize] += normal;
+            }
+        }
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            vertex.normal = Vec4::from((normal.normalize_or_zero(), 0.0));
+        }
+    }
+
+    /// Appends another mesh, offsetting its indices.
+    pub fn append(&mut self, other: Mesh) {
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend(other.vertices);
+        self.indices.extend(other.indices.iter().map(|index| index + offset));
+    }
+}
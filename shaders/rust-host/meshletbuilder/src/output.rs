@@ -0,0 +1,68 @@
+// Binary meshlet file, little endian:
+//
+//   magic "MSHL", version, vertex count, meshlet count, meshlet vertex count and meshlet
+//   triangle count as u32
+//   vertices           common::meshlet::Vertex, 32 bytes each
+//   meshlets           common::meshlet::Meshlet, 48 bytes each
+//   meshlet vertices   u32 mesh vertex index
+//   meshlet triangles  u32 packed triangle
+//
+// Each section can be uploaded as is to the storage buffer of the same name in the meshlet
+// entry points of the meshshader crate.
+
+use std::fs;
+use std::io::Result;
+use std::mem::size_of;
+use spirv_std::glam::Vec4;
+use common::meshlet::{Meshlet, Vertex};
+
+use crate::mesh::Mesh;
+use crate::partition::Meshlets;
+
+pub const MAGIC: [u8; 4] = *b"MSHL";
+pub const VERSION: u32 = 1;
+
+// The file layout is the std430 layout of the shader structs
+const _: () = assert!(size_of::<Vertex>() == 32);
+const _: () = assert!(size_of::<Meshlet>() == 48);
+
+fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_vec4(bytes: &mut Vec<u8>, value: Vec4) {
+    for component in value.to_array() {
+        bytes.extend_from_slice(&component.to_le_bytes());
+    }
+}
+
+pub fn write(path: &str, mesh: &Mesh, meshlets: &Meshlets) -> Result<()> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&MAGIC);
+    push_u32(&mut bytes, VERSION);
+    push_u32(&mut bytes, mesh.vertices.len() as u32);
+    push_u32(&mut bytes, meshlets.meshlets.len() as u32);
+    push_u32(&mut bytes, meshlets.vertices.len() as u32);
+    push_u32(&mut bytes, meshlets.triangles.len() as u32);
+
+    for vertex in &mesh.vertices {
+        push_vec4(&mut bytes, vertex.position);
+        push_vec4(&mut bytes, vertex.normal);
+    }
+    for meshlet in &meshlets.meshlets {
+        push_vec4(&mut bytes, meshlet.bounding_sphere);
+        push_vec4(&mut bytes, meshlet.cone);
+        push_u32(&mut bytes, meshlet.vertex_offset);
+        push_u32(&mut bytes, meshlet.vertex_count);
+        push_u32(&mut bytes, meshlet.triangle_offset);
+        push_u32(&mut bytes, meshlet.triangle_count);
+    }
+    for &index in &meshlets.vertices {
+        push_u32(&mut bytes, index);
+    }
+    for &triangle in &meshlets.triangles {
+        push_u32(&mut bytes, triangle);
+    }
+
+    fs::write(path, bytes)
+}
@@ -0,0 +1,171 @@
+// Greedy meshlet partitioning. A meshlet grows by the adjacent triangle that adds the fewest
+// new vertices, preferring triangles facing the same way as the meshlet so far to keep the
+// normal cones narrow, until either limit is reached. Then a new meshlet is started from the
+// triangle that didn't fit, or from the first unassigned triangle if the last one was closed
+// off.
+
+use spirv_std::glam::Vec3;
+use common::meshlet::{pack_triangle, Meshlet, MAX_MESHLET_TRIANGLES, MAX_MESHLET_VERTICES};
+
+use crate::bounds::{bounding_sphere, normal_cone};
+use crate::mesh::Mesh;
+
+const UNASSIGNED: u32 = u32::MAX;
+
+pub struct Meshlets {
+    pub meshlets: Vec<Meshlet>,
+    // Mesh vertex index of each meshlet vertex, indexed by Meshlet::vertex_offset
+    pub vertices: Vec<u32>,
+    // Triangles packed with common::meshlet::pack_triangle, indexed by Meshlet::triangle_offset
+    pub triangles: Vec<u32>,
+}
+
+// Meshlet being built
+struct Current {
+    vertices: Vec<u32>,
+    triangles: Vec<[u32; 3]>,
+    faces: Vec<usize>,
+    normal_sum: Vec3,
+}
+
+// Vertex to triangle adjacency in compressed rows
+struct Adjacency {
+    offsets: Vec<usize>,
+    triangles: Vec<usize>,
+}
+
+impl Adjacency {
+    fn new(mesh: &Mesh) -> Self {
+        let mut offsets = vec![0; mesh.vertices.len() + 1];
+        for &index in &mesh.indices {
+            offsets[index as usize + 1] += 1;
+        }
+        for i in 1..offsets.len() {
+            offsets[i] += offsets[i - 1];
+        }
+        let mut fill = offsets.clone();
+        let mut triangles = vec![0; mesh.indices.len()];
+        for (i, &index) in mesh.indices.iter().enumerate() {
+            triangles[fill[index as usize]] = i / 3;
+            fill[index as usize] += 1;
+        }
+        Self { offsets, triangles }
+    }
+
+    fn of(&self, vertex: u32) -> &[usize] {
+        &self.triangles[self.offsets[vertex as usize]..self.offsets[vertex as usize + 1]]
+    }
+}
+
+fn new_vertex_count([a, b, c]: [u32; 3], local_index: &[u32]) -> usize {
+    let is_new = |index: u32| local_index[index as usize] == UNASSIGNED;
+    // Repeated indices of degenerate triangles only count once
+    is_new(a) as usize + (is_new(b) && b != a) as usize + (is_new(c) && c != a && c != b) as usize
+}
+
+/// Partitions `mesh` into meshlets of at most `max_vertices` vertices and `max_triangles`
+/// triangles, which must not exceed the limits of common::meshlet.
+pub fn build_meshlets(mesh: &Mesh, max_vertices: usize, max_triangles: usize) -> Meshlets {
+    assert!((3..=MAX_MESHLET_VERTICES).contains(&max_vertices), "max_vertices out of range");
+    assert!((1..=MAX_MESHLET_TRIANGLES).contains(&max_triangles), "max_triangles out of range");
+
+    let triangle_count = mesh.triangle_count();
+    let face_normals: Vec<Vec3> = (0..triangle_count).map(|t| mesh.face_normal(t)).collect();
+    let unit_normals: Vec<Vec3> = face_normals.iter().map(|n| n.normalize_or_zero()).collect();
+    let adjacency = Adjacency::new(mesh);
+
+    let mut output = Meshlets { meshlets: Vec::new(), vertices: Vec::new(), triangles: Vec::new() };
+    let mut assigned = vec![false; triangle_count];
+    let mut local_index = vec![UNASSIGNED; mesh.vertices.len()];
+    let mut current = Current { vertices: Vec::new(), triangles: Vec::new(), faces: Vec::new(), normal_sum: Vec3::ZERO };
+    let mut next_seed = 0;
+    let mut seed = None;
+
+    loop {
+        let candidate = if current.triangles.is_empty() {
+            seed.take().or_else(|| {
+                while next_seed < triangle_count && assigned[next_seed] {
+                    next_seed += 1;
+                }
+                (next_seed < triangle_count).then_some(next_seed)
+            })
+        } else {
+            let axis = current.normal_sum.normalize_or_zero();
+            let mut best: Option<(usize, usize, f32)> = None;
+            for &vertex in &current.vertices {
+                for &triangle in adjacency.of(vertex) {
+                    if assigned[triangle] {
+                        continue;
+                    }
+                    let new_vertices = new_vertex_count(mesh.triangle(triangle), &local_index);
+                    let alignment = unit_normals[triangle].dot(axis);
+                    let better = match best {
+                        None => true,
+                        Some((_, best_new, best_alignment)) => {
+                            new_vertices < best_new || (new_vertices == best_new && alignment > best_alignment)
+                        }
+                    };
+                    if better {
+                        best = Some((triangle, new_vertices, alignment));
+                    }
+                }
+            }
+            best.map(|(triangle, _, _)| triangle)
+        };
+
+        let Some(triangle) = candidate else {
+            if current.triangles.is_empty() {
+                break;
+            }
+            finish_meshlet(mesh, &face_normals, &mut current, &mut local_index, &mut output);
+            continue;
+        };
+
+        let indices = mesh.triangle(triangle);
+        let fits = current.vertices.len() + new_vertex_count(indices, &local_index) <= max_vertices
+            && current.triangles.len() < max_triangles;
+        if !fits {
+            finish_meshlet(mesh, &face_normals, &mut current, &mut local_index, &mut output);
+            seed = Some(triangle);
+            continue;
+        }
+
+        let mut local = [0; 3];
+        for (slot, &index) in local.iter_mut().zip(&indices) {
+            if local_index[index as usize] == UNASSIGNED {
+                local_index[index as usize] = current.vertices.len() as u32;
+                current.vertices.push(index);
+            }
+            *slot = local_index[index as usize];
+        }
+        current.triangles.push(local);
+        current.faces.push(triangle);
+        current.normal_sum += unit_normals[triangle];
+        assigned[triangle] = true;
+    }
+
+    output
+}
+
+fn finish_meshlet(mesh: &Mesh, face_normals: &[Vec3], current: &mut Current, local_index: &mut [u32], output: &mut Meshlets) {
+    let positions: Vec<Vec3> = current.vertices.iter().map(|&index| mesh.position(index)).collect();
+    let normals: Vec<Vec3> = current.faces.iter().map(|&face| face_normals[face]).collect();
+    output.meshlets.push(Meshlet {
+        bounding_sphere: bounding_sphere(&positions),
+        cone: normal_cone(&normals),
+        vertex_offset: output.vertices.len() as u32,
+        vertex_count: current.vertices.len() as u32,
+        triangle_offset: output.triangles.len() as u32,
+        triangle_count: current.triangles.len() as u32,
+    });
+    output.vertices.extend_from_slice(&current.vertices);
+    output.triangles.extend(current.triangles.iter().map(|&[a, b, c]| pack_triangle(a, b, c)));
+
+    for &index in &current.vertices {
+        local_index[index as usize] = UNASSIGNED;
+    }
+    current.vertices.clear();
+    current.triangles.clear();
+    current.faces.clear();
+    current.normal_sum = Vec3::ZERO;
+}
@@ -0,0 +1,226 @@
+// Checks of a meshlet partition on generated meshes: every triangle of the mesh is in exactly
+// one meshlet, the meshlets stay within their limits and the normal cone test never culls a
+// meshlet with a triangle facing the camera.
+
+use std::collections::HashMap;
+use core::f32::consts::{PI, TAU};
+use spirv_std::glam::{vec3, Vec3, Vec4};
+use common::meshlet::{cone_culled, unpack_triangle, Vertex, MAX_MESHLET_TRIANGLES, MAX_MESHLET_VERTICES};
+
+use crate::mesh::Mesh;
+use crate::partition::{build_meshlets, Meshlets};
+
+// Camera positions tried per meshlet in the cone check
+const CAMERA_SAMPLES: u32 = 256;
+// Relative tolerance for triangles seen exactly edge on
+const FACING_EPSILON: f32 = 1e-4;
+
+/// Returns a description of every problem found in the partition.
+fn check_coverage(mesh: &Mesh, meshlets: &Meshlets, max_vertices: usize, max_triangles: usize) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    // Triangles are compared by their mesh indices in order, so a changed winding is an error too
+    let mut remaining: HashMap<[u32; 3], i64> = HashMap::new();
+    for triangle in 0..mesh.triangle_count() {
+        *remaining.entry(mesh.triangle(triangle)).or_default() += 1;
+    }
+
+    for (m, meshlet) in meshlets.meshlets.iter().enumerate() {
+        let vertex_count = meshlet.vertex_count as usize;
+        let triangle_count = meshlet.triangle_count as usize;
+        if vertex_count > max_vertices || triangle_count > max_triangles {
+            errors.push(format!("meshlet {m}: {vertex_count} vertices and {triangle_count} triangles exceed the limits"));
+        }
+        let vertices = &meshlets.vertices[meshlet.vertex_offset as usize..][..vertex_count];
+        let triangles = &meshlets.triangles[meshlet.triangle_offset as usize..][..triangle_count];
+        for &packed in triangles {
+            let local = unpack_triangle(packed);
+            if local.max_element() as usize >= vertex_count {
+                errors.push(format!("meshlet {m}: local index out of range in triangle {packed:#08x}"));
+                continue;
+            }
+            let triangle = [vertices[local.x as usize], vertices[local.y as usize], vertices[local.z as usize]];
+            match remaining.get_mut(&triangle) {
+                Some(count) => *count -= 1,
+                None => errors.push(format!("meshlet {m}: triangle {triangle:?} is not in the mesh")),
+            }
+        }
+    }
+
+    for (triangle, count) in remaining {
+        if count > 0 {
+            errors.push(format!("triangle {triangle:?} is missing from the meshlets"));
+        } else if count < 0 {
+            errors.push(format!("triangle {triangle:?} is in more than one meshlet"));
+        }
+    }
+    errors
+}
+
+// xorshift, deterministic so failures can be reproduced
+fn random(state: &mut u32) -> f32 {
+    *state ^= *state << 13;
+    *state ^= *state >> 17;
+    *state ^= *state << 5;
+    (*state >> 8) as f32 / (1 << 24) as f32
+}
+
+fn random_direction(state: &mut u32) -> Vec3 {
+    let z = random(state) * 2.0 - 1.0;
+    let phi = random(state) * TAU;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Places cameras around each meshlet, mostly behind its normal cone where the test can cull,
+/// and returns the problems found along with the number of culled samples and all samples.
+fn check_cone_culling(mesh: &Mesh, meshlets: &Meshlets) -> (Vec<String>, u32, u32) {
+    let mut errors = Vec::new();
+    let mut culled_samples = 0;
+    let mut state = 0x2545_f491;
+
+    for (m, meshlet) in meshlets.meshlets.iter().enumerate() {
+        let center = meshlet.bounding_sphere.truncate();
+        let radius = meshlet.bounding_sphere.w;
+        let axis = meshlet.cone.truncate();
+        let vertices = &meshlets.vertices[meshlet.vertex_offset as usize..][..meshlet.vertex_count as usize];
+        let triangles = &meshlets.triangles[meshlet.triangle_offset as usize..][..meshlet.triangle_count as usize];
+
+        for sample in 0..CAMERA_SAMPLES {
+            let distance = radius.max(1e-3) * (1.0 + 20.0 * random(&mut state));
+            // Half of the samples directly behind the cone, the rest anywhere
+            let direction = if sample % 2 == 0 {
+                (-axis + random_direction(&mut state) * random(&mut state)).normalize_or_zero()
+            } else {
+                random_direction(&mut state)
+            };
+            let camera = center + direction * distance;
+            if !cone_culled(center, radius, meshlet.cone, camera) {
+                continue;
+            }
+            culled_samples += 1;
+
+            for &packed in triangles {
+                let local = unpack_triangle(packed);
+                let p0 = mesh.position(vertices[local.x as usize]);
+                let p1 = mesh.position(vertices[local.y as usize]);
+                let p2 = mesh.position(vertices[local.z as usize]);
+                let Some(normal) = (p1 - p0).cross(p2 - p0).try_normalize() else {
+                    continue;
+                };
+                let to_camera = camera - p0;
+                if normal.dot(to_camera) > FACING_EPSILON * to_camera.length() {
+                    errors.push(format!("meshlet {m}: culled from {camera} with a triangle facing the camera"));
+                    break;
+                }
+            }
+        }
+    }
+    (errors, culled_samples, meshlets.meshlets.len() as u32 * CAMERA_SAMPLES)
+}
+
+fn mesh(positions: &[Vec3], indices: Vec<u32>) -> Mesh {
+    let vertices = positions.iter().map(|&p| Vertex { position: Vec4::from((p, 1.0)), normal: Vec4::ZERO }).collect();
+    let mut mesh = Mesh { vertices, indices };
+    mesh.compute_normals();
+    mesh
+}
+
+// n by n quads in the xy plane, facing +z
+fn grid(n: u32) -> Mesh {
+    let positions: Vec<Vec3> = (0..=n).flat_map(|y| (0..=n).map(move |x| vec3(x as f32, y as f32, 0.0))).collect();
+    let mut indices = Vec::new();
+    for y in 0..n {
+        for x in 0..n {
+            let i = y * (n + 1) + x;
+            indices.extend([i, i + 1, i + n + 2, i, i + n + 2, i + n + 1]);
+        }
+    }
+    mesh(&positions, indices)
+}
+
+// Unit sphere of rings by segments quads, facing outwards. The quads at the poles collapse to a
+// single triangle.
+fn sphere(rings: u32, segments: u32) -> Mesh {
+    let positions: Vec<Vec3> = (0..=rings)
+        .flat_map(|ring| {
+            let theta = ring as f32 / rings as f32 * PI;
+            (0..=segments).map(move |segment| {
+                let phi = segment as f32 / segments as f32 * TAU;
+                vec3(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin())
+            })
+        })
+        .collect();
+    let mut indices = Vec::new();
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let i = ring * (segments + 1) + segment;
+            let j = i + segments + 1;
+            if ring != 0 {
+                indices.extend([i, i + 1, j]);
+            }
+            if ring != rings - 1 {
+                indices.extend([i + 1, j + 1, j]);
+            }
+        }
+    }
+    mesh(&positions, indices)
+}
+
+fn check(name: &str, mesh: &Mesh, max_vertices: usize, max_triangles: usize) -> u32 {
+    let meshlets = build_meshlets(mesh, max_vertices, max_triangles);
+    let coverage_errors = check_coverage(mesh, &meshlets, max_vertices, max_triangles);
+    assert!(coverage_errors.is_empty(), "{name}, {max_vertices}/{max_triangles}: {coverage_errors:#?}");
+    let (cone_errors, culled, _) = check_cone_culling(mesh, &meshlets);
+    assert!(cone_errors.is_empty(), "{name}, {max_vertices}/{max_triangles}: {cone_errors:#?}");
+    culled
+}
+
+const LIMITS: [(usize, usize); 3] = [(MAX_MESHLET_VERTICES, MAX_MESHLET_TRIANGLES), (16, 16), (3, 1)];
+
+#[test]
+fn grid_meshlets() {
+    let mesh = grid(32);
+    for (max_vertices, max_triangles) in LIMITS {
+        // A flat grid has a zero width cone, so cameras behind it always cull
+        assert!(check("grid", &mesh, max_vertices, max_triangles) > 0);
+    }
+}
+
+#[test]
+fn sphere_meshlets() {
+    let mesh = sphere(24, 48);
+    // The sphere faces outwards, the partition test only compares indices
+    for triangle in 0..mesh.triangle_count() {
+        let [a, _, _] = mesh.triangle(triangle);
+        assert!(mesh.face_normal(triangle).dot(mesh.position(a)) > 0.0, "triangle {triangle}");
+    }
+    for (max_vertices, max_triangles) in LIMITS {
+        assert!(check("sphere", &mesh, max_vertices, max_triangles) > 0);
+    }
+}
+
+#[test]
+fn degenerate_triangles() {
+    // Triangles with repeated indices, collinear and coincident vertices mixed into a grid, and
+    // a triangle listed twice
+    let with_degenerate = |grid_indices: bool| {
+        let mut mesh = grid(8);
+        let copy = mesh.vertices.len() as u32;
+        mesh.vertices.push(mesh.vertices[0]);
+        if !grid_indices {
+            mesh.indices.clear();
+        }
+        mesh.indices.extend([0, 0, 1, 2, 2, 2, 0, 1, 2, 0, copy, 1]);
+        if grid_indices {
+            mesh.indices.extend([0, 1, 10]);
+        }
+        mesh
+    };
+    let mesh = with_degenerate(true);
+    let only_degenerate = with_degenerate(false);
+    for (max_vertices, max_triangles) in LIMITS {
+        check("grid with degenerate triangles", &mesh, max_vertices, max_triangles);
+        check("degenerate triangles", &only_degenerate, max_vertices, max_triangles);
+    }
+}
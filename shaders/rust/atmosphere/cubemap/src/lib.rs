@@ -0,0 +1,40 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Renders the sky view LUT into the faces of a cubemap, bound as a 2D array storage image
+// with one layer per face. The result can replace the static environment cubemap consumed by
+// pbribl/irradiancecube and pbribl/prefilterenvmap. The sun disk is left out, as its tiny
+// bright area only adds noise to the filtered maps; light scenes with it directly instead.
+
+use spirv_std::glam::{ivec3, vec2, vec4, UVec3};
+use spirv_std::{spirv, Image};
+use common::atmosphere::{sky_luminance, AtmosphereUBO, Lut};
+use common::cubemap::face_direction;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PushConsts {
+    pub face_size: u32,
+}
+
+#[spirv(compute(threads(8, 8, 1)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &AtmosphereUBO,
+    #[spirv(descriptor_set = 0, binding = 1)] sky_view_lut: &Lut,
+    #[spirv(descriptor_set = 0, binding = 2)] cubemap: &Image!(2D, format=rgba16f, sampled=false, arrayed),
+    #[spirv(push_constant)] push_consts: &PushConsts,
+) {
+    if global_id.x >= push_consts.face_size || global_id.y >= push_consts.face_size || global_id.z >= 6 {
+        return;
+    }
+    let uv = (vec2(global_id.x as f32, global_id.y as f32) + 0.5) / push_consts.face_size as f32;
+    let dir = face_direction(global_id.z, uv);
+    let color = sky_luminance(sky_view_lut, ubo, dir);
+
+    unsafe {
+        cubemap.write(
+            ivec3(global_id.x as i32, global_id.y as i32, global_id.z as i32),
+            vec4(color.x, color.y, color.z, 1.0),
+        );
+    }
+}
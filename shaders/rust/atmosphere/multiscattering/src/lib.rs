@@ -0,0 +1,102 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Multiple scattering LUT: approximates the luminance of all scattering orders beyond the
+// second one by an isotropic transfer function (Hillaire 2020, section 5.5), parameterized
+// by height and sun zenith angle. Needs the transmittance LUT and the ground albedo.
+
+use core::f32::consts::PI;
+use spirv_std::glam::{ivec2, vec2, vec3, vec4, UVec3, Vec3, Vec4Swizzles};
+use spirv_std::{num_traits::Float, spirv, Image};
+use common::atmosphere::{
+    beer_lambert, ray_sphere, sun_transmittance, AtmosphereUBO, Lut, Medium, BOTTOM_RADIUS, MULTI_SCATTERING_LUT_SIZE,
+    TOP_RADIUS,
+};
+
+// Directions are taken from a SQRT_SAMPLES x SQRT_SAMPLES grid over the sphere
+const SQRT_SAMPLES: u32 = 8;
+const STEPS: u32 = 20;
+
+// Second order luminance and the transfer factor f_ms along one direction, for unit sun illuminance
+fn integrate(pos: Vec3, dir: Vec3, sun: Vec3, ubo: &AtmosphereUBO, transmittance_lut: &Lut) -> (Vec3, Vec3) {
+    let t_ground = ray_sphere(pos, dir, BOTTOM_RADIUS);
+    let t_max = if t_ground > 0.0 {
+        t_ground
+    } else {
+        ray_sphere(pos, dir, TOP_RADIUS).max(0.0)
+    };
+    let dt = t_max / STEPS as f32;
+
+    let mut luminance = Vec3::ZERO;
+    let mut transfer = Vec3::ZERO;
+    let mut throughput = Vec3::ONE;
+    for i in 0..STEPS {
+        let p = pos + dir * ((i as f32 + 0.5) * dt);
+        let medium = Medium::at(p.length() - BOTTOM_RADIUS);
+        let scattering = medium.scattering();
+        let extinction = medium.extinction.max(Vec3::splat(0.000001));
+        let sample_transmittance = beer_lambert(medium.extinction * dt);
+
+        // Sun light, unless the planet is in the way, scattered isotropically
+        let shadow = if ray_sphere(p, sun, BOTTOM_RADIUS) > 0.0 { 0.0 } else { 1.0 };
+        let s = sun_transmittance(transmittance_lut, p, sun) * shadow * scattering / (4.0 * PI);
+
+        // Analytic integration over the step
+        luminance += throughput * (s - s * sample_transmittance) / extinction;
+        transfer += throughput * (scattering - scattering * sample_transmittance) / extinction;
+        throughput *= sample_transmittance;
+    }
+
+    // Light reflected by a lambertian ground
+    if t_ground > 0.0 {
+        let p = pos + dir * t_ground;
+        let n = p.normalize();
+        luminance += throughput * sun_transmittance(transmittance_lut, p, sun) * n.dot(sun).max(0.0) * ubo.ground_albedo.xyz() / PI;
+    }
+
+    (luminance, transfer)
+}
+
+#[spirv(compute(threads(8, 8)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &AtmosphereUBO,
+    #[spirv(descriptor_set = 0, binding = 1)] transmittance_lut: &Lut,
+    #[spirv(descriptor_set = 0, binding = 2)] multi_scattering_lut: &Image!(2D, format=rgba16f, sampled=false),
+) {
+    let size = MULTI_SCATTERING_LUT_SIZE.as_uvec2();
+    if global_id.x >= size.x || global_id.y >= size.y {
+        return;
+    }
+    let uv = (vec2(global_id.x as f32, global_id.y as f32) + 0.5) / MULTI_SCATTERING_LUT_SIZE;
+    let cos_sun_zenith = uv.x * 2.0 - 1.0;
+    let height = BOTTOM_RADIUS + uv.y * (TOP_RADIUS - BOTTOM_RADIUS);
+
+    let pos = vec3(0.0, height.max(BOTTOM_RADIUS + 0.01), 0.0);
+    let sun = vec3((1.0 - cos_sun_zenith * cos_sun_zenith).max(0.0).sqrt(), cos_sun_zenith, 0.0);
+
+    // Average over uniformly distributed directions, with the isotropic phase function
+    // the integral over the sphere becomes a plain mean
+    let mut luminance = Vec3::ZERO;
+    let mut transfer = Vec3::ZERO;
+    for i in 0..SQRT_SAMPLES {
+        for j in 0..SQRT_SAMPLES {
+            let theta = 2.0 * PI * (i as f32 + 0.5) / SQRT_SAMPLES as f32;
+            let cos_phi = 1.0 - 2.0 * (j as f32 + 0.5) / SQRT_SAMPLES as f32;
+            let sin_phi = (1.0 - cos_phi * cos_phi).max(0.0).sqrt();
+            let dir = vec3(theta.cos() * sin_phi, cos_phi, theta.sin() * sin_phi);
+
+            let (l, f) = integrate(pos, dir, sun, ubo, transmittance_lut);
+            luminance += l;
+            transfer += f;
+        }
+    }
+    let sample_count = (SQRT_SAMPLES * SQRT_SAMPLES) as f32;
+    luminance /= sample_count;
+    transfer /= sample_count;
+
+    // Sum of the geometric series of all higher scattering orders
+    let psi = luminance / (Vec3::ONE - transfer);
+    unsafe {
+        multi_scattering_lut.write(ivec2(global_id.x as i32, global_id.y as i32), vec4(psi.x, psi.y, psi.z, 1.0));
+    }
+}
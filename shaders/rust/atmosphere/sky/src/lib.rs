@@ -0,0 +1,48 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Fullscreen sky: looks up the sky view LUT along the view ray and adds the sun disk.
+// Replaces sampling a static skybox cubemap, draw it before the scene or with depth testing
+// against the far plane.
+
+use core::f32::consts::PI;
+use spirv_std::glam::{vec2, vec4, Vec2, Vec4, Vec4Swizzles};
+use spirv_std::{num_traits::Float, spirv};
+use common::atmosphere::{ray_sphere, sky_luminance, sun_transmittance, AtmosphereUBO, Lut, BOTTOM_RADIUS};
+
+#[spirv(vertex)]
+pub fn main_vs(
+    #[spirv(vertex_index)] vert_index: i32,
+    #[spirv(position)] out_position: &mut Vec4,
+    out_uv: &mut Vec2,
+) {
+    let uv = vec2(((vert_index << 1) & 2) as f32, (vert_index & 2) as f32);
+    *out_uv = uv;
+    *out_position = vec4(uv.x * 2.0 - 1.0, uv.y * 2.0 - 1.0, 0.0, 1.0);
+}
+
+#[spirv(fragment)]
+pub fn main_fs(
+    in_uv: Vec2,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &AtmosphereUBO,
+    #[spirv(descriptor_set = 0, binding = 1)] transmittance_lut: &Lut,
+    #[spirv(descriptor_set = 0, binding = 2)] sky_view_lut: &Lut,
+    out_frag_color: &mut Vec4,
+) {
+    // World space view ray through the pixel
+    let ndc = in_uv * 2.0 - 1.0;
+    let near = ubo.inverse_view_projection * vec4(ndc.x, ndc.y, 0.0, 1.0);
+    let far = ubo.inverse_view_projection * vec4(ndc.x, ndc.y, 1.0, 1.0);
+    let dir = (far.xyz() / far.w - near.xyz() / near.w).normalize();
+
+    let mut color = sky_luminance(sky_view_lut, ubo, dir);
+
+    // Sun disk, unless it is below the horizon
+    let sun = ubo.sun_direction.xyz().normalize();
+    let pos = ubo.camera_position();
+    if dir.dot(sun) > ubo.sun_angular_radius.cos() && ray_sphere(pos, dir, BOTTOM_RADIUS) < 0.0 {
+        let solid_angle = PI * ubo.sun_angular_radius * ubo.sun_angular_radius;
+        color += sun_transmittance(transmittance_lut, pos, sun) * ubo.sun_illuminance.xyz() / solid_angle;
+    }
+
+    *out_frag_color = vec4(color.x, color.y, color.z, 1.0);
+}
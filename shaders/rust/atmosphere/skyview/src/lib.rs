@@ -0,0 +1,78 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Sky view LUT: sky luminance around the camera, parameterized by view zenith angle and
+// azimuth relative to the sun. Recomputed whenever the sun or the camera height changes,
+// then sampled by atmosphere/sky and atmosphere/cubemap.
+
+use spirv_std::glam::{ivec2, vec2, vec3, vec4, UVec3, Vec3, Vec4Swizzles};
+use spirv_std::{num_traits::Float, spirv, Image};
+use common::atmosphere::{
+    beer_lambert, mie_phase, multi_scattering, ray_sphere, rayleigh_phase, sky_view_lut_params, sun_transmittance,
+    AtmosphereUBO, Lut, Medium, BOTTOM_RADIUS, SKY_VIEW_LUT_SIZE, TOP_RADIUS,
+};
+
+const STEPS: u32 = 30;
+
+#[spirv(compute(threads(8, 8)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &AtmosphereUBO,
+    #[spirv(descriptor_set = 0, binding = 1)] transmittance_lut: &Lut,
+    #[spirv(descriptor_set = 0, binding = 2)] multi_scattering_lut: &Lut,
+    #[spirv(descriptor_set = 0, binding = 3)] sky_view_lut: &Image!(2D, format=rgba16f, sampled=false),
+) {
+    let size = SKY_VIEW_LUT_SIZE.as_uvec2();
+    if global_id.x >= size.x || global_id.y >= size.y {
+        return;
+    }
+    let uv = (vec2(global_id.x as f32, global_id.y as f32) + 0.5) / SKY_VIEW_LUT_SIZE;
+    let pos = ubo.camera_position();
+    let (view_zenith_cos, light_view_cos) = sky_view_lut_params(uv, pos.length());
+
+    // Local frame with the sun in the x/y plane
+    let sun_world = ubo.sun_direction.xyz().normalize();
+    let sun = vec3((1.0 - sun_world.y * sun_world.y).max(0.0).sqrt(), sun_world.y, 0.0);
+    let view_zenith_sin = (1.0 - view_zenith_cos * view_zenith_cos).max(0.0).sqrt();
+    let light_view_sin = (1.0 - light_view_cos * light_view_cos).max(0.0).sqrt();
+    let dir = vec3(view_zenith_sin * light_view_cos, view_zenith_cos, view_zenith_sin * light_view_sin);
+
+    let t_ground = ray_sphere(pos, dir, BOTTOM_RADIUS);
+    let t_max = if t_ground > 0.0 {
+        t_ground
+    } else {
+        ray_sphere(pos, dir, TOP_RADIUS).max(0.0)
+    };
+    let dt = t_max / STEPS as f32;
+
+    let cos_theta = dir.dot(sun);
+    let phase_rayleigh = rayleigh_phase(cos_theta);
+    let phase_mie = mie_phase(cos_theta);
+
+    let mut luminance = Vec3::ZERO;
+    let mut throughput = Vec3::ONE;
+    for i in 0..STEPS {
+        let p = pos + dir * ((i as f32 + 0.5) * dt);
+        let medium = Medium::at(p.length() - BOTTOM_RADIUS);
+        let extinction = medium.extinction.max(Vec3::splat(0.000001));
+        let sample_transmittance = beer_lambert(medium.extinction * dt);
+
+        // Single scattering of the sun light plus the multiple scattering approximation
+        let shadow = if ray_sphere(p, sun, BOTTOM_RADIUS) > 0.0 { 0.0 } else { 1.0 };
+        let single = sun_transmittance(transmittance_lut, p, sun)
+            * shadow
+            * (medium.rayleigh_scattering * phase_rayleigh + Vec3::splat(medium.mie_scattering * phase_mie));
+        let multiple = multi_scattering(multi_scattering_lut, p, sun) * medium.scattering();
+        let s = single + multiple;
+
+        luminance += throughput * (s - s * sample_transmittance) / extinction;
+        throughput *= sample_transmittance;
+    }
+    luminance *= ubo.sun_illuminance.xyz();
+
+    unsafe {
+        sky_view_lut.write(
+            ivec2(global_id.x as i32, global_id.y as i32),
+            vec4(luminance.x, luminance.y, luminance.z, 1.0),
+        );
+    }
+}
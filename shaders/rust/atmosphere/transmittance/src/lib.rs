@@ -0,0 +1,45 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Transmittance LUT: transmittance from a point in the atmosphere to its top along a view
+// direction, parameterized by height and view zenith angle. Only changes with the atmosphere
+// parameters, so it can be computed once.
+
+use spirv_std::glam::{ivec2, vec2, vec3, vec4, UVec3, Vec3};
+use spirv_std::{num_traits::Float, spirv, Image};
+use common::atmosphere::{
+    beer_lambert, ray_sphere, transmittance_lut_params, Medium, BOTTOM_RADIUS, TOP_RADIUS, TRANSMITTANCE_LUT_SIZE,
+};
+
+const STEPS: u32 = 40;
+
+#[spirv(compute(threads(8, 8)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(descriptor_set = 0, binding = 0)] transmittance_lut: &Image!(2D, format=rgba16f, sampled=false),
+) {
+    let size = TRANSMITTANCE_LUT_SIZE.as_uvec2();
+    if global_id.x >= size.x || global_id.y >= size.y {
+        return;
+    }
+    let uv = (vec2(global_id.x as f32, global_id.y as f32) + 0.5) / TRANSMITTANCE_LUT_SIZE;
+    let (view_height, cos_zenith) = transmittance_lut_params(uv);
+
+    let pos = vec3(0.0, view_height, 0.0);
+    let dir = vec3((1.0 - cos_zenith * cos_zenith).max(0.0).sqrt(), cos_zenith, 0.0);
+    let dt = ray_sphere(pos, dir, TOP_RADIUS).max(0.0) / STEPS as f32;
+
+    // Optical depth to the top of the atmosphere
+    let mut optical_depth = Vec3::ZERO;
+    for i in 0..STEPS {
+        let p = pos + dir * ((i as f32 + 0.5) * dt);
+        optical_depth += Medium::at(p.length() - BOTTOM_RADIUS).extinction * dt;
+    }
+
+    let transmittance = beer_lambert(optical_depth);
+    unsafe {
+        transmittance_lut.write(
+            ivec2(global_id.x as i32, global_id.y as i32),
+            vec4(transmittance.x, transmittance.y, transmittance.z, 1.0),
+        );
+    }
+}
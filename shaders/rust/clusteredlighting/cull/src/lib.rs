@@ -0,0 +1,71 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+use spirv_std::glam::{UVec3, Vec4, Vec4Swizzles};
+use spirv_std::arch::workgroup_memory_barrier_with_group_sync;
+use spirv_std::spirv;
+use common::cluster::{sphere_intersects_aabb, ClusterGrid, MAX_LIGHTS_PER_CLUSTER};
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Light {
+    pub position: Vec4,
+    pub color_radius: Vec4, // color in xyz, radius in w
+}
+
+const WORKGROUP_SIZE: usize = 64;
+
+// One invocation per cluster. Lights are processed in batches that the workgroup
+// first moves to shared memory as view space spheres.
+#[spirv(compute(threads(64)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(local_invocation_index)] local_index: u32,
+    #[spirv(workgroup)] shared_lights: &mut [Vec4; WORKGROUP_SIZE],
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] grid: &ClusterGrid,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] lights: &[Light],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] cluster_light_counts: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] cluster_light_indices: &mut [u32],
+) {
+    let cluster = global_id.x;
+    // No early out, every invocation has to help loading the light batches
+    let valid = cluster < grid.cluster_count();
+    let (aabb_min, aabb_max) = grid.cluster_bounds(cluster);
+
+    let mut count = 0;
+    let mut base = 0;
+    while base < grid.light_count {
+        let i = base + local_index;
+        shared_lights[local_index as usize] = if i < grid.light_count {
+            let light = lights[i as usize];
+            let view_pos = grid.view * light.position.xyz().extend(1.0);
+            view_pos.xyz().extend(light.color_radius.w)
+        } else {
+            Vec4::ZERO
+        };
+        unsafe {
+            workgroup_memory_barrier_with_group_sync();
+        }
+
+        if valid {
+            for j in 0..WORKGROUP_SIZE as u32 {
+                let sphere = shared_lights[j as usize];
+                if base + j < grid.light_count
+                    && count < MAX_LIGHTS_PER_CLUSTER as u32
+                    && sphere_intersects_aabb(sphere.xyz(), sphere.w, aabb_min, aabb_max)
+                {
+                    cluster_light_indices[cluster as usize * MAX_LIGHTS_PER_CLUSTER + count as usize] = base + j;
+                    count += 1;
+                }
+            }
+        }
+        unsafe {
+            workgroup_memory_barrier_with_group_sync();
+        }
+
+        base += WORKGROUP_SIZE as u32;
+    }
+
+    if valid {
+        cluster_light_counts[cluster as usize] = count;
+    }
+}
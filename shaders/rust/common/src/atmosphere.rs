@@ -0,0 +1,219 @@
+//! Earth-like atmosphere of "A Scalable and Production Ready Sky and Atmosphere
+//! Rendering Technique" (Hillaire 2020), shared by the `atmosphere` crates.
+//!
+//! Distances are in kilometers. The planet center lies `BOTTOM_RADIUS` below
+//! the world origin, so world space directions can be used as they are with
+//! +y pointing up.
+
+use core::f32::consts::PI;
+use spirv_std::glam::{vec2, vec3, Mat4, Vec2, Vec3, Vec4, Vec4Swizzles};
+use spirv_std::image::SampledImage;
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::Float;
+use spirv_std::Image;
+
+pub type Lut = SampledImage<Image!(2D, type=f32, sampled)>;
+
+pub const BOTTOM_RADIUS: f32 = 6360.0;
+pub const TOP_RADIUS: f32 = 6460.0;
+
+pub const RAYLEIGH_SCATTERING: Vec3 = Vec3::new(0.005_802, 0.013_558, 0.0331);
+pub const RAYLEIGH_SCALE_HEIGHT: f32 = 8.0;
+pub const MIE_SCATTERING: f32 = 0.003_996;
+pub const MIE_EXTINCTION: f32 = 0.004_40;
+pub const MIE_SCALE_HEIGHT: f32 = 1.2;
+pub const MIE_ANISOTROPY: f32 = 0.8;
+pub const OZONE_ABSORPTION: Vec3 = Vec3::new(0.000_650, 0.001_881, 0.000_085);
+
+pub const TRANSMITTANCE_LUT_SIZE: Vec2 = Vec2::new(256.0, 64.0);
+pub const MULTI_SCATTERING_LUT_SIZE: Vec2 = Vec2::new(32.0, 32.0);
+pub const SKY_VIEW_LUT_SIZE: Vec2 = Vec2::new(192.0, 108.0);
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct AtmosphereUBO {
+    pub inverse_view_projection: Mat4,
+    // Direction towards the sun
+    pub sun_direction: Vec4,
+    // Illuminance of the sun at the top of the atmosphere in rgb
+    pub sun_illuminance: Vec4,
+    pub ground_albedo: Vec4,
+    // Camera height above the ground
+    pub camera_height: f32,
+    // Angular radius of the sun disk in radians
+    pub sun_angular_radius: f32,
+}
+
+impl AtmosphereUBO {
+    /// Camera position relative to the planet center.
+    pub fn camera_position(&self) -> Vec3 {
+        vec3(0.0, BOTTOM_RADIUS + self.camera_height.max(0.001), 0.0)
+    }
+}
+
+/// Scattering and extinction coefficients at a height above the ground.
+pub struct Medium {
+    pub rayleigh_scattering: Vec3,
+    pub mie_scattering: f32,
+    pub extinction: Vec3,
+}
+
+impl Medium {
+    pub fn at(height: f32) -> Self {
+        let rayleigh_density = (-height / RAYLEIGH_SCALE_HEIGHT).exp();
+        let mie_density = (-height / MIE_SCALE_HEIGHT).exp();
+        // Tent shaped ozone layer around 25 km
+        let ozone_density = (1.0 - (height - 25.0).abs() / 15.0).max(0.0);
+
+        let rayleigh_scattering = RAYLEIGH_SCATTERING * rayleigh_density;
+        Self {
+            rayleigh_scattering,
+            mie_scattering: MIE_SCATTERING * mie_density,
+            extinction: rayleigh_scattering + Vec3::splat(MIE_EXTINCTION * mie_density) + OZONE_ABSORPTION * ozone_density,
+        }
+    }
+
+    pub fn scattering(&self) -> Vec3 {
+        self.rayleigh_scattering + Vec3::splat(self.mie_scattering)
+    }
+}
+
+/// Transmittance through a medium with the given optical depth.
+pub fn beer_lambert(optical_depth: Vec3) -> Vec3 {
+    vec3((-optical_depth.x).exp(), (-optical_depth.y).exp(), (-optical_depth.z).exp())
+}
+
+pub fn rayleigh_phase(cos_theta: f32) -> f32 {
+    3.0 / (16.0 * PI) * (1.0 + cos_theta * cos_theta)
+}
+
+/// Cornette-Shanks approximation of the Mie phase function.
+pub fn mie_phase(cos_theta: f32) -> f32 {
+    let g = MIE_ANISOTROPY;
+    let g2 = g * g;
+    let k = 3.0 / (8.0 * PI) * (1.0 - g2) / (2.0 + g2);
+    k * (1.0 + cos_theta * cos_theta) / (1.0 + g2 - 2.0 * g * cos_theta).powf(1.5)
+}
+
+/// Distance along the ray to the nearest intersection in front of `origin`
+/// with a sphere around the planet center, or a negative value if there is none.
+pub fn ray_sphere(origin: Vec3, dir: Vec3, radius: f32) -> f32 {
+    let b = origin.dot(dir);
+    let c = origin.dot(origin) - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return -1.0;
+    }
+    let s = discriminant.sqrt();
+    if -b - s >= 0.0 {
+        -b - s
+    } else {
+        -b + s
+    }
+}
+
+/// Height above the center and cosine of the view zenith angle for a
+/// transmittance LUT texel (Bruneton 2017 parameterization).
+pub fn transmittance_lut_params(uv: Vec2) -> (f32, f32) {
+    let h = (TOP_RADIUS * TOP_RADIUS - BOTTOM_RADIUS * BOTTOM_RADIUS).sqrt();
+    let rho = h * uv.y;
+    let view_height = (rho * rho + BOTTOM_RADIUS * BOTTOM_RADIUS).sqrt();
+
+    let d_min = TOP_RADIUS - view_height;
+    let d_max = rho + h;
+    let d = d_min + uv.x * (d_max - d_min);
+    let cos_zenith = if d == 0.0 {
+        1.0
+    } else {
+        ((h * h - rho * rho - d * d) / (2.0 * view_height * d)).clamp(-1.0, 1.0)
+    };
+    (view_height, cos_zenith)
+}
+
+/// Inverse of [`transmittance_lut_params`].
+pub fn transmittance_lut_uv(view_height: f32, cos_zenith: f32) -> Vec2 {
+    let h = (TOP_RADIUS * TOP_RADIUS - BOTTOM_RADIUS * BOTTOM_RADIUS).sqrt();
+    let rho = (view_height * view_height - BOTTOM_RADIUS * BOTTOM_RADIUS).max(0.0).sqrt();
+
+    let discriminant = view_height * view_height * (cos_zenith * cos_zenith - 1.0) + TOP_RADIUS * TOP_RADIUS;
+    let d = (-view_height * cos_zenith + discriminant.max(0.0).sqrt()).max(0.0);
+    let d_min = TOP_RADIUS - view_height;
+    let d_max = rho + h;
+    vec2((d - d_min) / (d_max - d_min), rho / h)
+}
+
+/// Multiple scattering LUT coordinates: sun zenith cosine along x, height along y.
+pub fn multi_scattering_lut_uv(view_height: f32, cos_sun_zenith: f32) -> Vec2 {
+    vec2(
+        cos_sun_zenith * 0.5 + 0.5,
+        ((view_height - BOTTOM_RADIUS) / (TOP_RADIUS - BOTTOM_RADIUS)).clamp(0.0, 1.0),
+    )
+}
+
+/// View zenith cosine and the cosine of the azimuth to the sun for a sky view
+/// LUT texel. Rows are packed more densely around the horizon.
+pub fn sky_view_lut_params(uv: Vec2, view_height: f32) -> (f32, f32) {
+    let v_horizon = (view_height * view_height - BOTTOM_RADIUS * BOTTOM_RADIUS).max(0.0).sqrt();
+    let beta = (v_horizon / view_height).acos();
+    let zenith_horizon_angle = PI - beta;
+
+    let view_zenith_angle = if uv.y < 0.5 {
+        let coord = 1.0 - 2.0 * uv.y;
+        zenith_horizon_angle * (1.0 - coord * coord)
+    } else {
+        let coord = uv.y * 2.0 - 1.0;
+        zenith_horizon_angle + beta * coord * coord
+    };
+    let light_view_cos = -(uv.x * uv.x * 2.0 - 1.0);
+    (view_zenith_angle.cos(), light_view_cos)
+}
+
+/// Inverse of [`sky_view_lut_params`].
+pub fn sky_view_lut_uv(view_height: f32, view_zenith_cos: f32, light_view_cos: f32) -> Vec2 {
+    let v_horizon = (view_height * view_height - BOTTOM_RADIUS * BOTTOM_RADIUS).max(0.0).sqrt();
+    let beta = (v_horizon / view_height).acos();
+    let zenith_horizon_angle = PI - beta;
+    let view_zenith_angle = view_zenith_cos.clamp(-1.0, 1.0).acos();
+
+    let v = if view_zenith_angle < zenith_horizon_angle {
+        let coord = 1.0 - (1.0 - view_zenith_angle / zenith_horizon_angle).sqrt();
+        coord * 0.5
+    } else {
+        let coord = ((view_zenith_angle - zenith_horizon_angle) / beta).sqrt();
+        coord * 0.5 + 0.5
+    };
+    let u = (-light_view_cos * 0.5 + 0.5).max(0.0).sqrt();
+    vec2(u, v)
+}
+
+/// Transmittance from `pos` to the sun, read from the transmittance LUT.
+pub fn sun_transmittance(transmittance_lut: &Lut, pos: Vec3, sun_direction: Vec3) -> Vec3 {
+    let height = pos.length();
+    let cos_sun_zenith = sun_direction.dot(pos / height);
+    transmittance_lut.sample_by_lod(transmittance_lut_uv(height, cos_sun_zenith), 0.0).xyz()
+}
+
+/// Multiple scattering contribution at `pos` for unit sun illuminance.
+pub fn multi_scattering(multi_scattering_lut: &Lut, pos: Vec3, sun_direction: Vec3) -> Vec3 {
+    let height = pos.length();
+    let cos_sun_zenith = sun_direction.dot(pos / height);
+    multi_scattering_lut.sample_by_lod(multi_scattering_lut_uv(height, cos_sun_zenith), 0.0).xyz()
+}
+
+/// Sky luminance seen from the camera in world space direction `dir`, without the sun disk.
+pub fn sky_luminance(sky_view_lut: &Lut, ubo: &AtmosphereUBO, dir: Vec3) -> Vec3 {
+    let view_height = ubo.camera_position().length();
+    let sun = ubo.sun_direction.xyz().normalize();
+
+    // Azimuth relative to the sun, the LUT is symmetric around it
+    let dir_h = vec2(dir.x, dir.z);
+    let sun_h = vec2(sun.x, sun.z);
+    let light_view_cos = if dir_h.length() > 0.0001 && sun_h.length() > 0.0001 {
+        dir_h.normalize().dot(sun_h.normalize())
+    } else {
+        1.0
+    };
+
+    let uv = sky_view_lut_uv(view_height, dir.y, light_view_cos);
+    sky_view_lut.sample_by_lod(uv, 0.0).xyz()
+}
@@ -0,0 +1,37 @@
+//! Cascade selection for cascaded shadow maps with four cascades, matching
+//! the split layout of shadowmappingcascade.
+
+use spirv_std::glam::Vec4;
+
+/// Cascade containing a view space z. `splits` holds the far end of the
+/// first three cascades as negative, decreasing view space z, the last
+/// cascade reaches up to the far plane.
+pub fn cascade_index(view_z: f32, splits: Vec4) -> u32 {
+    // The largest split the position lies beyond, as the GLSL loop over the splits
+    let mut index = 0;
+    if view_z < splits.x {
+        index = 1;
+    }
+    if view_z < splits.y {
+        index = 2;
+    }
+    if view_z < splits.z {
+        index = 3;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spirv_std::glam::vec4;
+
+    #[test]
+    fn selects_cascade_by_depth() {
+        let splits = vec4(-2.0, -8.0, -20.0, -48.0);
+        let cases = [(-0.1, 0), (-1.9, 0), (-2.1, 1), (-7.9, 1), (-8.1, 2), (-19.9, 2), (-20.1, 3), (-47.0, 3), (-100.0, 3)];
+        for (view_z, expected) in cases {
+            assert_eq!(cascade_index(view_z, splits), expected, "{view_z}");
+        }
+    }
+}
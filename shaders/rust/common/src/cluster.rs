@@ -0,0 +1,103 @@
+//! Froxel grid shared by the clustered light culling pass and the passes that shade with its light lists.
+//!
+//! The view frustum is split into `tiles_x * tiles_y` screen tiles and
+//! `slices` depth slices that are distributed exponentially between the near
+//! and far plane. Clusters are numbered x first, then y, then slice.
+
+use spirv_std::glam::{vec2, vec4, Mat4, Vec2, Vec3, Vec4Swizzles};
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::Float;
+
+/// Size of the per-cluster slot in the light index list.
+pub const MAX_LIGHTS_PER_CLUSTER: usize = 128;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct ClusterGrid {
+    pub inverse_projection: Mat4,
+    pub view: Mat4,
+    pub screen_size: Vec2,
+    pub z_near: f32,
+    pub z_far: f32,
+    pub tiles_x: u32,
+    pub tiles_y: u32,
+    pub slices: u32,
+    pub light_count: u32,
+}
+
+impl ClusterGrid {
+    pub fn cluster_count(&self) -> u32 {
+        self.tiles_x * self.tiles_y * self.slices
+    }
+
+    /// Depth slice for a positive view space depth.
+    pub fn slice(&self, view_depth: f32) -> u32 {
+        let slice = (view_depth / self.z_near).ln() / (self.z_far / self.z_near).ln() * self.slices as f32;
+        (slice.max(0.0) as u32).min(self.slices - 1)
+    }
+
+    /// View space depth at which `slice` starts.
+    pub fn slice_depth(&self, slice: u32) -> f32 {
+        self.z_near * (self.z_far / self.z_near).powf(slice as f32 / self.slices as f32)
+    }
+
+    /// Cluster containing the fragment at `frag_coord` with the given positive view space depth.
+    pub fn cluster_index(&self, frag_coord: Vec2, view_depth: f32) -> u32 {
+        let tile_x = ((frag_coord.x / self.screen_size.x * self.tiles_x as f32) as u32).min(self.tiles_x - 1);
+        let tile_y = ((frag_coord.y / self.screen_size.y * self.tiles_y as f32) as u32).min(self.tiles_y - 1);
+        tile_x + self.tiles_x * (tile_y + self.tiles_y * self.slice(view_depth))
+    }
+
+    /// Positive view space depth of a world space position.
+    pub fn view_depth(&self, world_pos: Vec3) -> f32 {
+        -(self.view * world_pos.extend(1.0)).z
+    }
+
+    /// View space bounding box of a cluster as `(min, max)`.
+    pub fn cluster_bounds(&self, cluster: u32) -> (Vec3, Vec3) {
+        let tile_x = cluster % self.tiles_x;
+        let tile_y = (cluster / self.tiles_x) % self.tiles_y;
+        let slice = cluster / (self.tiles_x * self.tiles_y);
+
+        let tile_size = vec2(2.0 / self.tiles_x as f32, 2.0 / self.tiles_y as f32);
+        let ndc_min = vec2(tile_x as f32, tile_y as f32) * tile_size - 1.0;
+        let ndc_max = ndc_min + tile_size;
+
+        // Rays from the eye through the tile corners, cut by the slice's near and far depth
+        let near = self.slice_depth(slice);
+        let far = self.slice_depth(slice + 1);
+        let corner_min = self.view_ray(ndc_min);
+        let corner_max = self.view_ray(ndc_max);
+        let min_near = corner_min * near;
+        let min_far = corner_min * far;
+        let max_near = corner_max * near;
+        let max_far = corner_max * far;
+
+        (
+            min_near.min(min_far).min(max_near.min(max_far)),
+            min_near.max(min_far).max(max_near.max(max_far)),
+        )
+    }
+
+    // View space direction through an NDC position, scaled to a depth of one
+    fn view_ray(&self, ndc: Vec2) -> Vec3 {
+        let p = self.inverse_projection * vec4(ndc.x, ndc.y, 0.0, 1.0);
+        let p = p.xyz() / p.w;
+        p / -p.z
+    }
+}
+
+/// Whether a sphere overlaps an axis aligned box.
+pub fn sphere_intersects_aabb(center: Vec3, radius: f32, min: Vec3, max: Vec3) -> bool {
+    let closest = center.clamp(min, max);
+    let d = closest - center;
+    d.dot(d) <= radius * radius
+}
+
+/// Smooth falloff that brings a light's contribution to zero at its culling radius.
+pub fn range_window(dist: f32, radius: f32) -> f32 {
+    let x = dist / radius;
+    let x2 = x * x;
+    let w = (1.0 - x2 * x2).clamp(0.0, 1.0);
+    w * w
+}
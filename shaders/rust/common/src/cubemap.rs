@@ -0,0 +1,20 @@
+//! Cubemap face addressing for compute passes that write cubemaps through a
+//! 2D array storage view with one layer per face.
+
+use spirv_std::glam::{vec3, Vec2, Vec3};
+
+/// Unit direction through the point `uv` (in [0, 1]) of a cube face, following
+/// the Vulkan face order +X, -X, +Y, -Y, +Z, -Z.
+pub fn face_direction(face: u32, uv: Vec2) -> Vec3 {
+    let u = uv.x * 2.0 - 1.0;
+    let v = uv.y * 2.0 - 1.0;
+    let dir = match face {
+        0 => vec3(1.0, -v, -u),
+        1 => vec3(-1.0, -v, u),
+        2 => vec3(u, 1.0, v),
+        3 => vec3(u, -1.0, -v),
+        4 => vec3(u, -v, 1.0),
+        _ => vec3(-u, -v, -1.0),
+    };
+    dir.normalize()
+}
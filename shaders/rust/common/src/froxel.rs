@@ -0,0 +1,43 @@
+//! Frustum aligned voxel volume used by the volumetric fog passes.
+//!
+//! The volume covers the view frustum with `width * height` screen tiles and
+//! `depth` slices distributed exponentially between the near and far plane,
+//! the same distribution as the [`crate::cluster`] grid. Texture coordinates
+//! into the volume are the screen uv and the normalized slice coordinate `w`.
+
+use spirv_std::glam::{vec4, Mat4, Vec2, Vec3, Vec4Swizzles};
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::Float;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct FroxelGrid {
+    pub inverse_projection: Mat4,
+    pub inverse_view: Mat4,
+    pub screen_size: Vec2,
+    pub z_near: f32,
+    pub z_far: f32,
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+}
+
+impl FroxelGrid {
+    /// Positive view space depth at the normalized slice coordinate `w`.
+    pub fn slice_depth(&self, w: f32) -> f32 {
+        self.z_near * (self.z_far / self.z_near).powf(w)
+    }
+
+    /// Normalized slice coordinate of a positive view space depth.
+    pub fn depth_to_w(&self, view_depth: f32) -> f32 {
+        ((view_depth / self.z_near).ln() / (self.z_far / self.z_near).ln()).clamp(0.0, 1.0)
+    }
+
+    /// World space position at screen `uv` and slice coordinate `w`.
+    pub fn world_position(&self, uv: Vec2, w: f32) -> Vec3 {
+        let p = self.inverse_projection * vec4(uv.x * 2.0 - 1.0, uv.y * 2.0 - 1.0, 0.0, 1.0);
+        let ray = p.xyz() / p.w;
+        let view_pos = ray / -ray.z * self.slice_depth(w);
+        (self.inverse_view * view_pos.extend(1.0)).xyz()
+    }
+}
@@ -0,0 +1,26 @@
+//! Building blocks shared between the shader crates.
+//!
+//! Crates that use the subgroup paths need the `GroupNonUniform`,
+//! `GroupNonUniformArithmetic` and `GroupNonUniformBallot` capabilities in
+//! their rust-gpu build metadata.
+
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+pub mod atmosphere;
+pub mod cascade;
+pub mod cluster;
+pub mod cubemap;
+pub mod froxel;
+pub mod meshlet;
+pub mod morph;
+pub mod packing;
+pub mod pbr;
+pub mod scan;
+pub mod sh;
+#[cfg(not(target_arch = "spirv"))]
+pub mod sim;
+pub mod skinning;
+pub mod sort;
+pub mod subgroup;
+pub mod temporal;
+pub mod workgroup;
@@ -0,0 +1,49 @@
+//! Meshlet layout shared by the mesh shader and the host side meshlet builder.
+//!
+//! A meshlet references up to [`MAX_MESHLET_VERTICES`] vertices through the
+//! meshlet vertex buffer and up to [`MAX_MESHLET_TRIANGLES`] triangles in the
+//! meshlet triangle buffer, one `u32` per triangle holding three 8 bit indices
+//! into the meshlet's vertices.
+
+use spirv_std::glam::{UVec3, Vec3, Vec4};
+
+pub const MAX_MESHLET_VERTICES: usize = 64;
+pub const MAX_MESHLET_TRIANGLES: usize = 124;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Meshlet {
+    // Bounding sphere in model space, center in xyz and radius in w
+    pub bounding_sphere: Vec4,
+    // Normal cone axis in xyz and the sine of its half angle in w, 1 for cones that can't be culled
+    pub cone: Vec4,
+    pub vertex_offset: u32,
+    pub vertex_count: u32,
+    pub triangle_offset: u32,
+    pub triangle_count: u32,
+}
+
+/// Mesh vertex, w pads to the std430 layout.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Vertex {
+    pub position: Vec4,
+    pub normal: Vec4,
+}
+
+/// Packs three meshlet local vertex indices, the first in the lowest byte.
+pub fn pack_triangle(a: u32, b: u32, c: u32) -> u32 {
+    a | (b << 8) | (c << 16)
+}
+
+/// Inverse of [`pack_triangle`].
+pub fn unpack_triangle(packed: u32) -> UVec3 {
+    UVec3::new(packed & 0xff, (packed >> 8) & 0xff, (packed >> 16) & 0xff)
+}
+
+/// True if every triangle inside the bounding sphere with a normal in the cone
+/// faces away from `camera_pos`.
+pub fn cone_culled(center: Vec3, radius: f32, cone: Vec4, camera_pos: Vec3) -> bool {
+    let view = center - camera_pos;
+    view.dot(cone.truncate()) >= cone.w * view.length() + radius
+}
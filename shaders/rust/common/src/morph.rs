@@ -0,0 +1,39 @@
+//! glTF morph targets read from storage buffers. Each vertex has one
+//! `MorphDelta` per target, stored consecutively, and each mesh has a weight
+//! per target.
+
+use spirv_std::glam::Vec4;
+
+/// Displacements of one vertex for one morph target. Only xyz is used, w pads
+/// to the std430 layout.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MorphDelta {
+    pub position: Vec4,
+    pub normal: Vec4,
+    pub tangent: Vec4,
+}
+
+/// Morph targets of the current mesh.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MorphParams {
+    /// First vertex of the mesh in the vertex buffer.
+    pub first_vertex: u32,
+    pub target_count: u32,
+}
+
+/// Sum of the deltas of `vertex`, counted from the first vertex of the mesh,
+/// weighted by the current target weights.
+pub fn blend_morph_deltas(deltas: &[MorphDelta], weights: &[f32], vertex: u32, target_count: u32) -> MorphDelta {
+    let mut blended = MorphDelta { position: Vec4::ZERO, normal: Vec4::ZERO, tangent: Vec4::ZERO };
+    let first = (vertex * target_count) as usize;
+    for target in 0..target_count as usize {
+        let weight = weights[target];
+        let delta = deltas[first + target];
+        blended.position += delta.position * weight;
+        blended.normal += delta.normal * weight;
+        blended.tangent += delta.tangent * weight;
+    }
+    blended
+}
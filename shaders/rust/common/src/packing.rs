@@ -0,0 +1,272 @@
+//! G-Buffer encodings: octahedral normals, GLSL style `packUnorm4x8`/`packHalf2x16`
+//! equivalents, depth linearization and position reconstruction from depth.
+
+use spirv_std::glam::{uvec4, vec2, vec3, vec4, Mat4, UVec4, Vec2, Vec3, Vec3Swizzles, Vec4, Vec4Swizzles};
+
+fn sign_not_zero(v: Vec2) -> Vec2 {
+    vec2(
+        if v.x >= 0.0 { 1.0 } else { -1.0 },
+        if v.y >= 0.0 { 1.0 } else { -1.0 },
+    )
+}
+
+/// Maps a unit vector onto the [-1, 1] square of an octahedron unfolded into the plane.
+pub fn oct_encode(n: Vec3) -> Vec2 {
+    let n = n / (n.x.abs() + n.y.abs() + n.z.abs());
+    if n.z >= 0.0 {
+        n.xy()
+    } else {
+        // Fold the lower hemisphere over the diagonals
+        (Vec2::ONE - vec2(n.y.abs(), n.x.abs())) * sign_not_zero(n.xy())
+    }
+}
+
+/// Inverse of [`oct_encode`], returns a unit vector.
+pub fn oct_decode(e: Vec2) -> Vec3 {
+    let mut n = vec3(e.x, e.y, 1.0 - e.x.abs() - e.y.abs());
+    let t = (-n.z).max(0.0);
+    n.x += if n.x >= 0.0 { -t } else { t };
+    n.y += if n.y >= 0.0 { -t } else { t };
+    n.normalize()
+}
+
+/// Packs four [0, 1] values into 8 bit fields, x in the lowest byte.
+pub fn pack_unorm4x8(v: Vec4) -> u32 {
+    // Adding 0.5 before truncating rounds to nearest
+    let q = v.clamp(Vec4::ZERO, Vec4::ONE) * 255.0 + 0.5;
+    (q.x as u32) | (q.y as u32) << 8 | (q.z as u32) << 16 | (q.w as u32) << 24
+}
+
+/// Inverse of [`pack_unorm4x8`].
+pub fn unpack_unorm4x8(p: u32) -> Vec4 {
+    vec4(
+        (p & 0xff) as f32,
+        ((p >> 8) & 0xff) as f32,
+        ((p >> 16) & 0xff) as f32,
+        (p >> 24) as f32,
+    ) / 255.0
+}
+
+/// Converts to the bits of an IEEE half float, rounding to nearest even.
+pub fn f32_to_f16(value: f32) -> u32 {
+    let x = value.to_bits();
+    let sign = (x >> 16) & 0x8000;
+    let exp = ((x >> 23) & 0xff) as i32;
+    let mant = x & 0x7f_ffff;
+
+    // Infinity and NaN
+    if exp == 0xff {
+        return sign | 0x7c00 | if mant != 0 { 0x200 } else { 0 };
+    }
+
+    let e = exp - 127 + 15;
+    if e >= 0x1f {
+        // Too large, becomes infinity
+        return sign | 0x7c00;
+    }
+    if e <= 0 {
+        // Subnormal half, or zero if even that is too small
+        if e < -10 {
+            return sign;
+        }
+        let m = mant | 0x80_0000;
+        let shift = (14 - e) as u32;
+        let half = m >> shift;
+        let rem = m & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let round = (rem > halfway || (rem == halfway && half & 1 != 0)) as u32;
+        return sign | (half + round);
+    }
+
+    let half = (e as u32) << 10 | mant >> 13;
+    let rem = mant & 0x1fff;
+    let round = (rem > 0x1000 || (rem == 0x1000 && half & 1 != 0)) as u32;
+    // A carry out of the mantissa correctly bumps the exponent
+    sign | (half + round)
+}
+
+/// Converts the bits of an IEEE half float in the low 16 bits of `h`.
+pub fn f16_to_f32(h: u32) -> f32 {
+    let sign = (h & 0x8000) << 16;
+    let exp = (h >> 10) & 0x1f;
+    let mant = h & 0x3ff;
+
+    if exp == 0 {
+        let v = mant as f32 * (1.0 / 16_777_216.0);
+        return if sign != 0 { -v } else { v };
+    }
+    if exp == 0x1f {
+        return f32::from_bits(sign | 0x7f80_0000 | mant << 13);
+    }
+    f32::from_bits(sign | (exp + 112) << 23 | mant << 13)
+}
+
+/// Packs two floats as half floats, x in the low 16 bits.
+pub fn pack_half2x16(v: Vec2) -> u32 {
+    f32_to_f16(v.x) | f32_to_f16(v.y) << 16
+}
+
+/// Inverse of [`pack_half2x16`].
+pub fn unpack_half2x16(p: u32) -> Vec2 {
+    vec2(f16_to_f32(p & 0xffff), f16_to_f32(p >> 16))
+}
+
+/// Packs a compact G-Buffer texel for a single `rgba32ui` target: the
+/// octahedral normal as two half floats in x, albedo in y and the material
+/// (metallic, roughness, ambient occlusion) in z as unorm bytes. w is unused.
+/// Positions are not stored, see [`position_from_depth`].
+pub fn pack_gbuffer(normal: Vec3, albedo: Vec4, material: Vec4) -> UVec4 {
+    uvec4(
+        pack_half2x16(oct_encode(normal)),
+        pack_unorm4x8(albedo),
+        pack_unorm4x8(material),
+        0,
+    )
+}
+
+/// Inverse of [`pack_gbuffer`], returns the normal, albedo and material.
+pub fn unpack_gbuffer(texel: UVec4) -> (Vec3, Vec4, Vec4) {
+    (
+        oct_decode(unpack_half2x16(texel.x)),
+        unpack_unorm4x8(texel.y),
+        unpack_unorm4x8(texel.z),
+    )
+}
+
+/// Reconstructs a position from a [0, 1] depth buffer value at the given
+/// texture coordinate. Pass the inverse projection to get a view space
+/// position, or the inverse view projection to get a world space one.
+pub fn position_from_depth(uv: Vec2, depth: f32, inverse_projection: Mat4) -> Vec3 {
+    let ndc = vec4(uv.x * 2.0 - 1.0, uv.y * 2.0 - 1.0, depth, 1.0);
+    let p = inverse_projection * ndc;
+    p.xyz() / p.w
+}
+
+/// View space distance of a [0, 1] depth buffer value of a perspective
+/// projection, `z_near` at zero and `z_far` at one.
+pub fn view_distance(depth: f32, z_near: f32, z_far: f32) -> f32 {
+    z_near * z_far / (z_far - depth * (z_far - z_near))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::random_values;
+
+    // Unit vectors spread over the sphere, including the poles and the folded edges of the octahedron
+    fn directions() -> Vec<Vec3> {
+        let mut dirs = vec![Vec3::X, -Vec3::X, Vec3::Y, -Vec3::Y, Vec3::Z, -Vec3::Z, vec3(1.0, 1.0, -1.0).normalize()];
+        let r = random_values(3 * 500, 2001, 7);
+        for v in r.chunks(3) {
+            let d = vec3(v[0] as f32, v[1] as f32, v[2] as f32) / 1000.0 - 1.0;
+            if d.length() > 0.01 {
+                dirs.push(d.normalize());
+            }
+        }
+        dirs
+    }
+
+    #[test]
+    fn oct_round_trip() {
+        for n in directions() {
+            let e = oct_encode(n);
+            assert!(e.x.abs() <= 1.0 && e.y.abs() <= 1.0);
+            assert!(oct_decode(e).dot(n) > 0.99999, "{n}");
+        }
+    }
+
+    #[test]
+    fn unorm4x8_round_trip() {
+        for (i, v) in random_values(4 * 256, 256, 3).chunks(4).enumerate() {
+            let bytes = UVec4::from_slice(v);
+            let unorm = bytes.as_vec4() / 255.0;
+            let packed = pack_unorm4x8(unorm);
+            assert_eq!(packed.to_le_bytes().map(u32::from), bytes.to_array(), "{i}");
+            assert_eq!(unpack_unorm4x8(packed), unorm);
+        }
+        // Out of range values are clamped
+        assert_eq!(pack_unorm4x8(vec4(-1.0, 2.0, 0.5, 1.0)), 0xff80ff00);
+    }
+
+    #[test]
+    fn half_conversion() {
+        // Every finite half survives the round trip through f32
+        for h in (0..0x7c00).chain(0x8000..0xfc00) {
+            assert_eq!(f32_to_f16(f16_to_f32(h)), h, "{h:#x}");
+        }
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert_eq!(f32_to_f16(65520.0), 0x7c00);
+        assert_eq!(f32_to_f16(f32::INFINITY), 0x7c00);
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+        // Ties round to even
+        assert_eq!(f32_to_f16(1.0 + 1.0 / 2048.0), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 / 2048.0), 0x3c02);
+        // Smallest subnormal
+        assert_eq!(f32_to_f16(5.960_464_5e-8), 1);
+        assert_eq!(pack_half2x16(vec2(1.0, -2.0)), 0xc000_3c00);
+        assert_eq!(unpack_half2x16(0xc000_3c00), vec2(1.0, -2.0));
+    }
+
+    #[test]
+    fn gbuffer_round_trip() {
+        let colors = random_values(8 * 64, 256, 5);
+        for (n, c) in directions().into_iter().zip(colors.chunks(8)) {
+            let albedo = UVec4::from_slice(&c[..4]).as_vec4() / 255.0;
+            let material = UVec4::from_slice(&c[4..]).as_vec4() / 255.0;
+            let (normal, a, m) = unpack_gbuffer(pack_gbuffer(n, albedo, material));
+            assert!(normal.dot(n) > 0.9999, "{n}");
+            assert_eq!(a, albedo);
+            assert_eq!(m, material);
+        }
+    }
+
+    #[test]
+    fn position_reconstruction() {
+        // Right handed perspective with a [0, 1] depth range, near 0.1 and far 100
+        let (near, far) = (0.1, 100.0);
+        let projection = Mat4::from_cols(
+            vec4(1.2, 0.0, 0.0, 0.0),
+            vec4(0.0, 1.8, 0.0, 0.0),
+            vec4(0.0, 0.0, far / (near - far), -1.0),
+            vec4(0.0, 0.0, near * far / (near - far), 0.0),
+        );
+        // Quarter turn around y, then moved away from the points
+        let view = Mat4::from_translation(vec3(0.5, -1.0, -10.0))
+            * Mat4::from_cols(Vec4::Z, Vec4::Y, -Vec4::X, Vec4::W);
+        let view_projection = projection * view;
+        for p in [vec3(0.0, 0.0, 0.0), vec3(0.5, -0.3, 1.0), vec3(-2.0, 1.0, -4.0)] {
+            let clip = view_projection * p.extend(1.0);
+            let ndc = clip.xyz() / clip.w;
+            let uv = ndc.xy() * 0.5 + 0.5;
+            let world = position_from_depth(uv, ndc.z, view_projection.inverse());
+            // Depth precision falls off with the distance to the near plane
+            assert!(world.distance(p) < 1e-3, "{p} {world}");
+            let view_pos = position_from_depth(uv, ndc.z, projection.inverse());
+            assert!(view_pos.distance((view * p.extend(1.0)).xyz()) < 1e-3, "{p} {view_pos}");
+        }
+    }
+
+    #[test]
+    fn view_distance_of_depth() {
+        let (z_near, z_far) = (0.1, 64.0);
+        assert!((view_distance(0.0, z_near, z_far) - z_near).abs() < 1e-6);
+        assert!((view_distance(1.0, z_near, z_far) - z_far).abs() < 1e-2);
+        // Depth buffer values of points at known distances, written by a right handed projection
+        // with a [0, 1] depth range
+        let projection = Mat4::from_cols(
+            vec4(1.2, 0.0, 0.0, 0.0),
+            vec4(0.0, 1.8, 0.0, 0.0),
+            vec4(0.0, 0.0, z_far / (z_near - z_far), -1.0),
+            vec4(0.0, 0.0, z_near * z_far / (z_near - z_far), 0.0),
+        );
+        for distance in [0.1, 0.5, 1.0, 2.5, 10.0, 32.0, 60.0] {
+            let clip = projection * vec4(0.3, -0.2, -distance, 1.0);
+            let depth = clip.z / clip.w;
+            assert!((0.0..=1.0).contains(&depth));
+            let z = view_distance(depth, z_near, z_far);
+            // Depth precision falls off towards the far plane
+            assert!((z - distance).abs() < 1e-3 * distance, "{distance} {z}");
+        }
+    }
+}
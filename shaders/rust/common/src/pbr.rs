@@ -0,0 +1,60 @@
+//! Cook-Torrance BRDF with GGX distribution, Schlick-Smith geometry and Schlick Fresnel terms.
+
+use core::f32::consts::PI;
+use spirv_std::glam::{vec3, Vec3};
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::Float;
+
+// Normal Distribution function
+pub fn d_ggx(dot_nh: f32, roughness: f32) -> f32 {
+    let alpha = roughness * roughness;
+    let alpha2 = alpha * alpha;
+    let denom = dot_nh * dot_nh * (alpha2 - 1.0) + 1.0;
+    alpha2 / (PI * denom * denom)
+}
+
+// Geometric Shadowing function
+pub fn g_schlicksmith_ggx(dot_nl: f32, dot_nv: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = (r * r) / 8.0;
+    let gl = dot_nl / (dot_nl * (1.0 - k) + k);
+    let gv = dot_nv / (dot_nv * (1.0 - k) + k);
+    gl * gv
+}
+
+// Fresnel function
+pub fn f_schlick(cos_theta: f32, f0: Vec3) -> Vec3 {
+    f0 + (Vec3::ONE - f0) * (1.0 - cos_theta).powf(5.0)
+}
+
+/// Reflectance at normal incidence for a dielectric-metal blend.
+pub fn f0(albedo: Vec3, metallic: f32) -> Vec3 {
+    vec3(0.04, 0.04, 0.04).lerp(albedo, metallic)
+}
+
+/// Outgoing radiance towards `v` for light arriving from direction `l` with
+/// the given `radiance`, including the Lambertian diffuse part.
+pub fn brdf(l: Vec3, v: Vec3, n: Vec3, albedo: Vec3, metallic: f32, roughness: f32, radiance: Vec3) -> Vec3 {
+    let h = (v + l).normalize();
+    let dot_nv = n.dot(v).clamp(0.0, 1.0);
+    let dot_nl = n.dot(l).clamp(0.0, 1.0);
+    let dot_nh = n.dot(h).clamp(0.0, 1.0);
+
+    if dot_nl <= 0.0 {
+        return Vec3::ZERO;
+    }
+
+    let roughness = roughness.max(0.05);
+    // D = Normal distribution (Distribution of the microfacets)
+    let d = d_ggx(dot_nh, roughness);
+    // G = Geometric shadowing term (Microfacets shadowing)
+    let g = g_schlicksmith_ggx(dot_nl, dot_nv, roughness);
+    // F = Fresnel factor (Reflectance depending on angle of incidence)
+    let f = f_schlick(dot_nv, f0(albedo, metallic));
+
+    let spec = d * f * g / (4.0 * dot_nl * dot_nv + 0.001);
+    // Energy not reflected specularly is diffused, metals have no diffuse part
+    let kd = (Vec3::ONE - f) * (1.0 - metallic);
+
+    (kd * albedo / PI + spec) * dot_nl * radiance
+}
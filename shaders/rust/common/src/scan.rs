@@ -0,0 +1,115 @@
+//! Workgroup-wide prefix sums built on subgroup arithmetic.
+//!
+//! Cheaper than the [`crate::workgroup`] scans as only one value per subgroup
+//! goes through shared memory.
+
+use crate::subgroup;
+#[cfg(target_arch = "spirv")]
+use spirv_std::arch::workgroup_memory_barrier_with_group_sync;
+#[cfg(not(target_arch = "spirv"))]
+use crate::sim::workgroup_memory_barrier_with_group_sync;
+
+/// Upper bound on the number of subgroups in a workgroup. The subgroup totals
+/// are scanned by the first subgroup, so this also assumes
+/// `num_subgroups <= subgroup_size`, which holds for any workgroup of up to
+/// 1024 invocations on hardware with subgroups of 32 or more.
+pub const MAX_SUBGROUPS: usize = 32;
+
+/// Size of the `#[spirv(workgroup)]` scratch array the scans need: one slot per
+/// subgroup plus one for the workgroup total.
+pub const SCAN_SCRATCH_SIZE: usize = MAX_SUBGROUPS + 1;
+
+/// Exclusive prefix sum of `value` across the whole workgroup.
+///
+/// Returns `(prefix, total)` where `prefix` is the sum of `value` over all
+/// invocations with a lower `local_invocation_index` and `total` is the sum
+/// over the workgroup. Must be reached by every invocation of the workgroup
+/// in uniform control flow, as it contains workgroup barriers.
+pub fn workgroup_exclusive_add(
+    value: u32,
+    subgroup_id: u32,
+    subgroup_lane: u32,
+    num_subgroups: u32,
+    scratch: &mut [u32; SCAN_SCRATCH_SIZE],
+) -> (u32, u32) {
+    // Scan within the subgroup and publish the subgroup total
+    let lane_prefix = subgroup::exclusive_add(value);
+    let subgroup_total = subgroup::reduce_add(value);
+    if subgroup_lane == 0 {
+        scratch[subgroup_id as usize] = subgroup_total;
+    }
+    unsafe {
+        workgroup_memory_barrier_with_group_sync();
+    }
+
+    // The first subgroup scans the subgroup totals in place
+    if subgroup_id == 0 {
+        let total = if subgroup_lane < num_subgroups {
+            scratch[subgroup_lane as usize]
+        } else {
+            0
+        };
+        let subgroup_offset = subgroup::exclusive_add(total);
+        let workgroup_total = subgroup::reduce_add(total);
+        if subgroup_lane < num_subgroups {
+            scratch[subgroup_lane as usize] = subgroup_offset;
+        }
+        if subgroup_lane == 0 {
+            scratch[MAX_SUBGROUPS] = workgroup_total;
+        }
+    }
+    unsafe {
+        workgroup_memory_barrier_with_group_sync();
+    }
+
+    let prefix = scratch[subgroup_id as usize] + lane_prefix;
+    let total = scratch[MAX_SUBGROUPS];
+
+    // Keep the scratch intact until everyone has read it so it can be reused
+    unsafe {
+        workgroup_memory_barrier_with_group_sync();
+    }
+    (prefix, total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{dispatch, random_values, Shared};
+
+    fn check(workgroup_size: u32, subgroup_size: u32, values: &[u32]) {
+        let scratch = Shared::new([0; SCAN_SCRATCH_SIZE]);
+        let results = dispatch(workgroup_size, subgroup_size, |invocation| {
+            workgroup_exclusive_add(
+                values[invocation.local_index as usize],
+                invocation.subgroup_id,
+                invocation.subgroup_lane,
+                invocation.num_subgroups,
+                unsafe { scratch.get() },
+            )
+        });
+        let total: u32 = values.iter().sum();
+        let expected = values.iter().scan(0, |sum, &v| {
+            let prefix = *sum;
+            *sum += v;
+            Some((prefix, total))
+        });
+        assert!(results.into_iter().eq(expected));
+    }
+
+    #[test]
+    fn exclusive_add_matches_iterator() {
+        check(64, 8, &random_values(64, 100, 1));
+        // Partial last subgroup
+        check(100, 16, &random_values(100, 100, 2));
+        check(256, 32, &random_values(256, 100, 3));
+    }
+
+    #[test]
+    fn exclusive_add_of_flags_compacts() {
+        let flags = random_values(128, 2, 4);
+        check(128, 32, &flags);
+        check(128, 32, &[0; 128]);
+        check(128, 32, &[1; 128]);
+    }
+}
@@ -0,0 +1,148 @@
+//! Order 2 (L2) real spherical harmonics for irradiance environment maps
+//! (Ramamoorthi and Hanrahan 2001).
+//!
+//! Coefficients are stored as nine `Vec4`s with the RGB value in xyz, ordered
+//! by band: `Y00, Y1-1, Y10, Y11, Y2-2, Y2-1, Y20, Y21, Y22`.
+
+use core::f32::consts::PI;
+use spirv_std::glam::{vec2, Vec3, Vec4, Vec4Swizzles};
+
+use crate::cubemap::face_direction;
+
+pub const SH_COEFFICIENT_COUNT: usize = 9;
+
+/// The nine basis functions evaluated for the unit direction `d`.
+pub fn basis(d: Vec3) -> [f32; SH_COEFFICIENT_COUNT] {
+    [
+        0.282_095,
+        0.488_603 * d.y,
+        0.488_603 * d.z,
+        0.488_603 * d.x,
+        1.092_548 * d.x * d.y,
+        1.092_548 * d.y * d.z,
+        0.315_392 * (3.0 * d.z * d.z - 1.0),
+        1.092_548 * d.x * d.z,
+        0.546_274 * (d.x * d.x - d.y * d.y),
+    ]
+}
+
+/// Direction and weight of sample `index` of a projection that samples a
+/// `size` by `size` grid on each of the six cube faces, `index` counting the
+/// samples face by face in rows. The weight is the solid angle of the grid
+/// cell up to the constant factor `(2 / size)^2`, which
+/// [`normalize_projection`] divides out.
+pub fn cube_sample(index: u32, size: u32) -> (Vec3, f32) {
+    let face_samples = size * size;
+    let face = index / face_samples;
+    let x = index % size;
+    let y = (index % face_samples) / size;
+    let uv = (vec2(x as f32, y as f32) + 0.5) / size as f32;
+    let u = uv.x * 2.0 - 1.0;
+    let v = uv.y * 2.0 - 1.0;
+    let d = 1.0 + u * u + v * v;
+    (face_direction(face, uv), 1.0 / (d * d.sqrt()))
+}
+
+/// Adds the radiance arriving from the unit direction `dir`, weighted by the
+/// solid angle `weight` it stands for, to the projected coefficients.
+pub fn accumulate(coefficients: &mut [Vec3; SH_COEFFICIENT_COUNT], radiance: Vec3, dir: Vec3, weight: f32) {
+    let y = basis(dir);
+    for i in 0..SH_COEFFICIENT_COUNT {
+        coefficients[i] += radiance * (y[i] * weight);
+    }
+}
+
+/// Scales an accumulated coefficient so that the sample weights, whose sum is
+/// `weight_sum`, add up to the solid angle of the full sphere.
+pub fn normalize_projection(coefficient: Vec3, weight_sum: f32) -> Vec3 {
+    coefficient * (4.0 * PI / weight_sum)
+}
+
+/// Convolution of the band of a coefficient with the clamped cosine lobe, divided by pi.
+fn cosine_lobe(coefficient: usize) -> f32 {
+    match coefficient {
+        0 => 1.0,
+        1..=3 => 2.0 / 3.0,
+        _ => 0.25,
+    }
+}
+
+/// Irradiance around the normal `n` divided by pi, which is what
+/// `pbribl/irradiancecube` stores, from projected radiance coefficients.
+pub fn irradiance(coefficients: &[Vec4; SH_COEFFICIENT_COUNT], n: Vec3) -> Vec3 {
+    let y = basis(n);
+    let mut e = Vec3::ZERO;
+    for i in 0..SH_COEFFICIENT_COUNT {
+        e += coefficients[i].xyz() * (y[i] * cosine_lobe(i));
+    }
+    e.max(Vec3::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f32::consts::PI;
+    use spirv_std::glam::vec3;
+
+    // Integral of f over the sphere with a midpoint rule over latitude and longitude, for the
+    // exact irradiance the L2 approximation is compared against
+    fn integrate(f: impl Fn(Vec3) -> f32) -> f32 {
+        const THETA_STEPS: usize = 256;
+        const PHI_STEPS: usize = 512;
+        let d_theta = PI / THETA_STEPS as f32;
+        let d_phi = 2.0 * PI / PHI_STEPS as f32;
+        let mut sum = 0.0;
+        for i in 0..THETA_STEPS {
+            let theta = (i as f32 + 0.5) * d_theta;
+            for j in 0..PHI_STEPS {
+                let phi = (j as f32 + 0.5) * d_phi;
+                let d = vec3(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos());
+                sum += f(d) * theta.sin() * d_theta * d_phi;
+            }
+        }
+        sum
+    }
+
+    // Projects an analytic cubemap the way pbribl/shprojection does, with the same grid size
+    fn project(radiance: impl Fn(Vec3) -> Vec3) -> [Vec4; SH_COEFFICIENT_COUNT] {
+        const SAMPLE_SIZE: u32 = 32;
+        let mut coefficients = [Vec3::ZERO; SH_COEFFICIENT_COUNT];
+        let mut weight_sum = 0.0;
+        for index in 0..6 * SAMPLE_SIZE * SAMPLE_SIZE {
+            let (dir, weight) = cube_sample(index, SAMPLE_SIZE);
+            accumulate(&mut coefficients, radiance(dir), dir, weight);
+            weight_sum += weight;
+        }
+        coefficients.map(|c| normalize_projection(c, weight_sum).extend(0.0))
+    }
+
+    #[test]
+    fn constant_environment() {
+        let coefficients = project(|_| Vec3::ONE);
+        assert!((coefficients[0].truncate() - Vec3::splat(2.0 * PI.sqrt())).abs().max_element() < 1e-3, "{coefficients:?}");
+        assert!(coefficients[1..].iter().all(|c| c.abs().max_element() < 1e-3), "{coefficients:?}");
+
+        // A uniform environment of radiance one gives the irradiance pi everywhere
+        for n in [Vec3::X, -Vec3::Y, Vec3::Z, vec3(1.0, -2.0, 3.0).normalize()] {
+            let e = irradiance(&coefficients, n);
+            assert!((e - Vec3::ONE).abs().max_element() < 1e-3, "{n} {e}");
+        }
+    }
+
+    #[test]
+    fn cosine_lobe_environment() {
+        // Clamped cosine around +z, only the zonal coefficients remain
+        let coefficients = project(|d| Vec3::splat(d.z.max(0.0)));
+        let expected = [0.282_095 * PI, 0.0, 0.488_603 * 2.0 * PI / 3.0, 0.0, 0.0, 0.0, 0.315_392 * PI / 2.0, 0.0, 0.0];
+        for (c, e) in coefficients.iter().zip(expected) {
+            assert!((c.truncate() - Vec3::splat(e)).abs().max_element() < 1e-3, "{c} {e}");
+        }
+
+        // L2 keeps the irradiance within a few percent of the integrated one
+        for n in [Vec3::Z, Vec3::X, vec3(1.0, 0.0, 1.0).normalize(), vec3(0.0, 1.0, -1.0).normalize()] {
+            let e = irradiance(&coefficients, n);
+            let exact = integrate(|d| d.z.max(0.0) * d.dot(n).max(0.0)) / PI;
+            assert!((e.x - exact).abs() < 0.03, "{n} {e} {exact}");
+        }
+    }
+}
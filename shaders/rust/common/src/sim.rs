@@ -0,0 +1,235 @@
+//! Host simulation of a compute workgroup, used to test and run the workgroup
+//! and subgroup helpers on the CPU.
+//!
+//! [`dispatch`] runs every invocation of a workgroup on its own thread. On the
+//! host the [`crate::workgroup`], [`crate::subgroup`], [`crate::scan`] and
+//! [`crate::sort`] helpers use the barrier and subgroup operations below in
+//! place of the SPIR-V intrinsics, which are only available in shaders. They
+//! panic when called outside of [`dispatch`].
+//!
+//! Subgroups are formed from consecutive invocations and all of their lanes
+//! are active, which matches uniform control flow on the GPU. An invocation
+//! that panics leaves the others waiting at the next barrier, so keep
+//! assertions outside of the invocation closure.
+
+use spirv_std::glam::UVec4;
+use std::cell::{RefCell, UnsafeCell};
+use std::sync::{Arc, Barrier, Mutex};
+
+/// Built-in inputs of a simulated invocation.
+#[derive(Copy, Clone, Debug)]
+pub struct Invocation {
+    pub local_index: u32,
+    pub subgroup_id: u32,
+    pub subgroup_lane: u32,
+    pub num_subgroups: u32,
+}
+
+/// Memory shared by all invocations of a dispatch, like workgroup memory or a
+/// storage buffer on the GPU.
+pub struct Shared<T>(UnsafeCell<T>);
+
+// Access is synchronized by the simulated barriers, see Shared::get
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    pub fn new(value: T) -> Self {
+        Self(UnsafeCell::new(value))
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0.into_inner()
+    }
+
+    /// The shared value, as an entry point parameter sees it.
+    ///
+    /// # Safety
+    /// Between two barriers an element may only be accessed by the invocation
+    /// that writes it, the same rule that keeps a shader free of data races.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn get(&self) -> &mut T {
+        &mut *self.0.get()
+    }
+}
+
+struct Subgroup {
+    barrier: Barrier,
+    values: Mutex<Vec<u64>>,
+}
+
+struct Context {
+    workgroup: Arc<Barrier>,
+    subgroup: Arc<Subgroup>,
+    lane: u32,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+fn with_context<R>(f: impl FnOnce(&Context) -> R) -> R {
+    CONTEXT.with(|context| f(context.borrow().as_ref().expect("workgroup operation outside of sim::dispatch")))
+}
+
+/// Runs `invocation` once for every invocation of a workgroup of
+/// `workgroup_size` invocations split into subgroups of `subgroup_size` lanes
+/// and returns the results ordered by local invocation index.
+pub fn dispatch<R: Send>(workgroup_size: u32, subgroup_size: u32, invocation: impl Fn(Invocation) -> R + Sync) -> Vec<R> {
+    assert!(workgroup_size > 0 && subgroup_size > 0 && subgroup_size <= 128);
+    let num_subgroups = workgroup_size.div_ceil(subgroup_size);
+    let workgroup = Arc::new(Barrier::new(workgroup_size as usize));
+    let subgroups: Vec<Arc<Subgroup>> = (0..num_subgroups)
+        .map(|id| {
+            let lanes = subgroup_size.min(workgroup_size - id * subgroup_size) as usize;
+            Arc::new(Subgroup { barrier: Barrier::new(lanes), values: Mutex::new(vec![0; lanes]) })
+        })
+        .collect();
+
+    let invocation = &invocation;
+    std::thread::scope(|scope| {
+        let threads: Vec<_> = (0..workgroup_size)
+            .map(|local_index| {
+                let workgroup = workgroup.clone();
+                let subgroup = subgroups[(local_index / subgroup_size) as usize].clone();
+                scope.spawn(move || {
+                    let lane = local_index % subgroup_size;
+                    CONTEXT.with(|context| *context.borrow_mut() = Some(Context { workgroup, subgroup, lane }));
+                    invocation(Invocation {
+                        local_index,
+                        subgroup_id: local_index / subgroup_size,
+                        subgroup_lane: lane,
+                        num_subgroups,
+                    })
+                })
+            })
+            .collect();
+        threads.into_iter().map(|thread| thread.join().expect("invocation panicked")).collect()
+    })
+}
+
+/// Host version of `spirv_std::arch::workgroup_memory_barrier_with_group_sync`.
+///
+/// # Safety
+/// Has to be reached by every invocation of the workgroup.
+pub unsafe fn workgroup_memory_barrier_with_group_sync() {
+    with_context(|context| context.workgroup.wait());
+}
+
+// Publishes this lane's value and returns the values of all lanes of the subgroup
+fn exchange(value: u64) -> (Vec<u64>, usize) {
+    with_context(|context| {
+        let subgroup = &context.subgroup;
+        subgroup.values.lock().unwrap()[context.lane as usize] = value;
+        subgroup.barrier.wait();
+        let values = subgroup.values.lock().unwrap().clone();
+        // Nobody may overwrite the values before every lane has read them
+        subgroup.barrier.wait();
+        (values, context.lane as usize)
+    })
+}
+
+fn exchange_u32(value: u32) -> (Vec<u32>, usize) {
+    let (values, lane) = exchange(value as u64);
+    (values.into_iter().map(|v| v as u32).collect(), lane)
+}
+
+fn exchange_f32(value: f32) -> (Vec<f32>, usize) {
+    let (values, lane) = exchange(value.to_bits() as u64);
+    (values.into_iter().map(|v| f32::from_bits(v as u32)).collect(), lane)
+}
+
+pub fn subgroup_i_add(value: u32) -> u32 {
+    exchange_u32(value).0.into_iter().fold(0, u32::wrapping_add)
+}
+
+pub fn subgroup_u_min(value: u32) -> u32 {
+    exchange_u32(value).0.into_iter().fold(u32::MAX, u32::min)
+}
+
+pub fn subgroup_u_max(value: u32) -> u32 {
+    exchange_u32(value).0.into_iter().fold(0, u32::max)
+}
+
+pub fn subgroup_f_add(value: f32) -> f32 {
+    exchange_f32(value).0.into_iter().sum()
+}
+
+pub fn subgroup_f_min(value: f32) -> f32 {
+    exchange_f32(value).0.into_iter().fold(f32::INFINITY, f32::min)
+}
+
+pub fn subgroup_f_max(value: f32) -> f32 {
+    exchange_f32(value).0.into_iter().fold(f32::NEG_INFINITY, f32::max)
+}
+
+pub fn subgroup_inclusive_i_add(value: u32) -> u32 {
+    let (values, lane) = exchange_u32(value);
+    values[..=lane].iter().fold(0, |a, &b| a.wrapping_add(b))
+}
+
+pub fn subgroup_exclusive_i_add(value: u32) -> u32 {
+    let (values, lane) = exchange_u32(value);
+    values[..lane].iter().fold(0, |a, &b| a.wrapping_add(b))
+}
+
+pub fn subgroup_inclusive_f_add(value: f32) -> f32 {
+    let (values, lane) = exchange_f32(value);
+    values[..=lane].iter().sum()
+}
+
+pub fn subgroup_exclusive_f_add(value: f32) -> f32 {
+    let (values, lane) = exchange_f32(value);
+    values[..lane].iter().sum()
+}
+
+pub fn subgroup_broadcast_first(value: u32) -> u32 {
+    exchange_u32(value).0[0]
+}
+
+pub fn subgroup_broadcast(value: u32, id: u32) -> u32 {
+    exchange_u32(value).0[id as usize]
+}
+
+/// Host version of `spirv_std::arch::SubgroupMask`.
+pub type SubgroupMask = UVec4;
+
+pub fn subgroup_ballot(predicate: bool) -> SubgroupMask {
+    let (values, _) = exchange(predicate as u64);
+    let mut mask = [0; 4];
+    for (lane, &value) in values.iter().enumerate() {
+        mask[lane / 32] |= (value as u32) << (lane % 32);
+    }
+    UVec4::from_array(mask)
+}
+
+pub fn subgroup_ballot_bit_count(mask: SubgroupMask) -> u32 {
+    mask.to_array().iter().map(|bits| bits.count_ones()).sum()
+}
+
+pub fn subgroup_ballot_exclusive_bit_count(mask: SubgroupMask) -> u32 {
+    let lane = with_context(|context| context.lane);
+    let lower = |word: u32| {
+        let first = word * 32;
+        if lane >= first + 32 {
+            u32::MAX
+        } else if lane <= first {
+            0
+        } else {
+            (1 << (lane - first)) - 1
+        }
+    };
+    (0..4).map(|word| (mask[word as usize] & lower(word)).count_ones()).sum()
+}
+
+/// Deterministic pseudo random values below `bound` (xorshift), for test inputs.
+pub fn random_values(count: usize, bound: u32, seed: u32) -> Vec<u32> {
+    let mut state = seed.max(1);
+    (0..count)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state % bound
+        })
+        .collect()
+}
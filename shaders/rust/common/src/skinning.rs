@@ -0,0 +1,238 @@
+//! Joint blending for skinned meshes: linear blend skinning with joint
+//! matrices, and dual quaternion skinning (Kavan et al. 2008), which keeps the
+//! volume of twisting joints instead of collapsing them.
+//!
+//! Influences come in sets of four, as the glTF `JOINTS_n`/`WEIGHTS_n`
+//! attributes do. Dual quaternions only hold rotation and translation, so
+//! skeletons with scaled joints need linear blend skinning.
+
+use spirv_std::glam::{mat3, vec4, Mat4, Quat, Vec3, Vec4, Vec4Swizzles};
+
+/// Rigid transform as a unit dual quaternion, with the quaternions stored as
+/// (x, y, z, w).
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct DualQuat {
+    pub real: Vec4,
+    pub dual: Vec4,
+}
+
+impl DualQuat {
+    pub const ZERO: Self = Self { real: Vec4::ZERO, dual: Vec4::ZERO };
+
+    /// Rotation followed by translation.
+    pub fn from_rotation_translation(rotation: Quat, translation: Vec3) -> Self {
+        let t = Quat::from_xyzw(translation.x, translation.y, translation.z, 0.0);
+        let dual = t * rotation * 0.5;
+        Self { real: Vec4::from(rotation), dual: Vec4::from(dual) }
+    }
+
+    /// Scales both parts so that the real part has unit length again after blending.
+    pub fn normalize(self) -> Self {
+        let inv_length = 1.0 / self.real.length();
+        Self { real: self.real * inv_length, dual: self.dual * inv_length }
+    }
+
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let r = self.real.xyz();
+        v + 2.0 * r.cross(r.cross(v) + self.real.w * v)
+    }
+
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let r = self.real.xyz();
+        let d = self.dual.xyz();
+        let translation = 2.0 * (self.real.w * d - self.dual.w * r + r.cross(d));
+        self.transform_vector(p) + translation
+    }
+}
+
+/// Joint matrices weighted by one set of four influences.
+pub fn blend_matrices(joints: &[Mat4], indices: Vec4, weights: Vec4) -> Mat4 {
+    weights.x * joints[indices.x as usize]
+        + weights.y * joints[indices.y as usize]
+        + weights.z * joints[indices.z as usize]
+        + weights.w * joints[indices.w as usize]
+}
+
+fn accumulate_dual_quat(blended: &mut DualQuat, joint: DualQuat, pivot: Vec4, weight: f32) {
+    let weight = if joint.real.dot(pivot) < 0.0 { -weight } else { weight };
+    blended.real += joint.real * weight;
+    blended.dual += joint.dual * weight;
+}
+
+/// Adds one set of four weighted influences to `blended`. Joints whose real
+/// part lies in the other hemisphere than `pivot` are negated, so that the
+/// blend takes the shorter path between the rotations.
+pub fn accumulate_dual_quats(blended: &mut DualQuat, joints: &[DualQuat], pivot: Vec4, indices: Vec4, weights: Vec4) {
+    accumulate_dual_quat(blended, joints[indices.x as usize], pivot, weights.x);
+    accumulate_dual_quat(blended, joints[indices.y as usize], pivot, weights.y);
+    accumulate_dual_quat(blended, joints[indices.z as usize], pivot, weights.z);
+    accumulate_dual_quat(blended, joints[indices.w as usize], pivot, weights.w);
+}
+
+/// Skinned position, normal and tangent in model space. `influences` holds
+/// the joint indices and weights of `JOINTS_0`/`WEIGHTS_0` and
+/// `JOINTS_1`/`WEIGHTS_1`, the second set is only read with
+/// `eight_influences`. `joint_dual_quats` is read instead of `joint_matrices`
+/// with `dual_quaternion`.
+#[allow(clippy::too_many_arguments)]
+pub fn skin(
+    pos: Vec3,
+    normal: Vec3,
+    tangent: Vec3,
+    joint_matrices: &[Mat4],
+    joint_dual_quats: &[DualQuat],
+    dual_quaternion: bool,
+    influences: [(Vec4, Vec4); 2],
+    eight_influences: bool,
+) -> (Vec3, Vec3, Vec3) {
+    let (indices0, weights0) = influences[0];
+    let (indices1, weights1) = influences[1];
+    if dual_quaternion {
+        let mut blended = DualQuat::ZERO;
+        let pivot = joint_dual_quats[indices0.x as usize].real;
+        accumulate_dual_quats(&mut blended, joint_dual_quats, pivot, indices0, weights0);
+        if eight_influences {
+            accumulate_dual_quats(&mut blended, joint_dual_quats, pivot, indices1, weights1);
+        }
+        let blended = blended.normalize();
+        (blended.transform_point(pos), blended.transform_vector(normal), blended.transform_vector(tangent))
+    } else {
+        let mut skin_mat = blend_matrices(joint_matrices, indices0, weights0);
+        if eight_influences {
+            skin_mat += blend_matrices(joint_matrices, indices1, weights1);
+        }
+        let skin_mat3 = mat3(
+            skin_mat.x_axis.truncate(),
+            skin_mat.y_axis.truncate(),
+            skin_mat.z_axis.truncate(),
+        );
+        ((skin_mat * vec4(pos.x, pos.y, pos.z, 1.0)).truncate(), skin_mat3 * normal, skin_mat3 * tangent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spirv_std::glam::{vec3, vec4, Vec3Swizzles};
+
+    fn blend_dual_quats(joints: &[DualQuat], indices: Vec4, weights: Vec4) -> DualQuat {
+        let mut blended = DualQuat::ZERO;
+        accumulate_dual_quats(&mut blended, joints, joints[indices.x as usize].real, indices, weights);
+        blended.normalize()
+    }
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        a.distance(b) < 1e-4
+    }
+
+    #[test]
+    fn dual_quat_matches_matrix() {
+        let rotations = [
+            Quat::IDENTITY,
+            Quat::from_rotation_x(0.3),
+            Quat::from_axis_angle(vec3(1.0, -2.0, 0.5).normalize(), 2.5),
+            Quat::from_rotation_z(-3.0),
+        ];
+        let translations = [Vec3::ZERO, vec3(1.0, 2.0, 3.0), vec3(-0.5, 0.0, 4.0)];
+        let points = [Vec3::ZERO, Vec3::X, vec3(0.3, -1.2, 2.0), vec3(-5.0, 1.0, 0.5)];
+        for rotation in rotations {
+            for translation in translations {
+                let dq = DualQuat::from_rotation_translation(rotation, translation);
+                let m = Mat4::from_rotation_translation(rotation, translation);
+                for p in points {
+                    assert!(close(dq.transform_point(p), m.transform_point3(p)), "{rotation} {translation} {p}");
+                    assert!(close(dq.transform_vector(p), m.transform_vector3(p)), "{rotation} {translation} {p}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rigid_blend() {
+        // Every joint carries the same transform, so any weights give that transform
+        let rotation = Quat::from_axis_angle(vec3(0.0, 1.0, 1.0).normalize(), 1.2);
+        let translation = vec3(0.5, -1.0, 2.0);
+        let matrices = [Mat4::from_rotation_translation(rotation, translation); 4];
+        let dual_quats = [DualQuat::from_rotation_translation(rotation, translation); 4];
+        let indices = vec4(0.0, 1.0, 2.0, 3.0);
+        for weights in [vec4(1.0, 0.0, 0.0, 0.0), vec4(0.25, 0.25, 0.25, 0.25), vec4(0.1, 0.2, 0.3, 0.4)] {
+            let m = blend_matrices(&matrices, indices, weights);
+            let dq = blend_dual_quats(&dual_quats, indices, weights);
+            for p in [Vec3::ZERO, vec3(1.0, 2.0, -3.0)] {
+                assert!(close(m.transform_point3(p), dq.transform_point(p)), "{weights} {p}");
+            }
+        }
+    }
+
+    #[test]
+    fn twisting_joint() {
+        // Two joints twisted in opposite directions around the bone along x, blended halfway.
+        // Linear blending collapses the skin towards the bone, dual quaternions keep its
+        // distance and agree with the matrices on the bone itself.
+        let twist = 1.2;
+        let matrices = [Mat4::from_rotation_x(twist), Mat4::from_rotation_x(-twist)];
+        let dual_quats = [
+            DualQuat::from_rotation_translation(Quat::from_rotation_x(twist), Vec3::ZERO),
+            DualQuat::from_rotation_translation(Quat::from_rotation_x(-twist), Vec3::ZERO),
+        ];
+        let indices = vec4(0.0, 1.0, 0.0, 0.0);
+        let weights = vec4(0.5, 0.5, 0.0, 0.0);
+        let m = blend_matrices(&matrices, indices, weights);
+        let dq = blend_dual_quats(&dual_quats, indices, weights);
+
+        let p = vec3(0.5, 0.0, 1.0);
+        let radius = p.yz().length();
+        assert!(m.transform_point3(p).yz().length() < 0.5 * radius);
+        assert!((dq.transform_point(p).yz().length() - radius).abs() < 1e-4);
+        assert!(close(dq.transform_point(p), p));
+
+        let on_bone = vec3(2.0, 0.0, 0.0);
+        assert!(close(m.transform_point3(on_bone), dq.transform_point(on_bone)));
+    }
+
+    #[test]
+    fn eight_influences() {
+        // Splitting the weights over both sets of influences gives the same vertex as one set
+        let rotations = [Quat::from_rotation_x(0.4), Quat::from_rotation_y(-0.7), Quat::from_rotation_z(1.1), Quat::IDENTITY];
+        let translations = [vec3(0.0, 1.0, 0.0), vec3(0.5, 0.0, -1.0), Vec3::ZERO, vec3(2.0, 0.0, 0.0)];
+        let matrices: [Mat4; 4] = core::array::from_fn(|i| Mat4::from_rotation_translation(rotations[i], translations[i]));
+        let dual_quats: [DualQuat; 4] = core::array::from_fn(|i| DualQuat::from_rotation_translation(rotations[i], translations[i]));
+        let indices = vec4(0.0, 1.0, 2.0, 3.0);
+        let weights = vec4(0.4, 0.3, 0.2, 0.1);
+        let single = [(indices, weights), (Vec4::ZERO, Vec4::ZERO)];
+        let split = [(indices, weights * 0.5), (indices, weights * 0.5)];
+        let (pos, normal, tangent) = (vec3(0.3, 1.0, -0.5), Vec3::Y, Vec3::X);
+        for dual_quaternion in [false, true] {
+            let (p0, n0, t0) = skin(pos, normal, tangent, &matrices, &dual_quats, dual_quaternion, single, false);
+            let (p1, n1, t1) = skin(pos, normal, tangent, &matrices, &dual_quats, dual_quaternion, split, true);
+            assert!(close(p0, p1) && close(n0, n1) && close(t0, t1), "{dual_quaternion}");
+            // The second set is ignored without eight influences
+            let first_joint = [(Vec4::ZERO, Vec4::X), (indices, weights)];
+            let (p2, _, _) = skin(pos, normal, tangent, &matrices, &dual_quats, dual_quaternion, first_joint, false);
+            assert!(close(p2, matrices[0].transform_point3(pos)), "{dual_quaternion}");
+        }
+    }
+
+    #[test]
+    fn hemisphere_flip() {
+        // q and -q are the same rotation. Without the flip towards the pivot an even blend of
+        // the two would cancel out.
+        let rotation = Quat::from_rotation_y(0.8);
+        let translation = vec3(1.0, 0.0, -2.0);
+        let dq = DualQuat::from_rotation_translation(rotation, translation);
+        let negated = DualQuat { real: -dq.real, dual: -dq.dual };
+        let blended = blend_dual_quats(&[dq, negated], vec4(0.0, 1.0, 0.0, 0.0), vec4(0.5, 0.5, 0.0, 0.0));
+
+        let m = Mat4::from_rotation_translation(rotation, translation);
+        for p in [Vec3::ZERO, vec3(1.0, 2.0, 3.0)] {
+            assert!(close(blended.transform_point(p), m.transform_point3(p)), "{p}");
+        }
+
+        // Twists past 180 degrees apart still blend along the shorter arc
+        let a = DualQuat::from_rotation_translation(Quat::from_rotation_z(3.0), Vec3::ZERO);
+        let b = DualQuat::from_rotation_translation(Quat::from_rotation_z(-3.0), Vec3::ZERO);
+        let blended = blend_dual_quats(&[a, b], vec4(0.0, 1.0, 0.0, 0.0), vec4(0.5, 0.5, 0.0, 0.0));
+        assert!(close(blended.transform_vector(Vec3::X), -Vec3::X), "{}", blended.transform_vector(Vec3::X));
+    }
+}
@@ -0,0 +1,100 @@
+//! Building blocks for the GPU sorting kernels in the `sort` example crates.
+//!
+//! Keys are sorted in ascending order together with a `u32` payload, which is
+//! usually the index of the element the key was generated from.
+
+use crate::scan::{workgroup_exclusive_add, SCAN_SCRATCH_SIZE};
+#[cfg(target_arch = "spirv")]
+use spirv_std::arch::workgroup_memory_barrier_with_group_sync;
+#[cfg(not(target_arch = "spirv"))]
+use crate::sim::workgroup_memory_barrier_with_group_sync;
+
+/// Number of key bits sorted per radix pass, eight passes sort a full `u32`.
+pub const RADIX_BITS: u32 = 4;
+pub const RADIX_BINS: usize = 1 << RADIX_BITS;
+
+/// Radix digit of `key` for the pass that starts at bit `shift`.
+pub fn radix_digit(key: u32, shift: u32) -> u32 {
+    (key >> shift) & (RADIX_BINS as u32 - 1)
+}
+
+/// Maps a float to a `u32` whose unsigned order matches the float order, so
+/// depths and distances can be used as radix keys.
+pub fn sortable_f32(value: f32) -> u32 {
+    let bits = value.to_bits();
+    if bits & 0x8000_0000 != 0 {
+        !bits
+    } else {
+        bits | 0x8000_0000
+    }
+}
+
+/// Stable rank of this invocation's element among the elements of the
+/// workgroup that share its digit.
+///
+/// Invocations without an element pass `valid = false` and don't count
+/// towards any bin. On return `digit_counts` holds the per-digit element
+/// counts of the workgroup. Has to be called by all invocations in uniform
+/// control flow.
+#[allow(clippy::too_many_arguments)]
+pub fn workgroup_digit_rank(
+    digit: u32,
+    valid: bool,
+    local_index: u32,
+    subgroup_id: u32,
+    subgroup_lane: u32,
+    num_subgroups: u32,
+    scan_scratch: &mut [u32; SCAN_SCRATCH_SIZE],
+    digit_counts: &mut [u32; RADIX_BINS],
+) -> u32 {
+    let mut rank = 0;
+    for bin in 0..RADIX_BINS as u32 {
+        let matches = valid && digit == bin;
+        let (prefix, total) = workgroup_exclusive_add(matches as u32, subgroup_id, subgroup_lane, num_subgroups, scan_scratch);
+        if matches {
+            rank = prefix;
+        }
+        if local_index == 0 {
+            digit_counts[bin as usize] = total;
+        }
+    }
+    unsafe {
+        workgroup_memory_barrier_with_group_sync();
+    }
+    rank
+}
+
+/// Sorts `N` key/value pairs held in workgroup memory, `N` being a power of two.
+///
+/// Pairs with equal keys are ordered by value, so with element indices as
+/// values the result is the same as that of a stable sort. Runs on a workgroup
+/// of `N / 2` invocations that each compare and swap one pair per step. Has to
+/// be called by all invocations in uniform control flow.
+#[allow(clippy::manual_swap)]
+pub fn bitonic_sort<const N: usize>(local_index: u32, keys: &mut [u32; N], values: &mut [u32; N]) {
+    let t = local_index as usize;
+    let mut k = 2;
+    while k <= N {
+        let mut j = k / 2;
+        while j > 0 {
+            // Lower element of the pair this invocation is responsible for
+            let i = 2 * j * (t / j) + t % j;
+            let l = i + j;
+            let ascending = i & k == 0;
+            let greater = keys[i] > keys[l] || (keys[i] == keys[l] && values[i] > values[l]);
+            if greater == ascending {
+                let key = keys[i];
+                keys[i] = keys[l];
+                keys[l] = key;
+                let value = values[i];
+                values[i] = values[l];
+                values[l] = value;
+            }
+            unsafe {
+                workgroup_memory_barrier_with_group_sync();
+            }
+            j /= 2;
+        }
+        k *= 2;
+    }
+}
@@ -0,0 +1,167 @@
+//! Subgroup-wide reductions, scans, broadcasts and compaction.
+//!
+//! These operate on the active invocations of the current subgroup only. Use
+//! the [`crate::workgroup`] versions where subgroup operations aren't
+//! available or the result is needed across the whole workgroup.
+
+#[cfg(target_arch = "spirv")]
+use spirv_std::arch::{
+    subgroup_ballot, subgroup_ballot_bit_count, subgroup_ballot_exclusive_bit_count, subgroup_broadcast,
+    subgroup_broadcast_first, subgroup_exclusive_f_add, subgroup_exclusive_i_add, subgroup_f_add, subgroup_f_max,
+    subgroup_f_min, subgroup_i_add, subgroup_inclusive_f_add, subgroup_inclusive_i_add, subgroup_u_max, subgroup_u_min,
+};
+#[cfg(not(target_arch = "spirv"))]
+use crate::sim::{
+    subgroup_ballot, subgroup_ballot_bit_count, subgroup_ballot_exclusive_bit_count, subgroup_broadcast,
+    subgroup_broadcast_first, subgroup_exclusive_f_add, subgroup_exclusive_i_add, subgroup_f_add, subgroup_f_max,
+    subgroup_f_min, subgroup_i_add, subgroup_inclusive_f_add, subgroup_inclusive_i_add, subgroup_u_max, subgroup_u_min,
+};
+
+/// Sum of `value` over the subgroup.
+pub fn reduce_add(value: u32) -> u32 {
+    subgroup_i_add(value)
+}
+
+/// Minimum of `value` over the subgroup.
+pub fn reduce_min(value: u32) -> u32 {
+    subgroup_u_min(value)
+}
+
+/// Maximum of `value` over the subgroup.
+pub fn reduce_max(value: u32) -> u32 {
+    subgroup_u_max(value)
+}
+
+/// Sum of `value` over the subgroup.
+pub fn reduce_add_f32(value: f32) -> f32 {
+    subgroup_f_add(value)
+}
+
+/// Minimum of `value` over the subgroup.
+pub fn reduce_min_f32(value: f32) -> f32 {
+    subgroup_f_min(value)
+}
+
+/// Maximum of `value` over the subgroup.
+pub fn reduce_max_f32(value: f32) -> f32 {
+    subgroup_f_max(value)
+}
+
+/// Sum of `value` over this and all lower lanes.
+pub fn inclusive_add(value: u32) -> u32 {
+    subgroup_inclusive_i_add(value)
+}
+
+/// Sum of `value` over all lower lanes.
+pub fn exclusive_add(value: u32) -> u32 {
+    subgroup_exclusive_i_add(value)
+}
+
+/// Sum of `value` over this and all lower lanes.
+pub fn inclusive_add_f32(value: f32) -> f32 {
+    subgroup_inclusive_f_add(value)
+}
+
+/// Sum of `value` over all lower lanes.
+pub fn exclusive_add_f32(value: f32) -> f32 {
+    subgroup_exclusive_f_add(value)
+}
+
+/// `value` as seen by the lowest active lane.
+pub fn broadcast_first(value: u32) -> u32 {
+    subgroup_broadcast_first(value)
+}
+
+/// `value` as seen by `lane`.
+///
+/// # Safety
+/// `lane` must be dynamically uniform across the subgroup and name an active
+/// invocation.
+pub unsafe fn broadcast(value: u32, lane: u32) -> u32 {
+    subgroup_broadcast(value, lane)
+}
+
+/// Ballot-based stream compaction.
+///
+/// Returns `(slot, count)`: `slot` is the number of lower lanes whose
+/// `predicate` is set, which makes it a dense output index for invocations
+/// that keep their element, and `count` is the number of set predicates in
+/// the subgroup.
+pub fn compact(predicate: bool) -> (u32, u32) {
+    let mask = subgroup_ballot(predicate);
+    (subgroup_ballot_exclusive_bit_count(mask), subgroup_ballot_bit_count(mask))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{dispatch, random_values};
+
+    // Two full subgroups and a partial one
+    const WORKGROUP_SIZE: u32 = 40;
+    const SUBGROUP_SIZE: u32 = 16;
+
+    fn subgroups(values: &[u32]) -> impl Iterator<Item = &[u32]> {
+        values.chunks(SUBGROUP_SIZE as usize)
+    }
+
+    #[test]
+    fn reductions_match_iterator() {
+        let values = random_values(WORKGROUP_SIZE as usize, 1000, 1);
+        let results = dispatch(WORKGROUP_SIZE, SUBGROUP_SIZE, |invocation| {
+            let v = values[invocation.local_index as usize];
+            (reduce_add(v), reduce_min(v), reduce_max(v), reduce_add_f32(v as f32), reduce_max_f32(v as f32))
+        });
+        let expected = subgroups(&values).flat_map(|lanes| {
+            let sum: u32 = lanes.iter().sum();
+            let min = *lanes.iter().min().unwrap();
+            let max = *lanes.iter().max().unwrap();
+            lanes.iter().map(move |_| (sum, min, max, sum as f32, max as f32))
+        });
+        assert!(results.into_iter().eq(expected));
+    }
+
+    #[test]
+    fn scans_match_iterator() {
+        let values = random_values(WORKGROUP_SIZE as usize, 1000, 2);
+        let results = dispatch(WORKGROUP_SIZE, SUBGROUP_SIZE, |invocation| {
+            let v = values[invocation.local_index as usize];
+            (inclusive_add(v), exclusive_add(v), inclusive_add_f32(v as f32), exclusive_add_f32(v as f32))
+        });
+        let expected = subgroups(&values).flat_map(|lanes| {
+            lanes.iter().scan(0, |sum, &v| {
+                *sum += v;
+                Some((*sum, *sum - v, *sum as f32, (*sum - v) as f32))
+            })
+        });
+        assert!(results.into_iter().eq(expected));
+    }
+
+    #[test]
+    fn broadcasts_match_lane() {
+        let values = random_values(WORKGROUP_SIZE as usize, 1000, 3);
+        let results = dispatch(WORKGROUP_SIZE, SUBGROUP_SIZE, |invocation| {
+            let v = values[invocation.local_index as usize];
+            (broadcast_first(v), unsafe { broadcast(v, 3) })
+        });
+        let expected = subgroups(&values).flat_map(|lanes| lanes.iter().map(|_| (lanes[0], lanes[3])));
+        assert!(results.into_iter().eq(expected));
+    }
+
+    #[test]
+    fn compact_matches_filter() {
+        let predicates = random_values(WORKGROUP_SIZE as usize, 2, 4);
+        let results = dispatch(WORKGROUP_SIZE, SUBGROUP_SIZE, |invocation| {
+            compact(predicates[invocation.local_index as usize] == 1)
+        });
+        let expected = subgroups(&predicates).flat_map(|lanes| {
+            let count = lanes.iter().filter(|&&p| p == 1).count() as u32;
+            lanes.iter().scan(0, move |slot, &p| {
+                let result = (*slot, count);
+                *slot += p;
+                Some(result)
+            })
+        });
+        assert!(results.into_iter().eq(expected));
+    }
+}
@@ -0,0 +1,26 @@
+//! Helpers for temporal techniques: motion vectors and the YCoCg color space used for history clamping.
+
+use spirv_std::glam::{vec3, Vec2, Vec3, Vec4, Vec4Swizzles};
+
+/// Screen space motion in texture coordinates between the clip space
+/// positions of a point in the previous and the current frame. The history
+/// of a pixel is found at `uv - velocity`.
+pub fn velocity(clip_pos: Vec4, prev_clip_pos: Vec4) -> Vec2 {
+    (clip_pos.xy() / clip_pos.w - prev_clip_pos.xy() / prev_clip_pos.w) * 0.5
+}
+
+pub fn rgb_to_ycocg(rgb: Vec3) -> Vec3 {
+    vec3(
+        0.25 * rgb.x + 0.5 * rgb.y + 0.25 * rgb.z,
+        0.5 * rgb.x - 0.5 * rgb.z,
+        -0.25 * rgb.x + 0.5 * rgb.y - 0.25 * rgb.z,
+    )
+}
+
+pub fn ycocg_to_rgb(ycocg: Vec3) -> Vec3 {
+    vec3(
+        ycocg.x + ycocg.y - ycocg.z,
+        ycocg.x + ycocg.z,
+        ycocg.x - ycocg.y - ycocg.z,
+    )
+}
@@ -0,0 +1,185 @@
+//! Workgroup-wide reductions, scans, broadcasts and compaction built on shared memory.
+//!
+//! These are the fallbacks for devices without subgroup arithmetic. `N` is the
+//! workgroup size and `scratch` a `#[spirv(workgroup)]` array of that size
+//! declared by the entry point. Every invocation of the workgroup has to call
+//! them in uniform control flow, and the scratch array can be reused as soon
+//! as they return.
+
+#[cfg(target_arch = "spirv")]
+use spirv_std::arch::workgroup_memory_barrier_with_group_sync;
+#[cfg(not(target_arch = "spirv"))]
+use crate::sim::workgroup_memory_barrier_with_group_sync;
+
+fn barrier() {
+    unsafe {
+        workgroup_memory_barrier_with_group_sync();
+    }
+}
+
+/// Inclusive scan of `value` under the associative `op` (Hillis-Steele).
+pub fn inclusive_scan<const N: usize>(
+    value: u32,
+    local_index: u32,
+    scratch: &mut [u32; N],
+    op: impl Fn(u32, u32) -> u32,
+) -> u32 {
+    let i = local_index as usize;
+    scratch[i] = value;
+    barrier();
+
+    let mut offset = 1;
+    while offset < N {
+        let other = if i >= offset { Some(scratch[i - offset]) } else { None };
+        barrier();
+        if let Some(other) = other {
+            scratch[i] = op(other, scratch[i]);
+        }
+        barrier();
+        offset *= 2;
+    }
+
+    let result = scratch[i];
+    barrier();
+    result
+}
+
+/// Reduction of `value` under the associative `op`, returned to every invocation.
+pub fn reduce<const N: usize>(
+    value: u32,
+    local_index: u32,
+    scratch: &mut [u32; N],
+    op: impl Fn(u32, u32) -> u32,
+) -> u32 {
+    let i = local_index as usize;
+    scratch[i] = value;
+    barrier();
+
+    // Tree reduction, N doesn't have to be a power of two
+    let mut active = N;
+    while active > 1 {
+        let half = active.div_ceil(2);
+        if i + half < active {
+            scratch[i] = op(scratch[i], scratch[i + half]);
+        }
+        barrier();
+        active = half;
+    }
+
+    let result = scratch[0];
+    barrier();
+    result
+}
+
+/// Sum of `value` over the workgroup.
+pub fn reduce_add<const N: usize>(value: u32, local_index: u32, scratch: &mut [u32; N]) -> u32 {
+    reduce(value, local_index, scratch, |a, b| a + b)
+}
+
+/// Minimum of `value` over the workgroup.
+pub fn reduce_min<const N: usize>(value: u32, local_index: u32, scratch: &mut [u32; N]) -> u32 {
+    reduce(value, local_index, scratch, |a, b| a.min(b))
+}
+
+/// Maximum of `value` over the workgroup.
+pub fn reduce_max<const N: usize>(value: u32, local_index: u32, scratch: &mut [u32; N]) -> u32 {
+    reduce(value, local_index, scratch, |a, b| a.max(b))
+}
+
+/// Sum of `value` over this and all lower invocations.
+pub fn inclusive_add<const N: usize>(value: u32, local_index: u32, scratch: &mut [u32; N]) -> u32 {
+    inclusive_scan(value, local_index, scratch, |a, b| a + b)
+}
+
+/// Sum of `value` over all lower invocations.
+pub fn exclusive_add<const N: usize>(value: u32, local_index: u32, scratch: &mut [u32; N]) -> u32 {
+    inclusive_add(value, local_index, scratch) - value
+}
+
+/// `value` as seen by invocation `source`.
+pub fn broadcast<const N: usize>(value: u32, source: u32, local_index: u32, scratch: &mut [u32; N]) -> u32 {
+    if local_index == source {
+        scratch[0] = value;
+    }
+    barrier();
+    let result = scratch[0];
+    barrier();
+    result
+}
+
+/// Stream compaction, see [`crate::subgroup::compact`].
+pub fn compact<const N: usize>(predicate: bool, local_index: u32, scratch: &mut [u32; N]) -> (u32, u32) {
+    let inclusive = inclusive_add(predicate as u32, local_index, scratch);
+    let count = broadcast(inclusive, N as u32 - 1, local_index, scratch);
+    (inclusive - predicate as u32, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{dispatch, random_values, Shared};
+
+    // Not a power of two, to cover the partial steps of the reduction
+    const N: usize = 37;
+
+    fn run(values: &[u32], f: impl Fn(u32, u32, &mut [u32; N]) -> u32 + Sync) -> Vec<u32> {
+        let scratch = Shared::new([0; N]);
+        dispatch(N as u32, 32, |invocation| {
+            let i = invocation.local_index;
+            f(values[i as usize], i, unsafe { scratch.get() })
+        })
+    }
+
+    #[test]
+    fn scans_match_iterator() {
+        let values = random_values(N, 100, 1);
+        let inclusive: Vec<u32> = values.iter().scan(0, |sum, &v| { *sum += v; Some(*sum) }).collect();
+        let exclusive: Vec<u32> = inclusive.iter().zip(&values).map(|(sum, v)| sum - v).collect();
+        assert_eq!(run(&values, inclusive_add), inclusive);
+        assert_eq!(run(&values, exclusive_add), exclusive);
+        let max_scan: Vec<u32> = values.iter().scan(0, |max, &v| { *max = v.max(*max); Some(*max) }).collect();
+        assert_eq!(run(&values, |v, i, s| inclusive_scan(v, i, s, u32::max)), max_scan);
+    }
+
+    #[test]
+    fn reductions_match_iterator() {
+        let values = random_values(N, 1000, 2);
+        let sum: u32 = values.iter().sum();
+        let min = *values.iter().min().unwrap();
+        let max = *values.iter().max().unwrap();
+        assert!(run(&values, reduce_add).iter().all(|&r| r == sum));
+        assert!(run(&values, reduce_min).iter().all(|&r| r == min));
+        assert!(run(&values, reduce_max).iter().all(|&r| r == max));
+    }
+
+    #[test]
+    fn broadcast_from_every_source() {
+        let values = random_values(N, 1000, 3);
+        for source in [0, 1, N as u32 / 2, N as u32 - 1] {
+            let results = run(&values, |v, i, s| broadcast(v, source, i, s));
+            assert!(results.iter().all(|&r| r == values[source as usize]));
+        }
+    }
+
+    #[test]
+    fn compact_matches_filter() {
+        for seed in 1..4 {
+            let predicates = random_values(N, 3, seed);
+            let scratch = Shared::new([0; N]);
+            let results = dispatch(N as u32, 32, |invocation| {
+                let i = invocation.local_index;
+                compact(predicates[i as usize] == 0, i, unsafe { scratch.get() })
+            });
+
+            let kept: Vec<usize> = (0..N).filter(|&i| predicates[i] == 0).collect();
+            let mut compacted = vec![usize::MAX; kept.len()];
+            for (i, &(slot, count)) in results.iter().enumerate() {
+                assert_eq!(count as usize, kept.len());
+                if predicates[i] == 0 {
+                    compacted[slot as usize] = i;
+                }
+            }
+            assert_eq!(compacted, kept);
+        }
+    }
+}
@@ -7,6 +7,18 @@ import shutil
 import json
 from pathlib import Path
 
+# Stage suffixes of entry points that name their pass, see compile_shader
+STAGE_SUFFIXES = {
+    "vs": "vert",
+    "fs": "frag",
+    "cs": "comp",
+    "gs": "geom",
+    "tcs": "tesc",
+    "tes": "tese",
+    "task": "task",
+    "mesh": "mesh",
+}
+
 def check_requirements():
     """Check if cargo-gpu is installed"""
     if not shutil.which('cargo'):
@@ -51,8 +63,15 @@ def compile_shader(shader_dir):
                 if not source_path.exists():
                     continue
                 
+                # Entry points named <pass>_<stage>, e.g. fxaa_fs, are written to <pass>.<stage>.spv
+                # so that crates can hold several passes of the same stage
+                output_name = shader_name
+                pass_name, _, stage_suffix = entry_point.rpartition("_")
+                if pass_name and pass_name != "main" and stage_suffix in STAGE_SUFFIXES:
+                    output_name = pass_name
+                    shader_type = STAGE_SUFFIXES[stage_suffix]
                 # Determine the shader type from the entry point name
-                if entry_point == "main_vs" or "vertex" in entry_point.lower():
+                elif entry_point == "main_vs" or "vertex" in entry_point.lower():
                     shader_type = "vert"
                 elif entry_point == "main_fs" or "fragment" in entry_point.lower():
                     shader_type = "frag"
@@ -72,7 +91,7 @@ def compile_shader(shader_dir):
                     # Skip unknown entry points
                     continue
                 
-                final_path = shader_dir / f"{shader_name}.{shader_type}.spv"
+                final_path = shader_dir / f"{output_name}.{shader_type}.spv"
                 
                 # Just rename the file - the C++ code will look for the entry point by name
                 if final_path.exists():
@@ -84,9 +103,9 @@ def compile_shader(shader_dir):
                 # (top-level shaders like triangle, texture, etc. have their own Cargo.toml)
                 parent_dir = shader_dir.parent
                 if not (parent_dir / "Cargo.toml").exists():
-                    parent_final_path = parent_dir / f"{shader_name}.{shader_type}.spv"
+                    parent_final_path = parent_dir / f"{output_name}.{shader_type}.spv"
                     shutil.move(str(final_path), str(parent_final_path))
-                    print(f"  Moved to {parent_dir.name}/{shader_name}.{shader_type}.spv")
+                    print(f"  Moved to {parent_dir.name}/{output_name}.{shader_type}.spv")
         else:
             # Fallback for when no manifest exists
             shader_name = shader_dir.name
@@ -141,6 +160,9 @@ def main():
             # Parse package ID to get the path
             for package in metadata['packages']:
                 if package['id'] == member:
+                    # Skip plain library crates such as `common`, they have no entry points
+                    if not any('dylib' in target['crate_types'] for target in package['targets']):
+                        break
                     package_path = Path(package['manifest_path']).parent
                     # Apply filter if specified
                     if shader_filter:
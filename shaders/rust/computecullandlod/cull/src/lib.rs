@@ -2,7 +2,8 @@
 
 use spirv_std::spirv;
 use spirv_std::glam::{UVec3, Vec3, Vec4, Mat4, Vec4Swizzles};
-use spirv_std::arch::atomic_i_add;
+use spirv_std::arch::{atomic_i_add, workgroup_memory_barrier_with_group_sync};
+use common::scan::{workgroup_exclusive_add, SCAN_SCRATCH_SIZE};
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -55,6 +56,57 @@ fn frustum_check(pos: Vec4, radius: f32, frustum_planes: &[Vec4; 6]) -> bool {
     true
 }
 
+// Culls instance `idx` and selects its LOD, returns true if it is visible
+fn cull_instance(
+    idx: usize,
+    instances: &[InstanceData],
+    indirect_draws: &mut [IndexedIndirectCommand],
+    ubo: &UBO,
+    ubo_out: &mut UBOOut,
+    lods: &[LOD],
+    max_lod_level: u32,
+) -> bool {
+    let pos = Vec4::new(instances[idx].pos[0], instances[idx].pos[1], instances[idx].pos[2], 1.0);
+    
+    // Check if object is within current viewing frustum
+    if !frustum_check(pos, 1.0, &ubo.frustum_planes) {
+        indirect_draws[idx].instance_count = 0;
+        return false;
+    }
+    indirect_draws[idx].instance_count = 1;
+    
+    // Increase number of indirect draw counts
+    unsafe {
+        atomic_i_add::<i32, { spirv_std::memory::Scope::Device as u32 }, { spirv_std::memory::Semantics::NONE.bits() }>(
+            &mut ubo_out.draw_count, 
+            1
+        );
+    }
+    
+    // Select appropriate LOD level based on distance to camera
+    let mut lod_level = max_lod_level;
+    let camera_pos_vec3 = ubo.camera_pos.xyz();
+    let instance_pos = Vec3::new(instances[idx].pos[0], instances[idx].pos[1], instances[idx].pos[2]);
+    let dist = instance_pos.distance(camera_pos_vec3);
+    for i in 0..max_lod_level {
+        if dist < lods[i as usize].distance {
+            lod_level = i;
+            break;
+        }
+    }
+    indirect_draws[idx].first_index = lods[lod_level as usize].first_index;
+    indirect_draws[idx].index_count = lods[lod_level as usize].index_count;
+    
+    // Update stats
+    unsafe {
+        atomic_i_add::<i32, { spirv_std::memory::Scope::Device as u32 }, { spirv_std::memory::Semantics::NONE.bits() }>(
+            &mut ubo_out.lod_count[lod_level as usize], 
+            1
+        );
+    }
+    true
+}
+
 #[spirv(compute(threads(16)))]
 pub fn main_cs(
     #[spirv(global_invocation_id)] global_id: UVec3,
@@ -72,42 +124,52 @@ pub fn main_cs(
         return;
     }
     
-    let pos = Vec4::new(instances[idx].pos[0], instances[idx].pos[1], instances[idx].pos[2], 1.0);
+    cull_instance(idx, instances, indirect_draws, ubo, ubo_out, lods, max_lod_level);
+}
+
+// Same as main_cs, but additionally packs the visible commands densely into compacted_draws so
+// the GPU doesn't have to walk the culled ones. All invocations of a workgroup reserve their slots
+// with a single atomic, and the resulting count can be passed straight to
+// vkCmdDrawIndexedIndirectCount. Compiled to compact.comp.spv, the layout is the one of main_cs
+// plus bindings 5 and 6; draw_count has to be reset to zero by the host before each dispatch.
+#[spirv(compute(threads(16)))]
+pub fn compact_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(local_invocation_index)] local_index: u32,
+    #[spirv(subgroup_id)] subgroup_id: u32,
+    #[spirv(subgroup_local_invocation_id)] subgroup_lane: u32,
+    #[spirv(num_subgroups)] num_subgroups: u32,
+    #[spirv(workgroup)] scan_scratch: &mut [u32; SCAN_SCRATCH_SIZE],
+    #[spirv(workgroup)] workgroup_base: &mut u32,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] instances: &[InstanceData],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] indirect_draws: &mut [IndexedIndirectCommand],
+    #[spirv(uniform, descriptor_set = 0, binding = 2)] ubo: &UBO,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] ubo_out: &mut UBOOut,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 4)] lods: &[LOD],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 5)] compacted_draws: &mut [IndexedIndirectCommand],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 6)] draw_count: &mut u32,
+    #[spirv(spec_constant(id = 0, default = 5))] max_lod_level: u32,
+) {
+    let idx = global_id.x as usize;
     
-    // Check if object is within current viewing frustum
-    if frustum_check(pos, 1.0, &ubo.frustum_planes) {
-        indirect_draws[idx].instance_count = 1;
-        
-        // Increase number of indirect draw counts
-        unsafe {
-            atomic_i_add::<i32, { spirv_std::memory::Scope::Device as u32 }, { spirv_std::memory::Semantics::NONE.bits() }>(
-                &mut ubo_out.draw_count, 
-                1
-            );
-        }
-        
-        // Select appropriate LOD level based on distance to camera
-        let mut lod_level = max_lod_level;
-        let camera_pos_vec3 = ubo.camera_pos.xyz();
-        let instance_pos = Vec3::new(instances[idx].pos[0], instances[idx].pos[1], instances[idx].pos[2]);
-        let dist = instance_pos.distance(camera_pos_vec3);
-        for i in 0..max_lod_level {
-            if dist < lods[i as usize].distance {
-                lod_level = i;
-                break;
-            }
-        }
-        indirect_draws[idx].first_index = lods[lod_level as usize].first_index;
-        indirect_draws[idx].index_count = lods[lod_level as usize].index_count;
-        
-        // Update stats
-        unsafe {
-            atomic_i_add::<i32, { spirv_std::memory::Scope::Device as u32 }, { spirv_std::memory::Semantics::NONE.bits() }>(
-                &mut ubo_out.lod_count[lod_level as usize], 
-                1
-            );
-        }
-    } else {
-        indirect_draws[idx].instance_count = 0;
+    // Out of range invocations can't return early as they still have to take part in the compaction
+    let in_range = idx < instances.len() && idx < indirect_draws.len();
+    let visible = in_range && cull_instance(idx, instances, indirect_draws, ubo, ubo_out, lods, max_lod_level);
+    
+    let (offset, total) = workgroup_exclusive_add(visible as u32, subgroup_id, subgroup_lane, num_subgroups, scan_scratch);
+    if local_index == 0 {
+        *workgroup_base = unsafe {
+            atomic_i_add::<u32, { spirv_std::memory::Scope::Device as u32 }, { spirv_std::memory::Semantics::NONE.bits() }>(
+                draw_count, 
+                total
+            )
+        };
     }
-}
\ No newline at end of file
+    unsafe {
+        workgroup_memory_barrier_with_group_sync();
+    }
+    
+    if visible {
+        compacted_draws[(*workgroup_base + offset) as usize] = indirect_draws[idx];
+    }
+}
@@ -1,8 +1,11 @@
 #![no_std]
 
-use spirv_std::glam::{vec2, vec4, Vec2, Vec3, Vec4, Vec4Swizzles};
-use spirv_std::image::SampledImage;
+use spirv_std::glam::{vec2, vec4, Mat4, UVec4, Vec2, Vec3, Vec4, Vec4Swizzles};
+use spirv_std::image::{Cubemap, SampledImage};
 use spirv_std::{num_traits::Float, spirv, Image};
+use common::cluster::{range_window, ClusterGrid, MAX_LIGHTS_PER_CLUSTER};
+use common::packing::{position_from_depth, unpack_gbuffer};
+use common::pbr::{brdf, f0, f_schlick};
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -19,6 +22,21 @@ pub struct UBO {
     pub display_debug_target: i32,
 }
 
+// Uniforms of deferred_clustered_fs, the lights come from the light buffer of clusteredlighting/cull
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct ClusteredUBO {
+    pub view_pos: Vec4,
+    pub display_debug_target: i32,
+}
+
+// Uniforms of deferred_compact_fs for reconstructing world space positions from depth
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct GBufferUBO {
+    pub inverse_view_projection: Mat4,
+}
+
 #[spirv(vertex)]
 pub fn main_vs(
     #[spirv(vertex_index)] vert_index: i32,
@@ -30,6 +48,25 @@ pub fn main_vs(
     *out_position = vec4(uv.x * 2.0 - 1.0, uv.y * 2.0 - 1.0, 0.0, 1.0);
 }
 
+fn point_light(light: &Light, frag_pos: Vec3, normal: Vec3, albedo: Vec3, material: Vec4, view_pos: Vec3) -> Vec3 {
+    // Vector to light
+    let l_vec = light.position.xyz() - frag_pos;
+    // Distance from light to fragment position
+    let dist = l_vec.length();
+
+    // Viewer to fragment
+    let v = (view_pos - frag_pos).normalize();
+
+    // Light to fragment
+    let l = l_vec.normalize();
+
+    // Attenuation
+    let atten = light.color_radius.w / (dist.powf(2.0) + 1.0);
+
+    // Metallic and roughness are stored in the material mrt
+    brdf(l, v, normal.normalize(), albedo, material.x, material.y, light.color_radius.xyz() * atten)
+}
+
 #[spirv(fragment)]
 pub fn main_fs(
     in_uv: Vec2,
@@ -102,3 +139,256 @@ pub fn main_fs(
 
     *out_frag_color = vec4(frag_color.x, frag_color.y, frag_color.z, 1.0);
 }
+
+// GGX/Smith/Schlick shading of the G-Buffer written by mrt's mrt_pbr_fs, compiled to
+// deferred_pbr.frag.spv. The layout is the one of main_fs plus the material target (metallic,
+// roughness and ambient occlusion) in binding 5.
+#[spirv(fragment)]
+pub fn deferred_pbr_fs(
+    in_uv: Vec2,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_position: &SampledImage<
+        Image!(2D, type=f32, sampled),
+    >,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_normal: &SampledImage<
+        Image!(2D, type=f32, sampled),
+    >,
+    #[spirv(descriptor_set = 0, binding = 3)] sampler_albedo: &SampledImage<
+        Image!(2D, type=f32, sampled),
+    >,
+    #[spirv(uniform, descriptor_set = 0, binding = 4)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 5)] sampler_material: &SampledImage<
+        Image!(2D, type=f32, sampled),
+    >,
+    out_frag_color: &mut Vec4,
+) {
+    // Get G-Buffer values
+    let frag_pos = sampler_position.sample(in_uv).xyz();
+    let normal = sampler_normal.sample(in_uv).xyz();
+    let albedo = sampler_albedo.sample(in_uv);
+    let material = sampler_material.sample(in_uv);
+
+    // Debug display
+    if ubo.display_debug_target > 0 {
+        match ubo.display_debug_target {
+            1 => *out_frag_color = vec4(frag_pos.x, frag_pos.y, frag_pos.z, 1.0),
+            2 => *out_frag_color = vec4(normal.x, normal.y, normal.z, 1.0),
+            3 => *out_frag_color = vec4(albedo.x, albedo.y, albedo.z, 1.0),
+            4 => *out_frag_color = vec4(albedo.w, albedo.w, albedo.w, 1.0),
+            // Targets 6-8 show the material channels in all deferred compositions
+            6 => *out_frag_color = vec4(material.x, material.x, material.x, 1.0),
+            7 => *out_frag_color = vec4(material.y, material.y, material.y, 1.0),
+            8 => *out_frag_color = vec4(material.z, material.z, material.z, 1.0),
+            _ => {}
+        }
+        return;
+    }
+
+    // Render-target composition, without an ambient term like main_fs
+    const LIGHT_COUNT: usize = 6;
+    let mut frag_color = Vec3::ZERO;
+
+    for i in 0..LIGHT_COUNT {
+        frag_color += point_light(&ubo.lights[i], frag_pos, normal, albedo.xyz(), material, ubo.view_pos.xyz());
+    }
+
+    *out_frag_color = vec4(frag_color.x, frag_color.y, frag_color.z, 1.0);
+}
+
+// Sample prefiltered environment map
+fn prefiltered_reflection(r: Vec3, roughness: f32, prefiltered_map: &SampledImage<Cubemap>) -> Vec3 {
+    const MAX_REFLECTION_LOD: f32 = 9.0;
+    let lod = roughness * MAX_REFLECTION_LOD;
+    let lod_f = lod.floor();
+    let lod_c = lod.ceil();
+
+    let a = prefiltered_map.sample_by_lod(r, lod_f).truncate();
+    let b = prefiltered_map.sample_by_lod(r, lod_c).truncate();
+
+    a * (1.0 - (lod - lod_f)) + b * (lod - lod_f)
+}
+
+// deferred_pbr_fs with specular reflections, compiled to deferred_reflections.frag.spv. Layout:
+//   binding 1-5: as for deferred_pbr_fs
+//   binding 6: reflection buffer of ssr/trace, hit colour in rgb and its confidence in alpha
+//   binding 7: prefiltered environment map of pbribl for rays without a screen space hit
+#[spirv(fragment)]
+pub fn deferred_reflections_fs(
+    in_uv: Vec2,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_position: &SampledImage<
+        Image!(2D, type=f32, sampled),
+    >,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_normal: &SampledImage<
+        Image!(2D, type=f32, sampled),
+    >,
+    #[spirv(descriptor_set = 0, binding = 3)] sampler_albedo: &SampledImage<
+        Image!(2D, type=f32, sampled),
+    >,
+    #[spirv(uniform, descriptor_set = 0, binding = 4)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 5)] sampler_material: &SampledImage<
+        Image!(2D, type=f32, sampled),
+    >,
+    #[spirv(descriptor_set = 0, binding = 6)] sampler_reflection: &SampledImage<
+        Image!(2D, type=f32, sampled),
+    >,
+    #[spirv(descriptor_set = 0, binding = 7)] prefiltered_map: &SampledImage<Cubemap>,
+    out_frag_color: &mut Vec4,
+) {
+    // Get G-Buffer values
+    let frag_pos = sampler_position.sample(in_uv).xyz();
+    let normal = sampler_normal.sample(in_uv).xyz();
+    let albedo = sampler_albedo.sample(in_uv);
+    let material = sampler_material.sample(in_uv);
+    let ssr = sampler_reflection.sample(in_uv);
+
+    // Debug display
+    if ubo.display_debug_target > 0 {
+        match ubo.display_debug_target {
+            1 => *out_frag_color = vec4(frag_pos.x, frag_pos.y, frag_pos.z, 1.0),
+            2 => *out_frag_color = vec4(normal.x, normal.y, normal.z, 1.0),
+            3 => *out_frag_color = vec4(albedo.x, albedo.y, albedo.z, 1.0),
+            4 => *out_frag_color = vec4(albedo.w, albedo.w, albedo.w, 1.0),
+            6 => *out_frag_color = vec4(material.x, material.x, material.x, 1.0),
+            7 => *out_frag_color = vec4(material.y, material.y, material.y, 1.0),
+            8 => *out_frag_color = vec4(material.z, material.z, material.z, 1.0),
+            _ => {}
+        }
+        return;
+    }
+
+    const LIGHT_COUNT: usize = 6;
+    let mut frag_color = Vec3::ZERO;
+
+    for i in 0..LIGHT_COUNT {
+        frag_color += point_light(&ubo.lights[i], frag_pos, normal, albedo.xyz(), material, ubo.view_pos.xyz());
+    }
+
+    // Screen space hits replace the environment map by their confidence
+    let n = normal.normalize();
+    let v = (ubo.view_pos.xyz() - frag_pos).normalize();
+    let env = prefiltered_reflection((-v).reflect(n), material.y, prefiltered_map);
+    let reflection = env.lerp(ssr.xyz(), ssr.w);
+    let f = f_schlick(n.dot(v).clamp(0.0, 1.0), f0(albedo.xyz(), material.x));
+    frag_color += reflection * f * material.z;
+
+    *out_frag_color = vec4(frag_color.x, frag_color.y, frag_color.z, 1.0);
+}
+
+// Shading of the compact G-Buffer written by mrt's mrt_compact_fs, compiled to
+// deferred_compact.frag.spv. Positions are reconstructed from the depth attachment. Layout:
+//   binding 1: packed rgba32ui G-Buffer, see common::packing::pack_gbuffer
+//   binding 2: depth attachment
+//   binding 3: GBufferUBO
+//   binding 4: UBO
+#[spirv(fragment)]
+pub fn deferred_compact_fs(
+    in_uv: Vec2,
+    #[spirv(frag_coord)] frag_coord: Vec4,
+    #[spirv(descriptor_set = 0, binding = 1)] gbuffer: &Image!(2D, type=u32, sampled),
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_depth: &SampledImage<
+        Image!(2D, type=f32, sampled),
+    >,
+    #[spirv(uniform, descriptor_set = 0, binding = 3)] gbuffer_ubo: &GBufferUBO,
+    #[spirv(uniform, descriptor_set = 0, binding = 4)] ubo: &UBO,
+    out_frag_color: &mut Vec4,
+) {
+    // Get G-Buffer values
+    let texel: UVec4 = gbuffer.fetch(frag_coord.xy().as_ivec2());
+    let (normal, albedo, material) = unpack_gbuffer(texel);
+    let depth = sampler_depth.sample(in_uv).x;
+    let frag_pos = position_from_depth(in_uv, depth, gbuffer_ubo.inverse_view_projection);
+
+    // Debug display
+    if ubo.display_debug_target > 0 {
+        match ubo.display_debug_target {
+            1 => *out_frag_color = vec4(frag_pos.x, frag_pos.y, frag_pos.z, 1.0),
+            2 => *out_frag_color = vec4(normal.x, normal.y, normal.z, 1.0),
+            3 => *out_frag_color = vec4(albedo.x, albedo.y, albedo.z, 1.0),
+            4 => *out_frag_color = vec4(albedo.w, albedo.w, albedo.w, 1.0),
+            6 => *out_frag_color = vec4(material.x, material.x, material.x, 1.0),
+            7 => *out_frag_color = vec4(material.y, material.y, material.y, 1.0),
+            8 => *out_frag_color = vec4(material.z, material.z, material.z, 1.0),
+            _ => {}
+        }
+        return;
+    }
+
+    const LIGHT_COUNT: usize = 6;
+    let mut frag_color = Vec3::ZERO;
+
+    for i in 0..LIGHT_COUNT {
+        frag_color += point_light(&ubo.lights[i], frag_pos, normal, albedo.xyz(), material, ubo.view_pos.xyz());
+    }
+
+    *out_frag_color = vec4(frag_color.x, frag_color.y, frag_color.z, 1.0);
+}
+
+// Clustered shading with the light lists written by clusteredlighting/cull, compiled to
+// deferred_clustered.frag.spv. The lights only come from the light buffer of the culling pass, so
+// the uniform block has none. Layout:
+//   binding 1-3: position, normal and albedo targets as for main_fs
+//   binding 4:   ClusteredUBO
+//   binding 5:   material target
+//   binding 6:   ClusterGrid
+//   binding 7-9: lights, per cluster light counts and light indices
+#[spirv(fragment)]
+pub fn deferred_clustered_fs(
+    in_uv: Vec2,
+    #[spirv(frag_coord)] frag_coord: Vec4,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_position: &SampledImage<
+        Image!(2D, type=f32, sampled),
+    >,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_normal: &SampledImage<
+        Image!(2D, type=f32, sampled),
+    >,
+    #[spirv(descriptor_set = 0, binding = 3)] sampler_albedo: &SampledImage<
+        Image!(2D, type=f32, sampled),
+    >,
+    #[spirv(uniform, descriptor_set = 0, binding = 4)] ubo: &ClusteredUBO,
+    #[spirv(descriptor_set = 0, binding = 5)] sampler_material: &SampledImage<
+        Image!(2D, type=f32, sampled),
+    >,
+    #[spirv(uniform, descriptor_set = 0, binding = 6)] cluster_grid: &ClusterGrid,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 7)] lights: &[Light],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 8)] cluster_light_counts: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 9)] cluster_light_indices: &[u32],
+    out_frag_color: &mut Vec4,
+) {
+    // Get G-Buffer values
+    let frag_pos = sampler_position.sample(in_uv).xyz();
+    let normal = sampler_normal.sample(in_uv).xyz();
+    let albedo = sampler_albedo.sample(in_uv);
+    let material = sampler_material.sample(in_uv);
+
+    let cluster = cluster_grid.cluster_index(frag_coord.xy(), cluster_grid.view_depth(frag_pos));
+
+    // Debug display
+    if ubo.display_debug_target > 0 {
+        match ubo.display_debug_target {
+            1 => *out_frag_color = vec4(frag_pos.x, frag_pos.y, frag_pos.z, 1.0),
+            2 => *out_frag_color = vec4(normal.x, normal.y, normal.z, 1.0),
+            3 => *out_frag_color = vec4(albedo.x, albedo.y, albedo.z, 1.0),
+            4 => *out_frag_color = vec4(albedo.w, albedo.w, albedo.w, 1.0),
+            5 => {
+                // Lights per cluster, normalized to the cluster capacity
+                let load = cluster_light_counts[cluster as usize] as f32 / MAX_LIGHTS_PER_CLUSTER as f32;
+                *out_frag_color = vec4(load, 1.0 - load, 0.0, 1.0);
+            }
+            6 => *out_frag_color = vec4(material.x, material.x, material.x, 1.0),
+            7 => *out_frag_color = vec4(material.y, material.y, material.y, 1.0),
+            8 => *out_frag_color = vec4(material.z, material.z, material.z, 1.0),
+            _ => {}
+        }
+        return;
+    }
+
+    // Only visit the lights that touch this pixel's cluster
+    let mut frag_color = Vec3::ZERO;
+    let first = cluster as usize * MAX_LIGHTS_PER_CLUSTER;
+    for i in 0..cluster_light_counts[cluster as usize] as usize {
+        let light = &lights[cluster_light_indices[first + i] as usize];
+        let dist = (light.position.xyz() - frag_pos).length();
+        frag_color += point_light(light, frag_pos, normal, albedo.xyz(), material, ubo.view_pos.xyz()) * range_window(dist, light.color_radius.w);
+    }
+
+    *out_frag_color = vec4(frag_color.x, frag_color.y, frag_color.z, 1.0);
+}
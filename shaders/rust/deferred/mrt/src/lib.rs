@@ -1,9 +1,11 @@
 #![no_std]
 
-use spirv_std::glam::{vec4, Mat3, Mat4, Vec2, Vec3, Vec4, Vec4Swizzles};
+use spirv_std::glam::{vec4, Mat3, Mat4, UVec4, Vec2, Vec3, Vec4, Vec4Swizzles};
 use spirv_std::spirv;
 use spirv_std::Image;
 use spirv_std::image::SampledImage;
+use common::packing::pack_gbuffer;
+use common::temporal::velocity;
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -14,6 +16,38 @@ pub struct UBO {
     pub instance_pos: [Vec4; 3],
 }
 
+// Material parameters for the PBR composition, stored in the fourth G-Buffer target
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MaterialPushConsts {
+    pub metallic: f32,
+    pub roughness: f32,
+    pub ao: f32,
+}
+
+// Unjittered transforms of the current and previous frame for the velocity target
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MotionUBO {
+    pub mvp: Mat4,
+    pub prev_mvp: Mat4,
+}
+
+// Normal map sample in world space
+fn tangent_space_normal(
+    in_normal: Vec3,
+    in_tangent: Vec3,
+    in_uv: Vec2,
+    sampler_normal: &SampledImage<Image!(2D, type=f32, sampled)>,
+) -> Vec3 {
+    let n = in_normal.normalize();
+    let t = in_tangent.normalize();
+    let b = n.cross(t);
+    let tbn = Mat3::from_cols(t, b, n);
+    let sampled_normal = sampler_normal.sample(in_uv).xyz() * 2.0 - 1.0;
+    tbn * sampled_normal.normalize()
+}
+
 #[spirv(vertex)]
 pub fn main_vs(
     in_pos: Vec4,
@@ -72,4 +106,116 @@ pub fn main_fs(
     *out_normal = vec4(tnorm.x, tnorm.y, tnorm.z, 1.0);
     
     *out_albedo = sampler_color.sample(in_uv);
-}
\ No newline at end of file
+}
+
+// G-Buffer of the deferred_pbr_fs composition, compiled to mrt_pbr.frag.spv. The vertex stage
+// and bindings are the ones of main_fs, the material parameters come from MaterialPushConsts
+// and go to a fourth target.
+#[spirv(fragment)]
+pub fn mrt_pbr_fs(
+    in_normal: Vec3,
+    in_uv: Vec2,
+    _in_color: Vec3,
+    in_world_pos: Vec3,
+    in_tangent: Vec3,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_color: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_normal: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(push_constant)] material: &MaterialPushConsts,
+    out_position: &mut Vec4,
+    out_normal: &mut Vec4,
+    out_albedo: &mut Vec4,
+    out_material: &mut Vec4,
+) {
+    *out_position = vec4(in_world_pos.x, in_world_pos.y, in_world_pos.z, 1.0);
+
+    let tnorm = tangent_space_normal(in_normal, in_tangent, in_uv, sampler_normal);
+    *out_normal = vec4(tnorm.x, tnorm.y, tnorm.z, 1.0);
+
+    *out_albedo = sampler_color.sample(in_uv);
+
+    *out_material = vec4(material.metallic, material.roughness, material.ao, 1.0);
+}
+
+// Compact G-Buffer of the deferred_compact_fs composition, compiled to mrt_compact.frag.spv.
+// The vertex stage and bindings are the ones of mrt_pbr_fs, but there is no position target
+// (the composition reconstructs positions from the depth attachment) and normal, albedo and
+// material are packed into a single rgba32ui target, see common::packing::pack_gbuffer.
+#[spirv(fragment)]
+pub fn mrt_compact_fs(
+    in_normal: Vec3,
+    in_uv: Vec2,
+    _in_color: Vec3,
+    _in_world_pos: Vec3,
+    in_tangent: Vec3,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_color: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_normal: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(push_constant)] material: &MaterialPushConsts,
+    out_gbuffer: &mut UVec4,
+) {
+    let tnorm = tangent_space_normal(in_normal, in_tangent, in_uv, sampler_normal);
+    let material = vec4(material.metallic, material.roughness, material.ao, 1.0);
+    *out_gbuffer = pack_gbuffer(tnorm.normalize(), sampler_color.sample(in_uv), material);
+}
+
+// Vertex stage of mrt_motion_fs, compiled to mrt_motion.vert.spv. The layout is the one of
+// main_vs plus the MotionUBO in binding 3, the clip space positions of both frames are passed
+// on for the velocity target.
+#[spirv(vertex)]
+pub fn mrt_motion_vs(
+    in_pos: Vec4,
+    in_uv: Vec2,
+    in_color: Vec3,
+    in_normal: Vec3,
+    in_tangent: Vec3,
+    #[spirv(instance_index)] instance_index: i32,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
+    #[spirv(uniform, descriptor_set = 0, binding = 3)] motion: &MotionUBO,
+    #[spirv(position)] out_position: &mut Vec4,
+    out_normal: &mut Vec3,
+    out_uv: &mut Vec2,
+    out_color: &mut Vec3,
+    out_world_pos: &mut Vec3,
+    out_tangent: &mut Vec3,
+    out_clip_pos: &mut Vec4,
+    out_prev_clip_pos: &mut Vec4,
+) {
+    let tmp_pos = in_pos + ubo.instance_pos[instance_index as usize];
+
+    *out_position = ubo.projection * ubo.view * ubo.model * tmp_pos;
+    *out_uv = in_uv;
+    *out_world_pos = (ubo.model * tmp_pos).xyz();
+    *out_normal = in_normal.normalize();
+    *out_tangent = in_tangent.normalize();
+    *out_color = in_color;
+
+    *out_clip_pos = motion.mvp * tmp_pos;
+    *out_prev_clip_pos = motion.prev_mvp * tmp_pos;
+}
+
+// G-Buffer of main_fs plus a velocity target for taa/resolve, compiled to mrt_motion.frag.spv.
+// Bindings are the ones of main_fs.
+#[spirv(fragment)]
+pub fn mrt_motion_fs(
+    in_normal: Vec3,
+    in_uv: Vec2,
+    _in_color: Vec3,
+    in_world_pos: Vec3,
+    in_tangent: Vec3,
+    in_clip_pos: Vec4,
+    in_prev_clip_pos: Vec4,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_color: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_normal: &SampledImage<Image!(2D, type=f32, sampled)>,
+    out_position: &mut Vec4,
+    out_normal: &mut Vec4,
+    out_albedo: &mut Vec4,
+    out_velocity: &mut Vec2,
+) {
+    *out_position = vec4(in_world_pos.x, in_world_pos.y, in_world_pos.z, 1.0);
+
+    let tnorm = tangent_space_normal(in_normal, in_tangent, in_uv, sampler_normal);
+    *out_normal = vec4(tnorm.x, tnorm.y, tnorm.z, 1.0);
+
+    *out_albedo = sampler_color.sample(in_uv);
+
+    *out_velocity = velocity(in_clip_pos, in_prev_clip_pos);
+}
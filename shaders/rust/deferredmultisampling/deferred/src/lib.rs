@@ -3,6 +3,8 @@
 use spirv_std::glam::{ivec2, vec2, vec4, IVec2, UVec2, Vec2, Vec3, Vec4, Vec4Swizzles};
 use spirv_std::image::{ImageWithMethods, sample_with};
 use spirv_std::{num_traits::Float, spirv, Image};
+use common::cluster::{range_window, ClusterGrid, MAX_LIGHTS_PER_CLUSTER};
+use common::pbr::brdf;
 
 #[repr(C, align(16))]
 #[derive(Copy, Clone)]
@@ -20,6 +22,14 @@ pub struct UBO {
     pub debug_display_target: Vec4, // Use Vec4 with debug_display_target in x component
 }
 
+// Uniforms of deferred_clustered_fs, the lights come from the light buffer of clusteredlighting/cull
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct ClusteredUBO {
+    pub view_pos: Vec4,
+    pub debug_display_target: i32,
+}
+
 #[spirv(vertex)]
 pub fn main_vs(
     #[spirv(vertex_index)] vert_index: i32,
@@ -43,6 +53,25 @@ fn resolve(tex: &Image!(2D, format=rgba8, sampled, multisampled), uv: IVec2, num
     result / (num_samples as f32)
 }
 
+fn point_light(light: &Light, pos: Vec3, normal: Vec3, albedo: Vec3, material: Vec4, view_pos: Vec3) -> Vec3 {
+    // Vector to light
+    let l = light.position.xyz() - pos;
+    // Distance from light to fragment position
+    let dist = l.length();
+
+    // Viewer to fragment
+    let v = (view_pos - pos).normalize();
+    
+    // Light to fragment
+    let l = l.normalize();
+
+    // Attenuation
+    let atten = light.color_radius.w / (dist.powf(2.0) + 1.0);
+
+    // Metallic and roughness are stored in the material mrt
+    brdf(l, v, normal.normalize(), albedo, material.x, material.y, light.color_radius.xyz() * atten)
+}
+
 fn calculate_lighting(pos: Vec3, normal: Vec3, albedo: Vec4, ubo: &UBO) -> Vec3 {
     let mut result = Vec3::ZERO;
 
@@ -76,6 +105,40 @@ fn calculate_lighting(pos: Vec3, normal: Vec3, albedo: Vec4, ubo: &UBO) -> Vec3
     result
 }
 
+fn calculate_pbr_lighting(pos: Vec3, normal: Vec3, albedo: Vec4, material: Vec4, ubo: &UBO) -> Vec3 {
+    let mut result = Vec3::ZERO;
+
+    for i in 0..NUM_LIGHTS {
+        result += point_light(&ubo.lights[i], pos, normal, albedo.xyz(), material, ubo.view_pos.xyz());
+    }
+    result
+}
+
+// Only visits the lights of the sample's cluster, see clusteredlighting/cull
+fn calculate_clustered_lighting(
+    pos: Vec3,
+    normal: Vec3,
+    albedo: Vec4,
+    material: Vec4,
+    frag_coord: Vec2,
+    view_pos: Vec3,
+    grid: &ClusterGrid,
+    lights: &[Light],
+    light_counts: &[u32],
+    light_indices: &[u32],
+) -> Vec3 {
+    let mut result = Vec3::ZERO;
+
+    let cluster = grid.cluster_index(frag_coord, grid.view_depth(pos));
+    let first = cluster as usize * MAX_LIGHTS_PER_CLUSTER;
+    for i in 0..light_counts[cluster as usize] as usize {
+        let light = &lights[light_indices[first + i] as usize];
+        let dist = (light.position.xyz() - pos).length();
+        result += point_light(light, pos, normal, albedo.xyz(), material, view_pos) * range_window(dist, light.color_radius.w);
+    }
+    result
+}
+
 #[spirv(fragment)]
 pub fn main_fs(
     in_uv: Vec2,
@@ -122,4 +185,158 @@ pub fn main_fs(
     frag_color = (alb.xyz() * AMBIENT) + frag_color / (num_samples as f32);
    
     *out_frag_color = vec4(frag_color.x, frag_color.y, frag_color.z, 1.0);
-}
\ No newline at end of file
+}
+
+// GGX/Smith/Schlick shading of the G-Buffer written by mrt's mrt_pbr_fs, compiled to
+// deferred_pbr.frag.spv. The layout is the one of main_fs plus the material target (metallic,
+// roughness and ambient occlusion) in binding 5.
+#[spirv(fragment)]
+pub fn deferred_pbr_fs(
+    in_uv: Vec2,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_position: &Image!(2D, format=rgba16f, sampled, multisampled),
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_normal: &Image!(2D, format=rgba16f, sampled, multisampled),
+    #[spirv(descriptor_set = 0, binding = 3)] sampler_albedo: &Image!(2D, format=rgba8, sampled, multisampled),
+    #[spirv(uniform, descriptor_set = 0, binding = 4)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 5)] sampler_material: &Image!(2D, format=rgba8, sampled, multisampled),
+    #[spirv(spec_constant(id = 0, default = 8))] num_samples: u32,
+    out_frag_color: &mut Vec4,
+) {
+    let att_dim: UVec2 = sampler_position.query_size();
+    let uv = ivec2((in_uv.x * att_dim.x as f32) as i32, (in_uv.y * att_dim.y as f32) as i32);
+    
+    // Debug display
+    if ubo.debug_display_target.x as i32 > 0 {
+        let val: Vec4 = match ubo.debug_display_target.x as i32 {
+            1 => sampler_position.fetch_with(uv, sample_with::sample_index(0)),
+            2 => sampler_normal.fetch_with(uv, sample_with::sample_index(0)),
+            3 => sampler_albedo.fetch_with(uv, sample_with::sample_index(0)),
+            4 => {
+                let alb: Vec4 = sampler_albedo.fetch_with(uv, sample_with::sample_index(0));
+                vec4(alb.w, alb.w, alb.w, 1.0)
+            },
+            6..=8 => {
+                let material: Vec4 = sampler_material.fetch_with(uv, sample_with::sample_index(0));
+                let channel = match ubo.debug_display_target.x as i32 {
+                    6 => material.x,
+                    7 => material.y,
+                    _ => material.z,
+                };
+                vec4(channel, channel, channel, 1.0)
+            },
+            _ => Vec4::ZERO,
+        };
+        *out_frag_color = vec4(val.x, val.y, val.z, 1.0);
+        return;
+    }
+
+    const AMBIENT: f32 = 0.15;
+
+    // Ambient part
+    let alb = resolve(sampler_albedo, uv, num_samples);
+    let ao = resolve(sampler_material, uv, num_samples).z;
+    let mut frag_color = Vec3::ZERO;
+    
+    // Calculate lighting for every MSAA sample
+    for i in 0..num_samples {
+        let pos: Vec4 = sampler_position.fetch_with(uv, sample_with::sample_index(i as i32));
+        let normal: Vec4 = sampler_normal.fetch_with(uv, sample_with::sample_index(i as i32));
+        let albedo: Vec4 = sampler_albedo.fetch_with(uv, sample_with::sample_index(i as i32));
+        let material: Vec4 = sampler_material.fetch_with(uv, sample_with::sample_index(i as i32));
+        frag_color += calculate_pbr_lighting(pos.xyz(), normal.xyz(), albedo, material, ubo);
+    }
+
+    frag_color = (alb.xyz() * AMBIENT * ao) + frag_color / (num_samples as f32);
+   
+    *out_frag_color = vec4(frag_color.x, frag_color.y, frag_color.z, 1.0);
+}
+
+// Clustered shading with the light lists written by clusteredlighting/cull, compiled to
+// deferred_clustered.frag.spv. The lights only come from the light buffer of the culling pass, so
+// the uniform block has none. Layout:
+//   binding 1-3: position, normal and albedo targets as for main_fs
+//   binding 4:   ClusteredUBO
+//   binding 5:   material target
+//   binding 6:   ClusterGrid
+//   binding 7-9: lights, per cluster light counts and light indices
+#[spirv(fragment)]
+pub fn deferred_clustered_fs(
+    in_uv: Vec2,
+    #[spirv(frag_coord)] frag_coord: Vec4,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_position: &Image!(2D, format=rgba16f, sampled, multisampled),
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_normal: &Image!(2D, format=rgba16f, sampled, multisampled),
+    #[spirv(descriptor_set = 0, binding = 3)] sampler_albedo: &Image!(2D, format=rgba8, sampled, multisampled),
+    #[spirv(uniform, descriptor_set = 0, binding = 4)] ubo: &ClusteredUBO,
+    #[spirv(descriptor_set = 0, binding = 5)] sampler_material: &Image!(2D, format=rgba8, sampled, multisampled),
+    #[spirv(uniform, descriptor_set = 0, binding = 6)] cluster_grid: &ClusterGrid,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 7)] lights: &[Light],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 8)] cluster_light_counts: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 9)] cluster_light_indices: &[u32],
+    #[spirv(spec_constant(id = 0, default = 8))] num_samples: u32,
+    out_frag_color: &mut Vec4,
+) {
+    let att_dim: UVec2 = sampler_position.query_size();
+    let uv = ivec2((in_uv.x * att_dim.x as f32) as i32, (in_uv.y * att_dim.y as f32) as i32);
+
+    // Debug display
+    if ubo.debug_display_target > 0 {
+        let val: Vec4 = match ubo.debug_display_target {
+            1 => sampler_position.fetch_with(uv, sample_with::sample_index(0)),
+            2 => sampler_normal.fetch_with(uv, sample_with::sample_index(0)),
+            3 => sampler_albedo.fetch_with(uv, sample_with::sample_index(0)),
+            4 => {
+                let alb: Vec4 = sampler_albedo.fetch_with(uv, sample_with::sample_index(0));
+                vec4(alb.w, alb.w, alb.w, 1.0)
+            },
+            5 => {
+                // Lights per cluster of the first sample, normalized to the cluster capacity
+                let pos: Vec4 = sampler_position.fetch_with(uv, sample_with::sample_index(0));
+                let cluster = cluster_grid.cluster_index(frag_coord.xy(), cluster_grid.view_depth(pos.xyz()));
+                let load = cluster_light_counts[cluster as usize] as f32 / MAX_LIGHTS_PER_CLUSTER as f32;
+                vec4(load, 1.0 - load, 0.0, 1.0)
+            },
+            6..=8 => {
+                let material: Vec4 = sampler_material.fetch_with(uv, sample_with::sample_index(0));
+                let channel = match ubo.debug_display_target {
+                    6 => material.x,
+                    7 => material.y,
+                    _ => material.z,
+                };
+                vec4(channel, channel, channel, 1.0)
+            },
+            _ => Vec4::ZERO,
+        };
+        *out_frag_color = vec4(val.x, val.y, val.z, 1.0);
+        return;
+    }
+
+    const AMBIENT: f32 = 0.15;
+
+    // Ambient part
+    let alb = resolve(sampler_albedo, uv, num_samples);
+    let ao = resolve(sampler_material, uv, num_samples).z;
+    let mut frag_color = Vec3::ZERO;
+
+    // Calculate lighting for every MSAA sample
+    for i in 0..num_samples {
+        let pos: Vec4 = sampler_position.fetch_with(uv, sample_with::sample_index(i as i32));
+        let normal: Vec4 = sampler_normal.fetch_with(uv, sample_with::sample_index(i as i32));
+        let albedo: Vec4 = sampler_albedo.fetch_with(uv, sample_with::sample_index(i as i32));
+        let material: Vec4 = sampler_material.fetch_with(uv, sample_with::sample_index(i as i32));
+        frag_color += calculate_clustered_lighting(
+            pos.xyz(),
+            normal.xyz(),
+            albedo,
+            material,
+            frag_coord.xy(),
+            ubo.view_pos.xyz(),
+            cluster_grid,
+            lights,
+            cluster_light_counts,
+            cluster_light_indices,
+        );
+    }
+
+    frag_color = (alb.xyz() * AMBIENT * ao) + frag_color / (num_samples as f32);
+
+    *out_frag_color = vec4(frag_color.x, frag_color.y, frag_color.z, 1.0);
+}
@@ -14,6 +14,30 @@ pub struct UBO {
     pub instance_pos: [Vec4; 3],
 }
 
+// Material parameters for the PBR composition, stored in the fourth G-Buffer target
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MaterialPushConsts {
+    pub metallic: f32,
+    pub roughness: f32,
+    pub ao: f32,
+}
+
+// Normal map sample in world space
+fn tangent_space_normal(
+    in_normal: Vec3,
+    in_tangent: Vec3,
+    in_uv: Vec2,
+    sampler_normal_map: &SampledImage<Image!(2D, type=f32, sampled)>,
+) -> Vec3 {
+    let n = in_normal.normalize();
+    let t = in_tangent.normalize();
+    let b = n.cross(t);
+    let tbn = Mat3::from_cols(t, b, n);
+    let sampled_normal = sampler_normal_map.sample(in_uv).xyz() * 2.0 - Vec3::splat(1.0);
+    tbn * sampled_normal.normalize()
+}
+
 #[spirv(vertex)]
 pub fn main_vs(
     in_pos: Vec4,
@@ -73,4 +97,32 @@ pub fn main_fs(
     *out_normal = vec4(tnorm.x, tnorm.y, tnorm.z, 1.0);
     
     *out_albedo = sampler_color.sample(in_uv);
-}
\ No newline at end of file
+}
+
+// G-Buffer of the deferred_pbr_fs composition, compiled to mrt_pbr.frag.spv. The vertex stage
+// and bindings are the ones of main_fs, the material parameters come from MaterialPushConsts
+// and go to a fourth target.
+#[spirv(fragment)]
+pub fn mrt_pbr_fs(
+    in_normal: Vec3,
+    in_uv: Vec2,
+    _in_color: Vec3,
+    in_world_pos: Vec3,
+    in_tangent: Vec3,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_color: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_normal_map: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(push_constant)] material: &MaterialPushConsts,
+    out_position: &mut Vec4,
+    out_normal: &mut Vec4,
+    out_albedo: &mut Vec4,
+    out_material: &mut Vec4,
+) {
+    *out_position = vec4(in_world_pos.x, in_world_pos.y, in_world_pos.z, 1.0);
+
+    let tnorm = tangent_space_normal(in_normal, in_tangent, in_uv, sampler_normal_map);
+    *out_normal = vec4(tnorm.x, tnorm.y, tnorm.z, 1.0);
+
+    *out_albedo = sampler_color.sample(in_uv);
+
+    *out_material = vec4(material.metallic, material.roughness, material.ao, 1.0);
+}
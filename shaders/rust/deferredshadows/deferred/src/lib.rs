@@ -1,11 +1,13 @@
 #![cfg_attr(target_arch = "spirv", no_std)]
 
 use spirv_std::{
-    glam::{Mat4, Vec2, Vec3, Vec4, Vec4Swizzles},
+    glam::{Mat4, UVec4, Vec2, Vec3, Vec4, Vec4Swizzles},
     spirv, Image,
     image::SampledImage,
     num_traits::Float,
 };
+use common::packing::{position_from_depth, unpack_gbuffer};
+use common::pbr::brdf;
 
 const LIGHT_COUNT: usize = 3;
 const SHADOW_FACTOR: f32 = 0.25;
@@ -31,6 +33,13 @@ pub struct UBO {
     pub display_debug_target: i32,
 }
 
+// Uniforms of deferred_compact_fs for reconstructing world space positions from depth
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct GBufferUBO {
+    pub inverse_view_projection: Mat4,
+}
+
 fn texture_proj(
     shadow_map: &SampledImage<Image!(2D, type=f32, sampled, arrayed)>,
     p: Vec4,
@@ -99,6 +108,41 @@ fn shadow(
     result
 }
 
+// GGX/Smith/Schlick shading of the spot lights plus an ambient term scaled by ambient occlusion
+fn pbr_lighting(frag_pos: Vec3, normal: Vec3, albedo: Vec4, material: Vec4, ubo: &UBO) -> Vec3 {
+    // Ambient part
+    let mut frag_color = albedo.xyz() * AMBIENT_LIGHT * material.z;
+
+    let n = normal.normalize();
+
+    for i in 0..LIGHT_COUNT {
+        // Vector to light
+        let mut l = ubo.lights[i].position.xyz() - frag_pos;
+        let dist = l.length();
+        l = l.normalize();
+
+        // Viewer to fragment
+        let v = (ubo.view_pos.xyz() - frag_pos).normalize();
+
+        let light_cos_inner_angle = 15.0f32.to_radians().cos();
+        let light_cos_outer_angle = 25.0f32.to_radians().cos();
+        let light_range = 100.0;
+
+        // Direction vector from source to target
+        let dir = (ubo.lights[i].position.xyz() - ubo.lights[i].target.xyz()).normalize();
+
+        // Dual cone spot light with smooth transition between inner and outer angle
+        let cos_dir = l.dot(dir);
+        let spot_effect = smoothstep(light_cos_outer_angle, light_cos_inner_angle, cos_dir);
+        let height_attenuation = smoothstep(light_range, 0.0, dist);
+
+        let radiance = ubo.lights[i].color.xyz() * spot_effect * height_attenuation;
+        frag_color += brdf(l, v, n, albedo.xyz(), material.x, material.y, radiance);
+    }
+
+    frag_color
+}
+
 #[spirv(vertex)]
 pub fn main_vs(
     #[spirv(vertex_index)] vertex_index: u32,
@@ -190,6 +234,109 @@ pub fn main_fs(
     *out_frag_color = Vec4::new(frag_color.x, frag_color.y, frag_color.z, 1.0);
 }
 
+// GGX/Smith/Schlick shading of the G-Buffer written by mrt's mrt_pbr_fs, compiled to
+// deferred_pbr.frag.spv. The layout is the one of main_fs plus the material target (metallic,
+// roughness and ambient occlusion) in binding 6.
+#[spirv(fragment)]
+pub fn deferred_pbr_fs(
+    in_uv: Vec2,
+    #[spirv(descriptor_set = 0, binding = 1)] position_sampler: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(descriptor_set = 0, binding = 2)] normal_sampler: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(descriptor_set = 0, binding = 3)] albedo_sampler: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(uniform, descriptor_set = 0, binding = 4)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 5)] shadow_map: &SampledImage<Image!(2D, type=f32, sampled, arrayed)>,
+    #[spirv(descriptor_set = 0, binding = 6)] material_sampler: &SampledImage<Image!(2D, type=f32, sampled)>,
+    out_frag_color: &mut Vec4,
+) {
+    // Get G-Buffer values
+    let frag_pos = position_sampler.sample(in_uv).xyz();
+    let normal = normal_sampler.sample(in_uv).xyz();
+    let albedo = albedo_sampler.sample(in_uv);
+    // Metallic, roughness and ambient occlusion
+    let material = material_sampler.sample(in_uv);
+
+    let mut frag_color;
+
+    // Debug display
+    if ubo.display_debug_target > 0 {
+        frag_color = match ubo.display_debug_target {
+            1 => shadow(Vec3::ONE, frag_pos, ubo, shadow_map),
+            2 => frag_pos,
+            3 => normal,
+            4 => albedo.xyz(),
+            5 => Vec3::splat(albedo.w),
+            6 => Vec3::splat(material.x),
+            7 => Vec3::splat(material.y),
+            8 => Vec3::splat(material.z),
+            _ => Vec3::ZERO,
+        };
+        *out_frag_color = Vec4::new(frag_color.x, frag_color.y, frag_color.z, 1.0);
+        return;
+    }
+
+    frag_color = pbr_lighting(frag_pos, normal, albedo, material, ubo);
+
+    // Shadow calculations in a separate pass
+    if ubo.use_shadows > 0 {
+        frag_color = shadow(frag_color, frag_pos, ubo, shadow_map);
+    }
+
+    *out_frag_color = Vec4::new(frag_color.x, frag_color.y, frag_color.z, 1.0);
+}
+
+// Shading of the compact G-Buffer written by mrt's mrt_compact_fs, compiled to
+// deferred_compact.frag.spv. Positions are reconstructed from the depth attachment. Layout:
+//   binding 1: packed rgba32ui G-Buffer, see common::packing::pack_gbuffer
+//   binding 2: depth attachment
+//   binding 3: GBufferUBO
+//   binding 4: UBO
+//   binding 5: shadow map as for main_fs
+#[spirv(fragment)]
+pub fn deferred_compact_fs(
+    in_uv: Vec2,
+    #[spirv(frag_coord)] frag_coord: Vec4,
+    #[spirv(descriptor_set = 0, binding = 1)] gbuffer: &Image!(2D, type=u32, sampled),
+    #[spirv(descriptor_set = 0, binding = 2)] depth_sampler: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(uniform, descriptor_set = 0, binding = 3)] gbuffer_ubo: &GBufferUBO,
+    #[spirv(uniform, descriptor_set = 0, binding = 4)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 5)] shadow_map: &SampledImage<Image!(2D, type=f32, sampled, arrayed)>,
+    out_frag_color: &mut Vec4,
+) {
+    // Get G-Buffer values
+    let texel: UVec4 = gbuffer.fetch(frag_coord.xy().as_ivec2());
+    let (normal, albedo, material) = unpack_gbuffer(texel);
+    let depth = depth_sampler.sample(in_uv).x;
+    let frag_pos = position_from_depth(in_uv, depth, gbuffer_ubo.inverse_view_projection);
+
+    let mut frag_color;
+
+    // Debug display
+    if ubo.display_debug_target > 0 {
+        frag_color = match ubo.display_debug_target {
+            1 => shadow(Vec3::ONE, frag_pos, ubo, shadow_map),
+            2 => frag_pos,
+            3 => normal,
+            4 => albedo.xyz(),
+            5 => Vec3::splat(albedo.w),
+            6 => Vec3::splat(material.x),
+            7 => Vec3::splat(material.y),
+            8 => Vec3::splat(material.z),
+            _ => Vec3::ZERO,
+        };
+        *out_frag_color = Vec4::new(frag_color.x, frag_color.y, frag_color.z, 1.0);
+        return;
+    }
+
+    frag_color = pbr_lighting(frag_pos, normal, albedo, material, ubo);
+
+    // Shadow calculations in a separate pass
+    if ubo.use_shadows > 0 {
+        frag_color = shadow(frag_color, frag_pos, ubo, shadow_map);
+    }
+
+    *out_frag_color = Vec4::new(frag_color.x, frag_color.y, frag_color.z, 1.0);
+}
+
 fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
     let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
     t * t * (3.0 - 2.0 * t)
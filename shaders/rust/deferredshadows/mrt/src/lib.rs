@@ -1,10 +1,11 @@
 #![cfg_attr(target_arch = "spirv", no_std)]
 
 use spirv_std::{
-    glam::{Mat4, Vec2, Vec3, Vec4, Vec4Swizzles},
+    glam::{Mat4, UVec4, Vec2, Vec3, Vec4, Vec4Swizzles},
     spirv, Image,
     image::SampledImage,
 };
+use common::packing::pack_gbuffer;
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -15,6 +16,30 @@ pub struct UBO {
     pub instance_pos: [Vec4; 3],
 }
 
+// Material parameters for the PBR composition, stored in the fourth G-Buffer target
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MaterialPushConsts {
+    pub metallic: f32,
+    pub roughness: f32,
+    pub ao: f32,
+}
+
+// Normal map sample in world space
+fn tangent_space_normal(
+    in_normal: Vec3,
+    in_tangent: Vec3,
+    in_uv: Vec2,
+    sampler_normal_map: &SampledImage<Image!(2D, type=f32, sampled)>,
+) -> Vec3 {
+    let n = in_normal.normalize();
+    let t = in_tangent.normalize();
+    let b = n.cross(t);
+    let tnorm = (sampler_normal_map.sample(in_uv).xyz() * 2.0 - Vec3::ONE).normalize();
+    // TBN matrix multiplication - transforms from tangent space to world space
+    t * tnorm.x + b * tnorm.y + n * tnorm.z
+}
+
 #[spirv(vertex)]
 pub fn main_vs(
     in_pos: Vec4,
@@ -73,4 +98,53 @@ pub fn main_fs(
     *out_normal = Vec4::new(tnorm.x, tnorm.y, tnorm.z, 1.0);
     
     *out_albedo = sampler_color.sample(in_uv);
-}
\ No newline at end of file
+}
+
+// G-Buffer of the deferred_pbr_fs composition, compiled to mrt_pbr.frag.spv. The vertex stage
+// and bindings are the ones of main_fs, the material parameters come from MaterialPushConsts
+// and go to a fourth target.
+#[spirv(fragment)]
+pub fn mrt_pbr_fs(
+    in_normal: Vec3,
+    in_uv: Vec2,
+    _in_color: Vec3,
+    in_world_pos: Vec3,
+    in_tangent: Vec3,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_color: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_normal_map: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(push_constant)] material: &MaterialPushConsts,
+    out_position: &mut Vec4,
+    out_normal: &mut Vec4,
+    out_albedo: &mut Vec4,
+    out_material: &mut Vec4,
+) {
+    *out_position = Vec4::new(in_world_pos.x, in_world_pos.y, in_world_pos.z, 1.0);
+
+    let tnorm = tangent_space_normal(in_normal, in_tangent, in_uv, sampler_normal_map);
+    *out_normal = Vec4::new(tnorm.x, tnorm.y, tnorm.z, 1.0);
+
+    *out_albedo = sampler_color.sample(in_uv);
+
+    *out_material = Vec4::new(material.metallic, material.roughness, material.ao, 1.0);
+}
+
+// Compact G-Buffer of the deferred_compact_fs composition, compiled to mrt_compact.frag.spv.
+// The vertex stage and bindings are the ones of mrt_pbr_fs, but there is no position target
+// (the composition reconstructs positions from the depth attachment) and normal, albedo and
+// material are packed into a single rgba32ui target, see common::packing::pack_gbuffer.
+#[spirv(fragment)]
+pub fn mrt_compact_fs(
+    in_normal: Vec3,
+    in_uv: Vec2,
+    _in_color: Vec3,
+    _in_world_pos: Vec3,
+    in_tangent: Vec3,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_color: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_normal_map: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(push_constant)] material: &MaterialPushConsts,
+    out_gbuffer: &mut UVec4,
+) {
+    let tnorm = tangent_space_normal(in_normal, in_tangent, in_uv, sampler_normal_map);
+    let material = Vec4::new(material.metallic, material.roughness, material.ao, 1.0);
+    *out_gbuffer = pack_gbuffer(tnorm.normalize(), sampler_color.sample(in_uv), material);
+}
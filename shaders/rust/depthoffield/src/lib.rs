@@ -0,0 +1,198 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Depth of field with a thin lens circle of confusion.
+//
+// All passes are fullscreen triangles, the first three run at half resolution:
+//   coc_fs:       splits the scene into a near and a far field (color with the normalized
+//                 circle of confusion in alpha) and moves the energy of bright defocused
+//                 pixels into a separate highlight target
+//   blur_fs:      separable blur of both fields, run horizontally and then vertically
+//   bokeh_fs:     gathers the highlights into disk shaped bokeh
+//   composite_fs: blends the fields and the bokeh with the sharp image at full resolution
+// The circle of confusion is signed, negative in front of the focus distance.
+
+use core::f32::consts::PI;
+use spirv_std::glam::{vec2, vec3, vec4, Vec2, Vec3, Vec4, Vec4Swizzles};
+use spirv_std::image::SampledImage;
+use spirv_std::{num_traits::Float, spirv, Image};
+use common::packing::view_distance;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct UBO {
+    // Texel size of the half resolution targets
+    pub texel_size: Vec2,
+    pub z_near: f32,
+    pub z_far: f32,
+    // View space distance that is in focus
+    pub focus_distance: f32,
+    // Scales the circle of confusion, larger apertures give a shallower depth of field
+    pub aperture: f32,
+    // Radius of the largest circle of confusion in full resolution pixels
+    pub max_coc_radius: f32,
+    // Luminance above which defocused pixels turn into bokeh highlights
+    pub highlight_threshold: f32,
+}
+
+type Texture2D = SampledImage<Image!(2D, type=f32, sampled)>;
+
+// Taps on each side of the blurred pixel
+const BLUR_TAPS: i32 = 8;
+const BOKEH_SAMPLES: u32 = 48;
+const GOLDEN_ANGLE: f32 = 2.399_963;
+
+fn circle_of_confusion(depth: f32, ubo: &UBO) -> f32 {
+    let z = view_distance(depth, ubo.z_near, ubo.z_far);
+    (ubo.aperture * (z - ubo.focus_distance) / z).clamp(-1.0, 1.0)
+}
+
+fn luminance(color: Vec3) -> f32 {
+    color.dot(vec3(0.2126, 0.7152, 0.0722))
+}
+
+#[spirv(vertex)]
+pub fn main_vs(
+    #[spirv(vertex_index)] vert_index: i32,
+    #[spirv(position)] out_position: &mut Vec4,
+    out_uv: &mut Vec2,
+) {
+    let uv = vec2(((vert_index << 1) & 2) as f32, (vert_index & 2) as f32);
+    *out_uv = uv;
+    *out_position = vec4(uv.x * 2.0 - 1.0, uv.y * 2.0 - 1.0, 0.0, 1.0);
+}
+
+#[spirv(fragment)]
+pub fn coc_fs(
+    in_uv: Vec2,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_color: &Texture2D,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_depth: &Texture2D,
+    out_near: &mut Vec4,
+    out_far: &mut Vec4,
+    out_highlight: &mut Vec4,
+) {
+    let color = sampler_color.sample(in_uv).xyz();
+    let coc = circle_of_confusion(sampler_depth.sample(in_uv).x, ubo);
+
+    // Energy above the threshold is gathered by the bokeh pass instead of being blurred
+    let mut base = color;
+    let mut highlight = Vec3::ZERO;
+    let l = luminance(color);
+    if coc.abs() * ubo.max_coc_radius >= 1.0 && l > ubo.highlight_threshold {
+        base = color * (ubo.highlight_threshold / l);
+        highlight = color - base;
+    }
+
+    *out_near = vec4(base.x, base.y, base.z, (-coc).max(0.0));
+    *out_far = vec4(base.x, base.y, base.z, coc.max(0.0));
+    *out_highlight = vec4(highlight.x, highlight.y, highlight.z, coc);
+}
+
+#[spirv(fragment)]
+pub fn blur_fs(
+    in_uv: Vec2,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_near: &Texture2D,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_far: &Texture2D,
+    // 0 = horizontal, 1 = vertical
+    #[spirv(spec_constant(id = 0, default = 0))] direction: u32,
+    out_near: &mut Vec4,
+    out_far: &mut Vec4,
+) {
+    let dir = if direction == 0 {
+        vec2(ubo.texel_size.x, 0.0)
+    } else {
+        vec2(0.0, ubo.texel_size.y)
+    };
+    let max_radius = ubo.max_coc_radius * 0.5;
+    let step = max_radius / BLUR_TAPS as f32;
+
+    let mut near_color = Vec3::ZERO;
+    let mut near_weight = 0.0;
+    let mut far_color = Vec4::ZERO;
+    let mut far_weight = 0.0;
+    for i in -BLUR_TAPS..=BLUR_TAPS {
+        let offset = i as f32 * step;
+        let uv = in_uv + dir * offset;
+
+        // Scatter as gather: a tap contributes if its own circle of confusion reaches this pixel,
+        // so in focus pixels neither receive nor spread blur
+        let near = sampler_near.sample(uv);
+        if near.w > 0.0 && near.w * max_radius >= offset.abs() {
+            near_color += near.xyz();
+            near_weight += 1.0;
+        }
+        let far = sampler_far.sample(uv);
+        if far.w > 0.0 && far.w * max_radius >= offset.abs() {
+            far_color += far;
+            far_weight += 1.0;
+        }
+    }
+
+    // The near field spreads over the pixels behind it, its alpha becomes the coverage
+    let center = sampler_near.sample(in_uv).w;
+    *out_near = if near_weight > 0.0 {
+        let coverage = center.max(near_weight / (2 * BLUR_TAPS + 1) as f32);
+        let c = near_color / near_weight;
+        vec4(c.x, c.y, c.z, coverage)
+    } else {
+        Vec4::ZERO
+    };
+    *out_far = if far_weight > 0.0 { far_color / far_weight } else { Vec4::ZERO };
+}
+
+#[spirv(fragment)]
+pub fn bokeh_fs(
+    in_uv: Vec2,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_highlight: &Texture2D,
+    out_frag_color: &mut Vec4,
+) {
+    let max_radius = ubo.max_coc_radius * 0.5;
+    // Each sample of the spiral stands for this many texels of the disk
+    let sample_area = PI * max_radius * max_radius / BOKEH_SAMPLES as f32;
+
+    let mut color = Vec3::ZERO;
+    for i in 0..BOKEH_SAMPLES {
+        // Golden angle spiral, evenly covering the disk of the largest circle of confusion
+        let r = max_radius * ((i as f32 + 0.5) / BOKEH_SAMPLES as f32).sqrt();
+        let theta = i as f32 * GOLDEN_ANGLE;
+        let highlight = sampler_highlight.sample(in_uv + vec2(theta.cos(), theta.sin()) * r * ubo.texel_size);
+
+        // The energy of a highlight is spread evenly over its circle of confusion
+        let radius = highlight.w.abs() * max_radius;
+        if radius >= r {
+            color += highlight.xyz() * sample_area / (PI * radius * radius).max(1.0);
+        }
+    }
+
+    *out_frag_color = vec4(color.x, color.y, color.z, 1.0);
+}
+
+#[spirv(fragment)]
+pub fn composite_fs(
+    in_uv: Vec2,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_color: &Texture2D,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_depth: &Texture2D,
+    #[spirv(descriptor_set = 0, binding = 3)] sampler_near: &Texture2D,
+    #[spirv(descriptor_set = 0, binding = 4)] sampler_far: &Texture2D,
+    #[spirv(descriptor_set = 0, binding = 5)] sampler_bokeh: &Texture2D,
+    out_frag_color: &mut Vec4,
+) {
+    let sharp = sampler_color.sample(in_uv);
+    let coc = circle_of_confusion(sampler_depth.sample(in_uv).x, ubo);
+    let near = sampler_near.sample(in_uv);
+    let far = sampler_far.sample(in_uv);
+
+    // Fully blurred once the circle of confusion is two pixels wide
+    let coc_pixels = coc * ubo.max_coc_radius * 0.5;
+    let far_blend = if far.w > 0.0 { coc_pixels.clamp(0.0, 1.0) } else { 0.0 };
+    let near_blend = if near.w > 0.0 { near.w.max((-coc_pixels).clamp(0.0, 1.0)) } else { 0.0 };
+
+    let mut color = sharp.xyz().lerp(far.xyz(), far_blend);
+    color = color.lerp(near.xyz(), near_blend);
+    color += sampler_bokeh.sample(in_uv).xyz();
+
+    *out_frag_color = vec4(color.x, color.y, color.z, sharp.w);
+}
@@ -0,0 +1,335 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// glTF 2.0 metallic-roughness material (glTF 2.0 specification, appendix B) lit by a directional
+// light and a constant ambient term. Alpha modes, KHR_materials_clearcoat, KHR_materials_sheen,
+// KHR_materials_transmission, KHR_materials_ior and KHR_texture_transform are selected with
+// specialization constants, so one SPIR-V module serves every material permutation. Textures a
+// material does not have are bound to 1x1 defaults: white, or flat for normal maps.
+
+use core::f32::consts::PI;
+use spirv_std::glam::{mat3, vec2, vec3, vec4, Mat3, Mat4, Vec2, Vec3, Vec4, Vec4Swizzles};
+use spirv_std::image::SampledImage;
+use spirv_std::{num_traits::Float, spirv, Image};
+
+type Texture = SampledImage<Image!(2D, type=f32, sampled)>;
+
+const ALPHA_MODE_MASK: u32 = 1;
+const ALPHA_MODE_BLEND: u32 = 2;
+
+// Texture slots, in binding order after the material UBO
+const BASE_COLOR: usize = 0;
+const METALLIC_ROUGHNESS: usize = 1;
+const NORMAL: usize = 2;
+const OCCLUSION: usize = 3;
+const EMISSIVE: usize = 4;
+const CLEARCOAT: usize = 5;
+const CLEARCOAT_ROUGHNESS: usize = 6;
+const CLEARCOAT_NORMAL: usize = 7;
+const SHEEN_COLOR: usize = 8;
+const SHEEN_ROUGHNESS: usize = 9;
+const TRANSMISSION: usize = 10;
+const TEXTURE_COUNT: usize = 11;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct UboScene {
+    pub projection: Mat4,
+    pub view: Mat4,
+    pub view_pos: Vec4,
+    // Direction towards the light
+    pub light_direction: Vec4,
+    // Illuminance of the light in rgb
+    pub light_color: Vec4,
+    pub ambient_color: Vec4,
+    pub screen_size: Vec2,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PushConsts {
+    pub model: Mat4,
+}
+
+// KHR_texture_transform as the rows of a 2x3 matrix, offset * rotation * scale
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct TextureTransform {
+    pub row0: Vec4,
+    pub row1: Vec4,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MaterialUBO {
+    pub base_color_factor: Vec4,
+    pub emissive_factor: Vec4,
+    pub sheen_color_factor: Vec4,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub alpha_cutoff: f32,
+    pub ior: f32,
+    pub transmission_factor: f32,
+    pub clearcoat_factor: f32,
+    pub clearcoat_roughness_factor: f32,
+    pub clearcoat_normal_scale: f32,
+    pub sheen_roughness_factor: f32,
+    pub _padding: f32,
+    pub texture_transforms: [TextureTransform; TEXTURE_COUNT],
+}
+
+#[spirv(vertex)]
+pub fn main_vs(
+    in_pos: Vec3,
+    in_normal: Vec3,
+    in_uv: Vec2,
+    in_color: Vec3,
+    in_tangent: Vec4,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo_scene: &UboScene,
+    #[spirv(push_constant)] push_consts: &PushConsts,
+    #[spirv(position)] out_position: &mut Vec4,
+    out_world_pos: &mut Vec3,
+    out_normal: &mut Vec3,
+    out_uv: &mut Vec2,
+    out_color: &mut Vec3,
+    out_tangent: &mut Vec4,
+) {
+    let pos = push_consts.model * vec4(in_pos.x, in_pos.y, in_pos.z, 1.0);
+    let model_mat3 = mat3(
+        push_consts.model.x_axis.truncate(),
+        push_consts.model.y_axis.truncate(),
+        push_consts.model.z_axis.truncate(),
+    );
+    let tangent = model_mat3 * in_tangent.truncate();
+
+    *out_world_pos = pos.truncate();
+    *out_normal = model_mat3 * in_normal;
+    *out_uv = in_uv;
+    *out_color = in_color;
+    *out_tangent = vec4(tangent.x, tangent.y, tangent.z, in_tangent.w);
+    *out_position = ubo_scene.projection * ubo_scene.view * pos;
+}
+
+fn transform_uv(material: &MaterialUBO, slot: usize, uv: Vec2, texture_transform: bool) -> Vec2 {
+    if !texture_transform {
+        return uv;
+    }
+    let t = material.texture_transforms[slot];
+    let p = vec3(uv.x, uv.y, 1.0);
+    vec2(t.row0.xyz().dot(p), t.row1.xyz().dot(p))
+}
+
+fn perturb_normal(n: Vec3, tangent: Vec4, sample: Vec3, scale: f32) -> Vec3 {
+    let t = (tangent.truncate() - n * n.dot(tangent.truncate())).normalize();
+    let b = n.cross(t) * tangent.w;
+    let tbn = Mat3::from_cols(t, b, n);
+    let ts = (sample * 2.0 - Vec3::ONE) * vec3(scale, scale, 1.0);
+    (tbn * ts).normalize()
+}
+
+fn f_schlick(f0: Vec3, f90: Vec3, dot_vh: f32) -> Vec3 {
+    f0 + (f90 - f0) * (1.0 - dot_vh).clamp(0.0, 1.0).powf(5.0)
+}
+
+// Trowbridge-Reitz distribution with alpha = roughness^2
+fn d_ggx(dot_nh: f32, alpha: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    let denom = dot_nh * dot_nh * (alpha2 - 1.0) + 1.0;
+    alpha2 / (PI * denom * denom)
+}
+
+// Height correlated Smith visibility, G / (4 NoL NoV)
+fn v_ggx(dot_nl: f32, dot_nv: f32, alpha: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    let ggxv = dot_nl * (dot_nv * dot_nv * (1.0 - alpha2) + alpha2).sqrt();
+    let ggxl = dot_nv * (dot_nl * dot_nl * (1.0 - alpha2) + alpha2).sqrt();
+    let ggx = ggxv + ggxl;
+    if ggx > 0.0 {
+        0.5 / ggx
+    } else {
+        0.0
+    }
+}
+
+// Specular microfacet lobe without Fresnel
+fn specular_lobe(n: Vec3, l: Vec3, v: Vec3, alpha: f32) -> f32 {
+    let h = (l + v).normalize();
+    let dot_nl = n.dot(l).clamp(0.0, 1.0);
+    let dot_nv = n.dot(v).clamp(0.0, 1.0);
+    let dot_nh = n.dot(h).clamp(0.0, 1.0);
+    d_ggx(dot_nh, alpha) * v_ggx(dot_nl, dot_nv, alpha)
+}
+
+// "Charlie" sheen distribution (Estevez and Kulla 2017)
+fn d_charlie(dot_nh: f32, sheen_roughness: f32) -> f32 {
+    let alpha = (sheen_roughness * sheen_roughness).max(0.000_001);
+    let inv_alpha = 1.0 / alpha;
+    let sin2 = 1.0 - dot_nh * dot_nh;
+    (2.0 + inv_alpha) * sin2.max(0.0).powf(inv_alpha * 0.5) / (2.0 * PI)
+}
+
+// Sheen visibility (Neubelt and Pettineo 2013)
+fn v_neubelt(dot_nl: f32, dot_nv: f32) -> f32 {
+    (1.0 / (4.0 * (dot_nl + dot_nv - dot_nl * dot_nv))).clamp(0.0, 1.0)
+}
+
+fn max_component(v: Vec3) -> f32 {
+    v.x.max(v.y).max(v.z)
+}
+
+#[spirv(fragment)]
+pub fn main_fs(
+    in_world_pos: Vec3,
+    in_normal: Vec3,
+    in_uv: Vec2,
+    in_color: Vec3,
+    in_tangent: Vec4,
+    #[spirv(front_facing)] front_facing: bool,
+    #[spirv(frag_coord)] frag_coord: Vec4,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo_scene: &UboScene,
+    // Opaque scene rendered before the transmissive objects, with mips
+    #[spirv(descriptor_set = 0, binding = 1)] transmission_framebuffer: &Texture,
+    #[spirv(uniform, descriptor_set = 1, binding = 0)] material: &MaterialUBO,
+    #[spirv(descriptor_set = 1, binding = 1)] base_color_map: &Texture,
+    #[spirv(descriptor_set = 1, binding = 2)] metallic_roughness_map: &Texture,
+    #[spirv(descriptor_set = 1, binding = 3)] normal_map: &Texture,
+    #[spirv(descriptor_set = 1, binding = 4)] occlusion_map: &Texture,
+    #[spirv(descriptor_set = 1, binding = 5)] emissive_map: &Texture,
+    #[spirv(descriptor_set = 1, binding = 6)] clearcoat_map: &Texture,
+    #[spirv(descriptor_set = 1, binding = 7)] clearcoat_roughness_map: &Texture,
+    #[spirv(descriptor_set = 1, binding = 8)] clearcoat_normal_map: &Texture,
+    #[spirv(descriptor_set = 1, binding = 9)] sheen_color_map: &Texture,
+    #[spirv(descriptor_set = 1, binding = 10)] sheen_roughness_map: &Texture,
+    #[spirv(descriptor_set = 1, binding = 11)] transmission_map: &Texture,
+    // 0 = OPAQUE, 1 = MASK, 2 = BLEND
+    #[spirv(spec_constant(id = 0, default = 0))] alpha_mode: u32,
+    #[spirv(spec_constant(id = 1, default = 0))] clearcoat: u32,
+    #[spirv(spec_constant(id = 2, default = 0))] sheen: u32,
+    #[spirv(spec_constant(id = 3, default = 0))] transmission: u32,
+    // Without KHR_materials_ior the index of refraction is 1.5
+    #[spirv(spec_constant(id = 4, default = 0))] ior_extension: u32,
+    #[spirv(spec_constant(id = 5, default = 0))] texture_transform: u32,
+    out_frag_color: &mut Vec4,
+) {
+    let texture_transform = texture_transform != 0;
+    let uv = |slot: usize| transform_uv(material, slot, in_uv, texture_transform);
+
+    let base_color = material.base_color_factor
+        * base_color_map.sample(uv(BASE_COLOR))
+        * vec4(in_color.x, in_color.y, in_color.z, 1.0);
+    let alpha = match alpha_mode {
+        ALPHA_MODE_MASK => {
+            if base_color.w < material.alpha_cutoff {
+                spirv_std::arch::kill();
+            }
+            1.0
+        }
+        ALPHA_MODE_BLEND => base_color.w,
+        _ => 1.0,
+    };
+    let albedo = base_color.xyz();
+
+    // Metalness in blue, roughness in green
+    let metallic_roughness = metallic_roughness_map.sample(uv(METALLIC_ROUGHNESS));
+    let metallic = (material.metallic_factor * metallic_roughness.z).clamp(0.0, 1.0);
+    let roughness = (material.roughness_factor * metallic_roughness.y).clamp(0.0, 1.0);
+    let alpha_roughness = (roughness * roughness).max(0.001);
+
+    // Double sided materials see the back face with a flipped normal
+    let geometric_normal = if front_facing { in_normal.normalize() } else { -in_normal.normalize() };
+    let n = perturb_normal(geometric_normal, in_tangent, normal_map.sample(uv(NORMAL)).xyz(), material.normal_scale);
+    let v = (ubo_scene.view_pos.xyz() - in_world_pos).normalize();
+    let l = ubo_scene.light_direction.xyz().normalize();
+    let dot_nv = n.dot(v).clamp(0.0, 1.0);
+    let dot_nl = n.dot(l);
+
+    let ior = if ior_extension != 0 { material.ior } else { 1.5 };
+    let f0_dielectric = Vec3::splat(((ior - 1.0) / (ior + 1.0)).powi(2));
+    let h = (l + v).normalize();
+    let dot_vh = v.dot(h).clamp(0.0, 1.0);
+    let fresnel_dielectric = f_schlick(f0_dielectric, Vec3::ONE, dot_vh);
+    let fresnel_metal = f_schlick(albedo, Vec3::ONE, dot_vh);
+
+    let transmission_weight = if transmission != 0 {
+        material.transmission_factor * transmission_map.sample(uv(TRANSMISSION)).x
+    } else {
+        0.0
+    };
+
+    // Direct light through the base layer: a dielectric lobe layered over diffuse (or
+    // transmission), blended with a metal lobe tinted by the base color
+    let mut color = Vec3::ZERO;
+    let light = ubo_scene.light_color.xyz();
+    let specular = specular_lobe(n, l, v, alpha_roughness);
+    let mut base_diffuse = albedo / PI * (1.0 - transmission_weight);
+    if transmission_weight > 0.0 && dot_nl < 0.0 {
+        // Thin walled transmission: light from behind passes through the surface mirrored
+        // at its tangent plane
+        let l_mirror = l - 2.0 * dot_nl * n;
+        let btdf = albedo * specular_lobe(n, l_mirror, v, alpha_roughness);
+        color += btdf * transmission_weight * (1.0 - fresnel_dielectric) * -dot_nl * light;
+    }
+    let dot_nl = dot_nl.clamp(0.0, 1.0);
+
+    let mut sheen_color = Vec3::ZERO;
+    let mut sheen_roughness = 0.0;
+    if sheen != 0 {
+        sheen_color = material.sheen_color_factor.xyz() * sheen_color_map.sample(uv(SHEEN_COLOR)).xyz();
+        sheen_roughness = material.sheen_roughness_factor * sheen_roughness_map.sample(uv(SHEEN_ROUGHNESS)).w;
+        // Energy reflected by the sheen layer is taken from the base, with a constant
+        // estimate of the directional albedo of the sheen lobe in place of a LUT
+        base_diffuse *= 1.0 - max_component(sheen_color) * 0.157;
+    }
+
+    let dielectric = base_diffuse * (Vec3::ONE - fresnel_dielectric) + fresnel_dielectric * specular;
+    let metal = fresnel_metal * specular;
+    let mut base = dielectric.lerp(metal, metallic);
+
+    if sheen != 0 {
+        let dot_nh = n.dot(h).clamp(0.0, 1.0);
+        base += sheen_color * d_charlie(dot_nh, sheen_roughness) * v_neubelt(dot_nl, dot_nv);
+    }
+
+    // Clearcoat layer with its own normal and a fixed IOR of 1.5 on top
+    let mut direct = base * dot_nl;
+    let mut clearcoat_weight = 0.0;
+    let mut clearcoat_fresnel_nv = Vec3::ZERO;
+    if clearcoat != 0 {
+        clearcoat_weight = material.clearcoat_factor * clearcoat_map.sample(uv(CLEARCOAT)).x;
+        let clearcoat_roughness =
+            (material.clearcoat_roughness_factor * clearcoat_roughness_map.sample(uv(CLEARCOAT_ROUGHNESS)).y).clamp(0.0, 1.0);
+        let clearcoat_normal = perturb_normal(
+            geometric_normal,
+            in_tangent,
+            clearcoat_normal_map.sample(uv(CLEARCOAT_NORMAL)).xyz(),
+            material.clearcoat_normal_scale,
+        );
+        let f0 = Vec3::splat(0.04);
+        clearcoat_fresnel_nv = f_schlick(f0, Vec3::ONE, clearcoat_normal.dot(v).clamp(0.0, 1.0));
+        let clearcoat_alpha = (clearcoat_roughness * clearcoat_roughness).max(0.001);
+        let clearcoat_specular = f_schlick(f0, Vec3::ONE, dot_vh) * specular_lobe(clearcoat_normal, l, v, clearcoat_alpha);
+        direct = direct * (Vec3::ONE - clearcoat_weight * clearcoat_fresnel_nv)
+            + clearcoat_weight * clearcoat_specular * clearcoat_normal.dot(l).clamp(0.0, 1.0);
+    }
+    color += direct * light;
+
+    // Ambient light is only shadowed by the occlusion map
+    let occlusion = 1.0 + material.occlusion_strength * (occlusion_map.sample(uv(OCCLUSION)).x - 1.0);
+    let ambient_diffuse = albedo * (1.0 - metallic) * (1.0 - transmission_weight);
+    color += ambient_diffuse * ubo_scene.ambient_color.xyz() * occlusion;
+
+    // Transmitted background, blurred by roughness, behind a thin walled surface
+    if transmission_weight > 0.0 {
+        let screen_uv = frag_coord.xy() / ubo_scene.screen_size;
+        let lod = ubo_scene.screen_size.x.log2() * roughness * (ior * 2.0 - 2.0).clamp(0.0, 1.0);
+        let background = transmission_framebuffer.sample_by_lod(screen_uv, lod).xyz();
+        let transmitted = background * albedo * (Vec3::ONE - f_schlick(f0_dielectric, Vec3::ONE, dot_nv));
+        color += transmitted * transmission_weight * (1.0 - metallic) * (Vec3::ONE - clearcoat_weight * clearcoat_fresnel_nv);
+    }
+
+    let emissive = material.emissive_factor.xyz() * emissive_map.sample(uv(EMISSIVE)).xyz();
+    color += emissive * (Vec3::ONE - clearcoat_weight * clearcoat_fresnel_nv);
+
+    *out_frag_color = vec4(color.x, color.y, color.z, alpha);
+}
@@ -0,0 +1,161 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Skins the vertices of a mesh once per frame into a storage buffer, so that the depth, shadow
+// and main passes don't skin again and acceleration structures can be refit from the result.
+// main_cs is dispatched once per primitive with the joints of its skin bound. main_vs draws
+// the result with the skinned buffer and the bind pose buffer bound as two vertex bindings,
+// and pairs with the fragment shader of skinnedmodel.
+
+use spirv_std::glam::{mat3, vec3, vec4, Mat4, UVec3, Vec2, Vec3, Vec4};
+use spirv_std::spirv;
+use common::morph::{blend_morph_deltas, MorphDelta, MorphParams};
+use common::skinning::{skin, DualQuat};
+
+// Bind pose vertex of the glTF skinning example: position, normal, uv, color, joint indices
+// and joint weights, tightly packed. Meshes with eight influences append the second set of
+// joint indices and weights.
+const VERTEX_STRIDE: usize = 19;
+const VERTEX_STRIDE_INFLUENCES8: usize = 27;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PushConstsCompute {
+    // Range of vertices of the primitive in the vertex buffers
+    pub first_vertex: u32,
+    pub vertex_count: u32,
+}
+
+// Skinned vertex with a 32 byte stride, the position can be used as is for a BLAS refit
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SkinnedVertex {
+    pub position: Vec4,
+    pub normal: Vec4,
+}
+
+fn read_vec3(vertices: &[f32], offset: usize) -> Vec3 {
+    vec3(vertices[offset], vertices[offset + 1], vertices[offset + 2])
+}
+
+fn read_vec4(vertices: &[f32], offset: usize) -> Vec4 {
+    vec4(vertices[offset], vertices[offset + 1], vertices[offset + 2], vertices[offset + 3])
+}
+
+// Layout:
+//   set 0 binding 0: bind pose vertices, VERTEX_STRIDE or VERTEX_STRIDE_INFLUENCES8 floats each
+//   set 0 binding 1: SkinnedVertex output
+//   set 1 binding 0: joint matrices, or dual quaternions with spec constant 0 set
+//   set 2 binding 0-2: morph target deltas, weights and MorphParams of the mesh as for
+//     skinnedmodel::morph_vs, only read with spec constant 2 set
+// The bind pose has no tangents, so only the position and normal deltas are applied.
+#[spirv(compute(threads(64)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(push_constant)] push_consts: &PushConstsCompute,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] bind_pose: &[f32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] skinned: &mut [SkinnedVertex],
+    #[spirv(storage_buffer, descriptor_set = 1, binding = 0)] joint_matrices: &[Mat4],
+    // The same buffer, holding dual quaternions when dual quaternion skinning is enabled
+    #[spirv(storage_buffer, descriptor_set = 1, binding = 0)] joint_dual_quats: &[DualQuat],
+    #[spirv(storage_buffer, descriptor_set = 2, binding = 0)] morph_deltas: &[MorphDelta],
+    #[spirv(storage_buffer, descriptor_set = 2, binding = 1)] morph_weights: &[f32],
+    #[spirv(uniform, descriptor_set = 2, binding = 2)] morph_params: &MorphParams,
+    #[spirv(spec_constant(id = 0, default = 0))] dual_quaternion: u32,
+    #[spirv(spec_constant(id = 1, default = 0))] influences8: u32,
+    #[spirv(spec_constant(id = 2, default = 0))] morph_targets: u32,
+) {
+    if global_id.x >= push_consts.vertex_count {
+        return;
+    }
+    let index = (push_consts.first_vertex + global_id.x) as usize;
+    let eight_influences = influences8 != 0;
+    let base = index * if eight_influences { VERTEX_STRIDE_INFLUENCES8 } else { VERTEX_STRIDE };
+    let mut pos = read_vec3(bind_pose, base);
+    let mut normal = read_vec3(bind_pose, base + 3);
+    let influences = [
+        (read_vec4(bind_pose, base + 11), read_vec4(bind_pose, base + 15)),
+        if eight_influences {
+            (read_vec4(bind_pose, base + 19), read_vec4(bind_pose, base + 23))
+        } else {
+            (Vec4::ZERO, Vec4::ZERO)
+        },
+    ];
+
+    if morph_targets != 0 {
+        let vertex = index as u32 - morph_params.first_vertex;
+        let delta = blend_morph_deltas(morph_deltas, morph_weights, vertex, morph_params.target_count);
+        pos += delta.position.truncate();
+        normal += delta.normal.truncate();
+    }
+
+    let (pos, normal, _) = skin(
+        pos,
+        normal,
+        Vec3::ZERO,
+        joint_matrices,
+        joint_dual_quats,
+        dual_quaternion != 0,
+        influences,
+        eight_influences,
+    );
+    let normal = normal.normalize();
+
+    skinned[index] = SkinnedVertex {
+        position: vec4(pos.x, pos.y, pos.z, 1.0),
+        normal: vec4(normal.x, normal.y, normal.z, 0.0),
+    };
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct UboScene {
+    pub projection: Mat4,
+    pub view: Mat4,
+    pub light_pos: Vec4,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PushConsts {
+    pub model: Mat4,
+}
+
+// Same outputs as skinnedmodel::main_vs, with position and normal already skinned
+#[spirv(vertex)]
+pub fn main_vs(
+    in_pos: Vec3,
+    in_normal: Vec3,
+    in_uv: Vec2,
+    in_color: Vec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo_scene: &UboScene,
+    #[spirv(push_constant)] push_consts: &PushConsts,
+    #[spirv(position)] out_position: &mut Vec4,
+    out_normal: &mut Vec3,
+    out_color: &mut Vec3,
+    out_uv: &mut Vec2,
+    out_view_vec: &mut Vec3,
+    out_light_vec: &mut Vec3,
+) {
+    *out_color = in_color;
+    *out_uv = in_uv;
+
+    let pos = vec4(in_pos.x, in_pos.y, in_pos.z, 1.0);
+    *out_position = ubo_scene.projection * ubo_scene.view * push_consts.model * pos;
+
+    let model_mat3 = mat3(
+        push_consts.model.x_axis.truncate(),
+        push_consts.model.y_axis.truncate(),
+        push_consts.model.z_axis.truncate(),
+    );
+    *out_normal = model_mat3 * in_normal;
+
+    let view_pos = ubo_scene.view * pos;
+    let view_mat3 = mat3(
+        ubo_scene.view.x_axis.truncate(),
+        ubo_scene.view.y_axis.truncate(),
+        ubo_scene.view.z_axis.truncate(),
+    );
+    let l_pos = view_mat3 * ubo_scene.light_pos.truncate();
+    *out_light_vec = l_pos - view_pos.truncate();
+    *out_view_vec = -view_pos.truncate();
+}
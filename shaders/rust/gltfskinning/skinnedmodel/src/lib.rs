@@ -3,6 +3,8 @@
 
 use spirv_std::{spirv, glam::{mat3, vec3, vec4, Mat4, Vec2, Vec3, Vec4}, Image, num_traits::Float};
 use spirv_std::image::SampledImage;
+use common::morph::{blend_morph_deltas, MorphDelta, MorphParams};
+use common::skinning::{skin, DualQuat};
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -18,6 +20,49 @@ pub struct PushConsts {
     pub model: Mat4,
 }
 
+// Applies the weighted morph target deltas of the vertex, before skinning
+fn morph(
+    pos: Vec3,
+    normal: Vec3,
+    tangent: Vec4,
+    vertex_index: u32,
+    morph_params: &MorphParams,
+    morph_deltas: &[MorphDelta],
+    morph_weights: &[f32],
+) -> (Vec3, Vec3, Vec4) {
+    let vertex = vertex_index - morph_params.first_vertex;
+    let delta = blend_morph_deltas(morph_deltas, morph_weights, vertex, morph_params.target_count);
+    // The tangent delta has no handedness
+    let morphed_tangent = tangent.truncate() + delta.tangent.truncate();
+    (
+        pos + delta.position.truncate(),
+        normal + delta.normal.truncate(),
+        vec4(morphed_tangent.x, morphed_tangent.y, morphed_tangent.z, tangent.w),
+    )
+}
+
+// Clip space position, normal, light and view vectors shared by both vertex entry points
+fn transform(in_pos: Vec3, skinned_pos: Vec3, skinned_normal: Vec3, ubo_scene: &UboScene, push_consts: &PushConsts) -> (Vec4, Vec3, Vec3, Vec3) {
+    let position = ubo_scene.projection * ubo_scene.view * push_consts.model * vec4(skinned_pos.x, skinned_pos.y, skinned_pos.z, 1.0);
+
+    // Transform normal with model and skin matrices (matching slang version)
+    let model_mat3 = mat3(
+        push_consts.model.x_axis.truncate(),
+        push_consts.model.y_axis.truncate(),
+        push_consts.model.z_axis.truncate(),
+    );
+    let normal = model_mat3 * skinned_normal;
+
+    let pos = ubo_scene.view * vec4(in_pos.x, in_pos.y, in_pos.z, 1.0);
+    let view_mat3 = mat3(
+        ubo_scene.view.x_axis.truncate(),
+        ubo_scene.view.y_axis.truncate(),
+        ubo_scene.view.z_axis.truncate(),
+    );
+    let l_pos = view_mat3 * ubo_scene.light_pos.truncate();
+    (position, normal, l_pos - pos.truncate(), -pos.truncate())
+}
+
 #[spirv(vertex)]
 pub fn main_vs(
     in_pos: Vec3,
@@ -29,6 +74,9 @@ pub fn main_vs(
     #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo_scene: &UboScene,
     #[spirv(push_constant)] push_consts: &PushConsts,
     #[spirv(storage_buffer, descriptor_set = 1, binding = 0)] joint_matrices: &[Mat4],
+    // The same buffer, holding dual quaternions when dual quaternion skinning is enabled
+    #[spirv(storage_buffer, descriptor_set = 1, binding = 0)] joint_dual_quats: &[DualQuat],
+    #[spirv(spec_constant(id = 0, default = 0))] dual_quaternion: u32,
     #[spirv(position)] out_position: &mut Vec4,
     out_normal: &mut Vec3,
     out_color: &mut Vec3,
@@ -36,41 +84,175 @@ pub fn main_vs(
     out_view_vec: &mut Vec3,
     out_light_vec: &mut Vec3,
 ) {
-    *out_normal = in_normal;
     *out_color = in_color;
     *out_uv = in_uv;
 
-    // Calculate skinned matrix from weights and joint indices of the current vertex
-    let skin_mat = 
-        in_joint_weights.x * joint_matrices[in_joint_indices.x as usize] +
-        in_joint_weights.y * joint_matrices[in_joint_indices.y as usize] +
-        in_joint_weights.z * joint_matrices[in_joint_indices.z as usize] +
-        in_joint_weights.w * joint_matrices[in_joint_indices.w as usize];
+    let influences = [(in_joint_indices, in_joint_weights), (Vec4::ZERO, Vec4::ZERO)];
+    let (pos, normal, _) = skin(in_pos, in_normal, Vec3::ZERO, joint_matrices, joint_dual_quats, dual_quaternion != 0, influences, false);
+    let (position, normal, light_vec, view_vec) = transform(in_pos, pos, normal, ubo_scene, push_consts);
+    *out_position = position;
+    *out_normal = normal;
+    *out_light_vec = light_vec;
+    *out_view_vec = view_vec;
+}
 
-    *out_position = ubo_scene.projection * ubo_scene.view * push_consts.model * skin_mat * vec4(in_pos.x, in_pos.y, in_pos.z, 1.0);
-    
-    // Transform normal with model and skin matrices (matching slang version)
+// Variant for meshes with a second set of joints and weights, for up to eight influences
+#[spirv(vertex)]
+pub fn influences8_vs(
+    in_pos: Vec3,
+    in_normal: Vec3,
+    in_uv: Vec2,
+    in_color: Vec3,
+    in_joint_indices: Vec4,
+    in_joint_weights: Vec4,
+    in_joint_indices1: Vec4,
+    in_joint_weights1: Vec4,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo_scene: &UboScene,
+    #[spirv(push_constant)] push_consts: &PushConsts,
+    #[spirv(storage_buffer, descriptor_set = 1, binding = 0)] joint_matrices: &[Mat4],
+    #[spirv(storage_buffer, descriptor_set = 1, binding = 0)] joint_dual_quats: &[DualQuat],
+    #[spirv(spec_constant(id = 0, default = 0))] dual_quaternion: u32,
+    #[spirv(position)] out_position: &mut Vec4,
+    out_normal: &mut Vec3,
+    out_color: &mut Vec3,
+    out_uv: &mut Vec2,
+    out_view_vec: &mut Vec3,
+    out_light_vec: &mut Vec3,
+) {
+    *out_color = in_color;
+    *out_uv = in_uv;
+
+    let influences = [(in_joint_indices, in_joint_weights), (in_joint_indices1, in_joint_weights1)];
+    let (pos, normal, _) = skin(in_pos, in_normal, Vec3::ZERO, joint_matrices, joint_dual_quats, dual_quaternion != 0, influences, true);
+    let (position, normal, light_vec, view_vec) = transform(in_pos, pos, normal, ubo_scene, push_consts);
+    *out_position = position;
+    *out_normal = normal;
+    *out_light_vec = light_vec;
+    *out_view_vec = view_vec;
+}
+
+// Skinned tangent in world space, keeping the handedness in w
+fn transform_tangent(skinned_tangent: Vec3, handedness: f32, push_consts: &PushConsts) -> Vec4 {
     let model_mat3 = mat3(
         push_consts.model.x_axis.truncate(),
         push_consts.model.y_axis.truncate(),
         push_consts.model.z_axis.truncate(),
     );
-    let skin_mat3 = mat3(
-        skin_mat.x_axis.truncate(),
-        skin_mat.y_axis.truncate(),
-        skin_mat.z_axis.truncate(),
+    let tangent = model_mat3 * skinned_tangent;
+    vec4(tangent.x, tangent.y, tangent.z, handedness)
+}
+
+// Variant of main_vs for meshes with morph targets, compiled to morph.vert.spv. Position,
+// normal and tangent are morphed before skinning and the tangent is written to location 5.
+// Layout:
+//   vertex input: the one of main_vs plus the tangent in location 6
+//   set 0-2: as for main_vs
+//   set 3 binding 0: MorphDelta of each vertex and target, see common::morph
+//   set 3 binding 1: target weights of the mesh
+//   set 3 binding 2: MorphParams
+#[spirv(vertex)]
+pub fn morph_vs(
+    in_pos: Vec3,
+    in_normal: Vec3,
+    in_uv: Vec2,
+    in_color: Vec3,
+    in_joint_indices: Vec4,
+    in_joint_weights: Vec4,
+    in_tangent: Vec4,
+    #[spirv(vertex_index)] vertex_index: u32,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo_scene: &UboScene,
+    #[spirv(push_constant)] push_consts: &PushConsts,
+    #[spirv(storage_buffer, descriptor_set = 1, binding = 0)] joint_matrices: &[Mat4],
+    #[spirv(storage_buffer, descriptor_set = 1, binding = 0)] joint_dual_quats: &[DualQuat],
+    #[spirv(spec_constant(id = 0, default = 0))] dual_quaternion: u32,
+    #[spirv(storage_buffer, descriptor_set = 3, binding = 0)] morph_deltas: &[MorphDelta],
+    #[spirv(storage_buffer, descriptor_set = 3, binding = 1)] morph_weights: &[f32],
+    #[spirv(uniform, descriptor_set = 3, binding = 2)] morph_params: &MorphParams,
+    #[spirv(position)] out_position: &mut Vec4,
+    out_normal: &mut Vec3,
+    out_color: &mut Vec3,
+    out_uv: &mut Vec2,
+    out_view_vec: &mut Vec3,
+    out_light_vec: &mut Vec3,
+    out_tangent: &mut Vec4,
+) {
+    *out_color = in_color;
+    *out_uv = in_uv;
+
+    let (morphed_pos, morphed_normal, morphed_tangent) =
+        morph(in_pos, in_normal, in_tangent, vertex_index, morph_params, morph_deltas, morph_weights);
+    let influences = [(in_joint_indices, in_joint_weights), (Vec4::ZERO, Vec4::ZERO)];
+    let (pos, normal, tangent) = skin(
+        morphed_pos,
+        morphed_normal,
+        morphed_tangent.truncate(),
+        joint_matrices,
+        joint_dual_quats,
+        dual_quaternion != 0,
+        influences,
+        false,
     );
-    *out_normal = model_mat3 * skin_mat3 * in_normal;
+    let (position, normal, light_vec, view_vec) = transform(morphed_pos, pos, normal, ubo_scene, push_consts);
+    *out_position = position;
+    *out_normal = normal;
+    *out_light_vec = light_vec;
+    *out_view_vec = view_vec;
+    *out_tangent = transform_tangent(tangent, morphed_tangent.w, push_consts);
+}
 
-    let pos = ubo_scene.view * vec4(in_pos.x, in_pos.y, in_pos.z, 1.0);
-    let view_mat3 = mat3(
-        ubo_scene.view.x_axis.truncate(),
-        ubo_scene.view.y_axis.truncate(),
-        ubo_scene.view.z_axis.truncate(),
+// Variant of influences8_vs for meshes with morph targets, compiled to influences8_morph.vert.spv.
+// The layout is the one of morph_vs, with the tangent in location 8 after the second set of
+// joints and weights.
+#[spirv(vertex)]
+pub fn influences8_morph_vs(
+    in_pos: Vec3,
+    in_normal: Vec3,
+    in_uv: Vec2,
+    in_color: Vec3,
+    in_joint_indices: Vec4,
+    in_joint_weights: Vec4,
+    in_joint_indices1: Vec4,
+    in_joint_weights1: Vec4,
+    in_tangent: Vec4,
+    #[spirv(vertex_index)] vertex_index: u32,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo_scene: &UboScene,
+    #[spirv(push_constant)] push_consts: &PushConsts,
+    #[spirv(storage_buffer, descriptor_set = 1, binding = 0)] joint_matrices: &[Mat4],
+    #[spirv(storage_buffer, descriptor_set = 1, binding = 0)] joint_dual_quats: &[DualQuat],
+    #[spirv(spec_constant(id = 0, default = 0))] dual_quaternion: u32,
+    #[spirv(storage_buffer, descriptor_set = 3, binding = 0)] morph_deltas: &[MorphDelta],
+    #[spirv(storage_buffer, descriptor_set = 3, binding = 1)] morph_weights: &[f32],
+    #[spirv(uniform, descriptor_set = 3, binding = 2)] morph_params: &MorphParams,
+    #[spirv(position)] out_position: &mut Vec4,
+    out_normal: &mut Vec3,
+    out_color: &mut Vec3,
+    out_uv: &mut Vec2,
+    out_view_vec: &mut Vec3,
+    out_light_vec: &mut Vec3,
+    out_tangent: &mut Vec4,
+) {
+    *out_color = in_color;
+    *out_uv = in_uv;
+
+    let (morphed_pos, morphed_normal, morphed_tangent) =
+        morph(in_pos, in_normal, in_tangent, vertex_index, morph_params, morph_deltas, morph_weights);
+    let influences = [(in_joint_indices, in_joint_weights), (in_joint_indices1, in_joint_weights1)];
+    let (pos, normal, tangent) = skin(
+        morphed_pos,
+        morphed_normal,
+        morphed_tangent.truncate(),
+        joint_matrices,
+        joint_dual_quats,
+        dual_quaternion != 0,
+        influences,
+        true,
     );
-    let l_pos = view_mat3 * ubo_scene.light_pos.truncate();
-    *out_light_vec = l_pos - pos.truncate();
-    *out_view_vec = -pos.truncate();
+    let (position, normal, light_vec, view_vec) = transform(morphed_pos, pos, normal, ubo_scene, push_consts);
+    *out_position = position;
+    *out_normal = normal;
+    *out_light_vec = light_vec;
+    *out_view_vec = view_vec;
+    *out_tangent = transform_tangent(tangent, morphed_tangent.w, push_consts);
 }
 
 #[spirv(fragment)]
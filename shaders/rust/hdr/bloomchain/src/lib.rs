@@ -0,0 +1,132 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Physically based bloom (Jimenez 2014, "Next Generation Post Processing in Call of Duty").
+//
+// bloom_downsample_cs is dispatched once per level of the bloom image, reading the linear HDR
+// scene of hdr/gbuffer's gbuffer_bloomchain_fs for level 0 and the previous level otherwise.
+// The scene has no exposure applied yet, so the threshold is in scene radiance. bloom_upsample_cs then walks the
+// chain back up, adding the tent filtered smaller level onto each larger one. Level 0 of
+// the bloom image is blended over the scene by hdr/composition's composition_bloomchain_fs.
+
+use spirv_std::glam::{ivec2, vec2, vec3, vec4, UVec2, UVec3, Vec2, Vec3, Vec4, Vec4Swizzles};
+use spirv_std::image::SampledImage;
+use spirv_std::{spirv, Image};
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PushConsts {
+    // Texel size of the level that is read
+    pub src_texel_size: Vec2,
+    // Size of the level that is written
+    pub dst_size: UVec2,
+    pub threshold: f32,
+    // Width of the soft transition around the threshold
+    pub knee: f32,
+    // Set for the first downsample, which applies the threshold and the Karis average
+    pub first_mip: u32,
+    // Radius of the upsample tent filter in source texels
+    pub filter_radius: f32,
+}
+
+fn luma(color: Vec3) -> f32 {
+    color.dot(vec3(0.2126, 0.7152, 0.0722))
+}
+
+// Quadratic soft knee around the threshold
+fn apply_threshold(color: Vec3, threshold: f32, knee: f32) -> Vec3 {
+    let brightness = color.x.max(color.y).max(color.z);
+    let soft = (brightness - threshold + knee).clamp(0.0, 2.0 * knee);
+    let soft = soft * soft / (4.0 * knee + 0.00001);
+    color * (soft.max(brightness - threshold) / brightness.max(0.00001))
+}
+
+// Weight of a box in the Karis average, suppresses fireflies from single very bright pixels
+fn karis_weight(color: Vec3) -> f32 {
+    1.0 / (1.0 + luma(color))
+}
+
+#[spirv(compute(threads(8, 8)))]
+pub fn bloom_downsample_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(descriptor_set = 0, binding = 0)] sampler_src: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(descriptor_set = 0, binding = 1)] dst_image: &Image!(2D, format=rgba16f, sampled=false),
+    #[spirv(push_constant)] push_consts: &PushConsts,
+) {
+    if global_id.x >= push_consts.dst_size.x || global_id.y >= push_consts.dst_size.y {
+        return;
+    }
+    let uv = (vec2(global_id.x as f32, global_id.y as f32) + 0.5) / push_consts.dst_size.as_vec2();
+    let texel = push_consts.src_texel_size;
+    let s = |x: f32, y: f32| sampler_src.sample_by_lod(uv + vec2(x, y) * texel, 0.0).xyz();
+
+    // 13 bilinear taps covering a 6x6 texel footprint
+    let a = s(-2.0, -2.0);
+    let b = s(0.0, -2.0);
+    let c = s(2.0, -2.0);
+    let d = s(-1.0, -1.0);
+    let e = s(1.0, -1.0);
+    let f = s(-2.0, 0.0);
+    let g = s(0.0, 0.0);
+    let h = s(2.0, 0.0);
+    let i = s(-1.0, 1.0);
+    let j = s(1.0, 1.0);
+    let k = s(-2.0, 2.0);
+    let l = s(0.0, 2.0);
+    let m = s(2.0, 2.0);
+
+    // Overlapping 2x2 boxes: the inner one and the four corner ones
+    let inner = (d + e + i + j) * 0.25;
+    let top_left = (a + b + f + g) * 0.25;
+    let top_right = (b + c + g + h) * 0.25;
+    let bottom_left = (f + g + k + l) * 0.25;
+    let bottom_right = (g + h + l + m) * 0.25;
+
+    let mut color = if push_consts.first_mip != 0 {
+        let w0 = karis_weight(inner) * 0.5;
+        let w1 = karis_weight(top_left) * 0.125;
+        let w2 = karis_weight(top_right) * 0.125;
+        let w3 = karis_weight(bottom_left) * 0.125;
+        let w4 = karis_weight(bottom_right) * 0.125;
+        (inner * w0 + top_left * w1 + top_right * w2 + bottom_left * w3 + bottom_right * w4) / (w0 + w1 + w2 + w3 + w4)
+    } else {
+        inner * 0.5 + (top_left + top_right + bottom_left + bottom_right) * 0.125
+    };
+
+    if push_consts.first_mip != 0 {
+        color = apply_threshold(color, push_consts.threshold, push_consts.knee);
+    }
+
+    unsafe {
+        dst_image.write(ivec2(global_id.x as i32, global_id.y as i32), vec4(color.x, color.y, color.z, 1.0));
+    }
+}
+
+#[spirv(compute(threads(8, 8)))]
+pub fn bloom_upsample_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(descriptor_set = 0, binding = 0)] sampler_src: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(descriptor_set = 0, binding = 1)] dst_image: &Image!(2D, format=rgba16f, sampled=false),
+    #[spirv(push_constant)] push_consts: &PushConsts,
+) {
+    if global_id.x >= push_consts.dst_size.x || global_id.y >= push_consts.dst_size.y {
+        return;
+    }
+    let coord = ivec2(global_id.x as i32, global_id.y as i32);
+    let uv = (vec2(global_id.x as f32, global_id.y as f32) + 0.5) / push_consts.dst_size.as_vec2();
+    let texel = push_consts.src_texel_size * push_consts.filter_radius;
+    let s = |x: f32, y: f32| sampler_src.sample_by_lod(uv + vec2(x, y) * texel, 0.0).xyz();
+
+    // 3x3 tent filter
+    let mut color = s(0.0, 0.0) * 4.0;
+    color += (s(0.0, -1.0) + s(-1.0, 0.0) + s(1.0, 0.0) + s(0.0, 1.0)) * 2.0;
+    color += s(-1.0, -1.0) + s(1.0, -1.0) + s(-1.0, 1.0) + s(1.0, 1.0);
+    color /= 16.0;
+
+    // Accumulate onto the downsampled content of this level
+    let current: Vec4 = dst_image.read(coord);
+    let color = current.xyz() + color;
+
+    unsafe {
+        dst_image.write(coord, vec4(color.x, color.y, color.z, 1.0));
+    }
+}
@@ -1,11 +1,19 @@
 #![cfg_attr(target_arch = "spirv", no_std)]
 
 use spirv_std::{
-    glam::{Vec2, Vec4},
+    glam::{vec4, Vec2, Vec3, Vec4, Vec4Swizzles},
     spirv,
+    num_traits::Float,
     Image, Sampler,
 };
 
+// Exposure of composition_bloomchain_fs, the value hdr/gbuffer's UBO holds
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct ExposureUBO {
+    pub exposure: f32,
+}
+
 #[spirv(vertex)]
 pub fn main_vs(
     #[spirv(vertex_index)] vertex_index: i32,
@@ -30,4 +38,33 @@ pub fn main_fs(
     out_color: &mut Vec4,
 ) {
     *out_color = image_color0.sample(*sampler_color0, in_uv);
-}
\ No newline at end of file
+}
+
+// Composition with the bloom of hdr/bloomchain, compiled to composition_bloomchain.frag.spv.
+// Binding 0 holds the linear HDR color of gbuffer_bloomchain_fs, which gets its exposure here
+// after the bloom is blended in. The layout is the one of main_fs plus level 0 of the bloom mip
+// chain in binding 2 and the ExposureUBO in binding 3.
+#[spirv(fragment)]
+pub fn composition_bloomchain_fs(
+    in_uv: Vec2,
+    #[spirv(descriptor_set = 0, binding = 0)] sampler_color0: &Sampler,
+    #[spirv(descriptor_set = 0, binding = 0)] image_color0: &Image!(2D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 1)] _sampler_color1: &Sampler,
+    #[spirv(descriptor_set = 0, binding = 1)] _image_color1: &Image!(2D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_bloom: &Sampler,
+    #[spirv(descriptor_set = 0, binding = 2)] image_bloom: &Image!(2D, type=f32, sampled),
+    #[spirv(uniform, descriptor_set = 0, binding = 3)] ubo: &ExposureUBO,
+    #[spirv(spec_constant(id = 0, default = 1025758986))] bloom_strength_bits: u32,
+    out_color: &mut Vec4,
+) {
+    let scene: Vec4 = image_color0.sample(*sampler_color0, in_uv);
+
+    // Energy conserving blend instead of adding the blurred bright parts on top
+    let bloom_strength = f32::from_bits(bloom_strength_bits);
+    let bloom: Vec4 = image_bloom.sample(*sampler_bloom, in_uv);
+    let color = scene.xyz().lerp(bloom.xyz(), bloom_strength);
+
+    // Manual exposure as in hdr/gbuffer
+    let color = Vec3::ONE - (-color * ubo.exposure).exp();
+    *out_color = vec4(color.x, color.y, color.z, scene.w);
+}
@@ -1,11 +1,12 @@
 #![cfg_attr(target_arch = "spirv", no_std)]
 
 use spirv_std::{
-    glam::{Mat3, Mat4, Vec3, Vec4},
+    glam::{Mat3, Mat4, Vec2, Vec3, Vec4},
     spirv,
     num_traits::Float,
     Image, Sampler,
 };
+use common::temporal::velocity;
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -16,62 +17,26 @@ pub struct UBO {
     pub exposure: f32,
 }
 
-#[spirv(vertex)]
-pub fn main_vs(
-    in_pos: Vec3,
-    in_normal: Vec3,
-    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
-    #[spirv(spec_constant(id = 0, default = 0))] type_id: u32,
-    #[spirv(position)] out_position: &mut Vec4,
-    out_uvw: &mut Vec3,
-    out_pos: &mut Vec3,
-    out_normal: &mut Vec3,
-    out_view_vec: &mut Vec3,
-    out_light_vec: &mut Vec3,
-) {
-    *out_uvw = in_pos;
-
-    match type_id as i32 {
-        0 => { // Skybox
-            let pos = Mat3::from_mat4(ubo.modelview) * in_pos;
-            *out_pos = pos;
-            *out_position = ubo.projection * Vec4::new(pos.x, pos.y, pos.z, 1.0);
-        }
-        1 => { // Object
-            let pos = ubo.modelview * Vec4::new(in_pos.x, in_pos.y, in_pos.z, 1.0);
-            *out_pos = Vec3::new(pos.x, pos.y, pos.z);
-            *out_position = ubo.projection * ubo.modelview * Vec4::new(in_pos.x, in_pos.y, in_pos.z, 1.0);
-        }
-        _ => {
-            let pos = ubo.modelview * Vec4::new(in_pos.x, in_pos.y, in_pos.z, 1.0);
-            *out_pos = Vec3::new(pos.x, pos.y, pos.z);
-            *out_position = ubo.projection * ubo.modelview * Vec4::new(in_pos.x, in_pos.y, in_pos.z, 1.0);
-        }
-    }
-
-    let pos = ubo.modelview * Vec4::new(in_pos.x, in_pos.y, in_pos.z, 1.0);
-    *out_pos = Vec3::new(pos.x, pos.y, pos.z);
-    *out_normal = Mat3::from_mat4(ubo.modelview) * in_normal;
-
-    let light_pos = Vec3::new(0.0, -5.0, 5.0);
-    *out_light_vec = light_pos - *out_pos;
-    *out_view_vec = -*out_pos;
+// Unjittered transforms of the current and previous frame for the velocity target
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MotionUBO {
+    pub mvp: Mat4,
+    pub prev_mvp: Mat4,
 }
 
-#[spirv(fragment)]
-pub fn main_fs(
+// Linear HDR color of the skybox, reflective or refractive object of the type_id pipeline
+#[allow(clippy::too_many_arguments)]
+fn scene_color(
     in_uvw: Vec3,
-    _in_pos: Vec3,
     in_normal: Vec3,
     in_view_vec: Vec3,
     in_light_vec: Vec3,
-    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
-    #[spirv(descriptor_set = 0, binding = 1)] sampler_env_map: &Sampler,
-    #[spirv(descriptor_set = 0, binding = 1)] image_env_map: &Image!(cube, type=f32, sampled),
-    #[spirv(spec_constant(id = 0, default = 0))] type_id: u32,
-    out_color0: &mut Vec4,
-    out_color1: &mut Vec4,
-) {
+    ubo: &UBO,
+    sampler_env_map: &Sampler,
+    image_env_map: &Image!(cube, type=f32, sampled),
+    type_id: u32,
+) -> Vec3 {
     let color = match type_id as i32 {
         0 => { // Skybox
             let normal = in_uvw.normalize();
@@ -124,9 +89,17 @@ pub fn main_fs(
         }
         _ => Vec4::new(1.0, 0.0, 1.0, 1.0)
     };
+    Vec3::new(color.x, color.y, color.z)
+}
 
+// Manual exposure
+fn expose(color: Vec3, exposure: f32) -> Vec3 {
+    Vec3::ONE - (-color * exposure).exp()
+}
+
+// Exposed color into attachment 0 and its bright parts for bloom into attachment 1
+fn color_targets(exposed: Vec3, out_color0: &mut Vec4, out_color1: &mut Vec4) {
     // Color with manual exposure into attachment 0
-    let exposed = Vec3::ONE - (-Vec3::new(color.x, color.y, color.z) * ubo.exposure).exp();
     out_color0.x = exposed.x;
     out_color0.y = exposed.y;
     out_color0.z = exposed.z;
@@ -145,4 +118,157 @@ pub fn main_fs(
         out_color1.z = 0.0;
     }
     out_color1.w = 1.0;
-}
\ No newline at end of file
+}
+
+// Vertex transform shared by main_vs and gbuffer_motion_vs
+#[allow(clippy::too_many_arguments)]
+fn transform(
+    in_pos: Vec3,
+    in_normal: Vec3,
+    ubo: &UBO,
+    type_id: u32,
+    out_position: &mut Vec4,
+    out_uvw: &mut Vec3,
+    out_pos: &mut Vec3,
+    out_normal: &mut Vec3,
+    out_view_vec: &mut Vec3,
+    out_light_vec: &mut Vec3,
+) {
+    *out_uvw = in_pos;
+
+    match type_id as i32 {
+        0 => { // Skybox
+            let pos = Mat3::from_mat4(ubo.modelview) * in_pos;
+            *out_pos = pos;
+            *out_position = ubo.projection * Vec4::new(pos.x, pos.y, pos.z, 1.0);
+        }
+        1 => { // Object
+            let pos = ubo.modelview * Vec4::new(in_pos.x, in_pos.y, in_pos.z, 1.0);
+            *out_pos = Vec3::new(pos.x, pos.y, pos.z);
+            *out_position = ubo.projection * ubo.modelview * Vec4::new(in_pos.x, in_pos.y, in_pos.z, 1.0);
+        }
+        _ => {
+            let pos = ubo.modelview * Vec4::new(in_pos.x, in_pos.y, in_pos.z, 1.0);
+            *out_pos = Vec3::new(pos.x, pos.y, pos.z);
+            *out_position = ubo.projection * ubo.modelview * Vec4::new(in_pos.x, in_pos.y, in_pos.z, 1.0);
+        }
+    }
+
+    let pos = ubo.modelview * Vec4::new(in_pos.x, in_pos.y, in_pos.z, 1.0);
+    *out_pos = Vec3::new(pos.x, pos.y, pos.z);
+    *out_normal = Mat3::from_mat4(ubo.modelview) * in_normal;
+
+    let light_pos = Vec3::new(0.0, -5.0, 5.0);
+    *out_light_vec = light_pos - *out_pos;
+    *out_view_vec = -*out_pos;
+}
+
+#[spirv(vertex)]
+pub fn main_vs(
+    in_pos: Vec3,
+    in_normal: Vec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
+    #[spirv(spec_constant(id = 0, default = 0))] type_id: u32,
+    #[spirv(position)] out_position: &mut Vec4,
+    out_uvw: &mut Vec3,
+    out_pos: &mut Vec3,
+    out_normal: &mut Vec3,
+    out_view_vec: &mut Vec3,
+    out_light_vec: &mut Vec3,
+) {
+    transform(in_pos, in_normal, ubo, type_id, out_position, out_uvw, out_pos, out_normal, out_view_vec, out_light_vec);
+}
+
+#[spirv(fragment)]
+pub fn main_fs(
+    in_uvw: Vec3,
+    _in_pos: Vec3,
+    in_normal: Vec3,
+    in_view_vec: Vec3,
+    in_light_vec: Vec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_env_map: &Sampler,
+    #[spirv(descriptor_set = 0, binding = 1)] image_env_map: &Image!(cube, type=f32, sampled),
+    #[spirv(spec_constant(id = 0, default = 0))] type_id: u32,
+    out_color0: &mut Vec4,
+    out_color1: &mut Vec4,
+) {
+    let color = scene_color(in_uvw, in_normal, in_view_vec, in_light_vec, ubo, sampler_env_map, image_env_map, type_id);
+    let exposed = expose(color, ubo.exposure);
+    color_targets(exposed, out_color0, out_color1);
+}
+
+// Vertex stage of gbuffer_motion_fs, compiled to gbuffer_motion.vert.spv. The layout is the one
+// of main_vs plus the MotionUBO in binding 2, the clip space positions of both frames are passed
+// on for the velocity target.
+#[spirv(vertex)]
+pub fn gbuffer_motion_vs(
+    in_pos: Vec3,
+    in_normal: Vec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
+    #[spirv(uniform, descriptor_set = 0, binding = 2)] motion: &MotionUBO,
+    #[spirv(spec_constant(id = 0, default = 0))] type_id: u32,
+    #[spirv(position)] out_position: &mut Vec4,
+    out_uvw: &mut Vec3,
+    out_pos: &mut Vec3,
+    out_normal: &mut Vec3,
+    out_view_vec: &mut Vec3,
+    out_light_vec: &mut Vec3,
+    out_clip_pos: &mut Vec4,
+    out_prev_clip_pos: &mut Vec4,
+) {
+    transform(in_pos, in_normal, ubo, type_id, out_position, out_uvw, out_pos, out_normal, out_view_vec, out_light_vec);
+
+    // The skybox only follows the camera rotation, so translation is dropped for it
+    let w = if type_id == 0 { 0.0 } else { 1.0 };
+    *out_clip_pos = motion.mvp * Vec4::new(in_pos.x, in_pos.y, in_pos.z, w);
+    *out_prev_clip_pos = motion.prev_mvp * Vec4::new(in_pos.x, in_pos.y, in_pos.z, w);
+}
+
+// Color targets of main_fs plus a velocity target for taa/resolve, compiled to
+// gbuffer_motion.frag.spv. Bindings are the ones of main_fs.
+#[spirv(fragment)]
+pub fn gbuffer_motion_fs(
+    in_uvw: Vec3,
+    _in_pos: Vec3,
+    in_normal: Vec3,
+    in_view_vec: Vec3,
+    in_light_vec: Vec3,
+    in_clip_pos: Vec4,
+    in_prev_clip_pos: Vec4,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_env_map: &Sampler,
+    #[spirv(descriptor_set = 0, binding = 1)] image_env_map: &Image!(cube, type=f32, sampled),
+    #[spirv(spec_constant(id = 0, default = 0))] type_id: u32,
+    out_color0: &mut Vec4,
+    out_color1: &mut Vec4,
+    out_velocity: &mut Vec2,
+) {
+    let color = scene_color(in_uvw, in_normal, in_view_vec, in_light_vec, ubo, sampler_env_map, image_env_map, type_id);
+    let exposed = expose(color, ubo.exposure);
+    color_targets(exposed, out_color0, out_color1);
+
+    // Motion vector for temporal anti-aliasing into attachment 2
+    *out_velocity = velocity(in_clip_pos, in_prev_clip_pos);
+}
+
+// Scene color for the bloom chain of hdr/bloomchain, compiled to gbuffer_bloomchain.frag.spv.
+// The vertex stage and bindings are the ones of main_fs. The single target holds the linear
+// HDR color without exposure, so that bloom_downsample_cs thresholds scene radiance and
+// composition_bloomchain_fs applies the exposure after adding the bloom.
+#[spirv(fragment)]
+pub fn gbuffer_bloomchain_fs(
+    in_uvw: Vec3,
+    _in_pos: Vec3,
+    in_normal: Vec3,
+    in_view_vec: Vec3,
+    in_light_vec: Vec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_env_map: &Sampler,
+    #[spirv(descriptor_set = 0, binding = 1)] image_env_map: &Image!(cube, type=f32, sampled),
+    #[spirv(spec_constant(id = 0, default = 0))] type_id: u32,
+    out_color: &mut Vec4,
+) {
+    let color = scene_color(in_uvw, in_normal, in_view_vec, in_light_vec, ubo, sampler_env_map, image_env_map, type_id);
+    *out_color = color.extend(1.0);
+}
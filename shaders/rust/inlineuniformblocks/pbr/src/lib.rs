@@ -1,7 +1,9 @@
 #![cfg_attr(target_arch = "spirv", no_std)]
 #![allow(clippy::missing_safety_doc)]
 
-use spirv_std::{spirv, glam::{mat3, vec3, vec4, Mat4, Vec3, Vec4}, num_traits::Float};
+use spirv_std::{spirv, glam::{mat3, vec3, vec4, Mat4, Vec3, Vec4}};
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::Float;
 use core::f32::consts::PI;
 
 #[repr(C)]
@@ -52,7 +54,7 @@ pub fn main_vs(
 }
 
 // Normal Distribution function --------------------------------------
-fn d_ggx(dot_nh: f32, roughness: f32) -> f32 {
+pub fn d_ggx(dot_nh: f32, roughness: f32) -> f32 {
     let alpha = roughness * roughness;
     let alpha2 = alpha * alpha;
     let denom = dot_nh * dot_nh * (alpha2 - 1.0) + 1.0;
@@ -60,7 +62,7 @@ fn d_ggx(dot_nh: f32, roughness: f32) -> f32 {
 }
 
 // Geometric Shadowing function --------------------------------------
-fn g_schlicksmith_ggx(dot_nl: f32, dot_nv: f32, roughness: f32) -> f32 {
+pub fn g_schlicksmith_ggx(dot_nl: f32, dot_nv: f32, roughness: f32) -> f32 {
     let r = roughness + 1.0;
     let k = (r * r) / 8.0;
     let gl = dot_nl / (dot_nl * (1.0 - k) + k);
@@ -75,7 +77,7 @@ fn f_schlick(cos_theta: f32, metallic: f32, material_color: Vec3) -> Vec3 {
 }
 
 // Specular BRDF composition --------------------------------------------
-fn brdf(l: Vec3, v: Vec3, n: Vec3, metallic: f32, roughness: f32, material_color: Vec3) -> Vec3 {
+pub fn brdf(l: Vec3, v: Vec3, n: Vec3, metallic: f32, roughness: f32, material_color: Vec3) -> Vec3 {
     // Precalculate vectors and dot products
     let h = (v + l).normalize();
     let dot_nv = n.dot(v).clamp(0.0, 1.0);
@@ -1,8 +1,10 @@
 #![no_std]
 
-use spirv_std::arch::{emit_mesh_tasks_ext, set_mesh_outputs_ext};
-use spirv_std::glam::{vec4, Mat4, UVec3, Vec3, Vec4};
+use spirv_std::arch::{emit_mesh_tasks_ext, emit_mesh_tasks_ext_payload, set_mesh_outputs_ext};
+use spirv_std::glam::{mat3, vec3, vec4, Mat4, UVec3, Vec3, Vec4};
 use spirv_std::spirv;
+use common::meshlet::{cone_culled, unpack_triangle, Meshlet, Vertex, MAX_MESHLET_TRIANGLES, MAX_MESHLET_VERTICES};
+use common::workgroup;
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -69,4 +71,156 @@ pub fn main_fs(
     out_frag_color: &mut Vec4,
 ) {
     *out_frag_color = vec4(in_color.x, in_color.y, in_color.z, 1.0);
-}
\ No newline at end of file
+}
+
+// Meshlet renderer, compiled to meshlet.task.spv, meshlet.mesh.spv and meshlet.frag.spv. The
+// task shader culls a meshlet per invocation against the view frustum and its normal cone and
+// passes the surviving meshlet indices on in the payload, the mesh shader then outputs the
+// vertices and triangles of one meshlet per workgroup.
+// The meshlet buffers use the layout of common::meshlet and are written by the meshlet builder.
+// Layout:
+//   binding 0: MeshletUBO
+//   binding 1: meshlets
+//   binding 2: meshlet vertex indices
+//   binding 3: packed meshlet triangles
+//   binding 4: vertices
+// Spec constant 0 colors each meshlet differently for debugging.
+
+const TASK_THREADS: usize = 32;
+const MESH_THREADS: usize = 64;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MeshletUBO {
+    pub projection: Mat4,
+    pub model: Mat4,
+    pub view: Mat4,
+    pub camera_pos: Vec4,
+    // World space, normalized so that the distance of a point to the plane is the dot product
+    pub frustum_planes: [Vec4; 6],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Payload {
+    pub meshlet_indices: [u32; TASK_THREADS],
+}
+
+// The cone test assumes a model matrix with uniform scale
+fn meshlet_visible(meshlet: &Meshlet, ubo: &MeshletUBO) -> bool {
+    let center = (ubo.model * meshlet.bounding_sphere.truncate().extend(1.0)).truncate();
+    let scale = ubo.model.x_axis.truncate().length()
+        .max(ubo.model.y_axis.truncate().length())
+        .max(ubo.model.z_axis.truncate().length());
+    let radius = meshlet.bounding_sphere.w * scale;
+
+    for i in 0..6 {
+        if ubo.frustum_planes[i].dot(center.extend(1.0)) + radius < 0.0 {
+            return false;
+        }
+    }
+
+    let axis = (ubo.model * meshlet.cone.truncate().extend(0.0)).truncate().normalize();
+    !cone_culled(center, radius, axis.extend(meshlet.cone.w), ubo.camera_pos.truncate())
+}
+
+#[spirv(task_ext(threads(32)))]
+pub fn meshlet_task(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(local_invocation_index)] local_index: u32,
+    #[spirv(workgroup)] compact_scratch: &mut [u32; TASK_THREADS],
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &MeshletUBO,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] meshlets: &[Meshlet],
+    #[spirv(task_payload_workgroup_ext)] payload: &mut Payload,
+) {
+    let index = global_id.x as usize;
+
+    // Out of range invocations can't return early as they still have to take part in the compaction
+    let visible = index < meshlets.len() && meshlet_visible(&meshlets[index], ubo);
+    let (offset, count) = workgroup::compact(visible, local_index, compact_scratch);
+    if visible {
+        payload.meshlet_indices[offset as usize] = global_id.x;
+    }
+
+    unsafe {
+        emit_mesh_tasks_ext_payload(count, 1, 1, payload);
+    }
+}
+
+// Distinct color per meshlet for debugging
+fn meshlet_color(index: u32) -> Vec3 {
+    let mut hash = index.wrapping_mul(0x9e37_79b9);
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    vec3(
+        (hash & 0xff) as f32 / 255.0,
+        ((hash >> 8) & 0xff) as f32 / 255.0,
+        ((hash >> 16) & 0xff) as f32 / 255.0,
+    )
+}
+
+// Output sizes match MAX_MESHLET_VERTICES and MAX_MESHLET_TRIANGLES
+#[spirv(mesh_ext(
+    threads(64),
+    output_vertices = 64,
+    output_primitives_ext = 124,
+    output_triangles_ext
+))]
+pub fn meshlet_mesh(
+    #[spirv(workgroup_id)] workgroup_id: UVec3,
+    #[spirv(local_invocation_index)] local_index: u32,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &MeshletUBO,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] meshlets: &[Meshlet],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] meshlet_vertices: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] meshlet_triangles: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 4)] vertices: &[Vertex],
+    #[spirv(task_payload_workgroup_ext)] payload: &Payload,
+    #[spirv(spec_constant(id = 0, default = 0))] debug_meshlets: u32,
+    #[spirv(position)] positions: &mut [Vec4; MAX_MESHLET_VERTICES],
+    #[spirv(primitive_triangle_indices_ext)] indices: &mut [UVec3; MAX_MESHLET_TRIANGLES],
+    out_normals: &mut [Vec3; MAX_MESHLET_VERTICES],
+    out_colors: &mut [Vec3; MAX_MESHLET_VERTICES],
+) {
+    let meshlet_index = payload.meshlet_indices[workgroup_id.x as usize];
+    let meshlet = meshlets[meshlet_index as usize];
+
+    unsafe {
+        set_mesh_outputs_ext(meshlet.vertex_count, meshlet.triangle_count);
+    }
+
+    let mvp = ubo.projection * ubo.view * ubo.model;
+    let model_mat3 = mat3(
+        ubo.model.x_axis.truncate(),
+        ubo.model.y_axis.truncate(),
+        ubo.model.z_axis.truncate(),
+    );
+    let color = if debug_meshlets != 0 { meshlet_color(meshlet_index) } else { Vec3::ONE };
+
+    let mut i = local_index as usize;
+    while i < meshlet.vertex_count as usize {
+        let vertex = vertices[meshlet_vertices[meshlet.vertex_offset as usize + i] as usize];
+        positions[i] = mvp * vertex.position.truncate().extend(1.0);
+        out_normals[i] = model_mat3 * vertex.normal.truncate();
+        out_colors[i] = color;
+        i += MESH_THREADS;
+    }
+
+    let mut i = local_index as usize;
+    while i < meshlet.triangle_count as usize {
+        indices[i] = unpack_triangle(meshlet_triangles[meshlet.triangle_offset as usize + i]);
+        i += MESH_THREADS;
+    }
+}
+
+#[spirv(fragment)]
+pub fn meshlet_fs(
+    in_normal: Vec3,
+    in_color: Vec3,
+    out_frag_color: &mut Vec4,
+) {
+    let light_dir = vec3(0.5, 1.0, 0.25).normalize();
+    let diffuse = in_normal.normalize().dot(light_dir).max(0.0);
+    let color = in_color * (0.25 + 0.75 * diffuse);
+    *out_frag_color = vec4(color.x, color.y, color.z, 1.0);
+}
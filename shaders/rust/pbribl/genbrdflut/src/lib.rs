@@ -1,7 +1,9 @@
 #![cfg_attr(target_arch = "spirv", no_std)]
 
 use spirv_std::glam::{vec2, vec3, vec4, Vec2, Vec3, Vec4};
-use spirv_std::{spirv, num_traits::Float};
+use spirv_std::spirv;
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::Float;
 
 use core::f32::consts::PI;
 
@@ -31,7 +33,7 @@ fn random(co: Vec2) -> f32 {
 }
 
 // Hammersley 2D sequence
-fn hammersley2d(i: u32, n: u32) -> Vec2 {
+pub fn hammersley2d(i: u32, n: u32) -> Vec2 {
     // Radical inverse based on http://holger.dammertz.org/stuff/notes_HammersleyOnHemisphere.html
     let mut bits = (i << 16) | (i >> 16);
     bits = ((bits & 0x55555555) << 1) | ((bits & 0xAAAAAAAA) >> 1);
@@ -43,7 +45,7 @@ fn hammersley2d(i: u32, n: u32) -> Vec2 {
 }
 
 // Importance sampling for GGX
-fn importance_sample_ggx(xi: Vec2, roughness: f32, normal: Vec3) -> Vec3 {
+pub fn importance_sample_ggx(xi: Vec2, roughness: f32, normal: Vec3) -> Vec3 {
     let alpha = roughness * roughness;
     let phi = 2.0 * PI * xi.x + random(vec2(normal.x, normal.z)) * 0.1;
     let cos_theta = ((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y)).sqrt();
@@ -65,7 +67,7 @@ fn importance_sample_ggx(xi: Vec2, roughness: f32, normal: Vec3) -> Vec3 {
 }
 
 // Geometric Shadowing function
-fn g_schlicksmith_ggx(dot_nl: f32, dot_nv: f32, roughness: f32) -> f32 {
+pub fn g_schlicksmith_ggx(dot_nl: f32, dot_nv: f32, roughness: f32) -> f32 {
     let k = (roughness * roughness) / 2.0;
     let gl = dot_nl / (dot_nl * (1.0 - k) + k);
     let gv = dot_nv / (dot_nv * (1.0 - k) + k);
@@ -73,7 +75,7 @@ fn g_schlicksmith_ggx(dot_nl: f32, dot_nv: f32, roughness: f32) -> f32 {
 }
 
 // BRDF integration
-fn brdf(nov: f32, roughness: f32, num_samples: u32) -> Vec2 {
+pub fn brdf(nov: f32, roughness: f32, num_samples: u32) -> Vec2 {
     // Normal always points along z-axis for the 2D lookup
     let n = vec3(0.0, 0.0, 1.0);
     let v = vec3((1.0 - nov * nov).sqrt(), 0.0, nov);
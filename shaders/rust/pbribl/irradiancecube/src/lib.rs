@@ -1,8 +1,9 @@
 #![cfg_attr(target_arch = "spirv", no_std)]
 
-use spirv_std::glam::{vec3, vec4, Mat4, Vec3, Vec4};
-use spirv_std::{spirv, num_traits::Float};
+use spirv_std::glam::{ivec3, vec2, vec3, vec4, Mat4, UVec2, UVec3, Vec3, Vec4};
+use spirv_std::{spirv, num_traits::Float, Image};
 use spirv_std::image::{SampledImage, Cubemap};
+use common::cubemap::face_direction;
 
 // Push constants with padding to match GLSL layout
 #[derive(Copy, Clone)]
@@ -15,6 +16,15 @@ pub struct PushConsts {
 
 use core::f32::consts::{PI, TAU};
 
+// Compute shader push constants
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct PushConstsCompute {
+    face_size: u32,
+    delta_phi: f32,
+    delta_theta: f32,
+}
+
 // Vertex shader push constants
 #[derive(Copy, Clone)]
 #[repr(C)]
@@ -70,4 +80,58 @@ pub fn main_fs(
         color.z * PI / (sample_count as f32),
         1.0
     );
-}
\ No newline at end of file
+}
+
+// Convolves all six faces in one dispatch of (face_size / 8, face_size / 8, 6) workgroups,
+// writing to the irradiance cubemap bound as a 2D array storage view with one layer per face.
+// Without derivatives each sample reads the source mip whose texels best match the solid
+// angle it covers, which keeps the small number of samples from aliasing.
+#[spirv(compute(threads(8, 8, 1)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(push_constant)] consts: &PushConstsCompute,
+    #[spirv(descriptor_set = 0, binding = 0)] sampler_env: &SampledImage<Cubemap>,
+    #[spirv(descriptor_set = 0, binding = 1)] irradiance_map: &Image!(2D, format=rgba16f, sampled=false, arrayed),
+) {
+    if global_id.x >= consts.face_size || global_id.y >= consts.face_size || global_id.z >= 6 {
+        return;
+    }
+    let uv = (vec2(global_id.x as f32, global_id.y as f32) + 0.5) / consts.face_size as f32;
+    let n = face_direction(global_id.z, uv);
+    let up = vec3(0.0, 1.0, 0.0);
+    let right = up.cross(n).normalize();
+    let up = n.cross(right);
+
+    const HALF_PI: f32 = PI * 0.5;
+
+    // Solid angle of one texel of the environment map
+    let env_map_size: UVec2 = sampler_env.query_size_lod(0);
+    let omega_p = 4.0 * PI / (6.0 * (env_map_size.x * env_map_size.x) as f32);
+
+    let mut color = Vec3::ZERO;
+    let mut sample_count = 0u32;
+
+    let mut phi = 0.0;
+    while phi < TAU {
+        let mut theta = 0.0;
+        while theta < HALF_PI {
+            let temp_vec = phi.cos() * right + phi.sin() * up;
+            let sample_vector = theta.cos() * n + theta.sin() * temp_vec;
+            let omega_s = consts.delta_phi * consts.delta_theta * theta.sin().max(consts.delta_theta);
+            let lod = (0.5 * (omega_s / omega_p).log2()).max(0.0);
+            color += sampler_env.sample_by_lod(sample_vector, lod).truncate() * theta.cos() * theta.sin();
+            sample_count += 1;
+
+            theta += consts.delta_theta;
+        }
+        phi += consts.delta_phi;
+    }
+
+    let result = color * PI / (sample_count as f32);
+    unsafe {
+        irradiance_map.write(
+            ivec3(global_id.x as i32, global_id.y as i32, global_id.z as i32),
+            vec4(result.x, result.y, result.z, 1.0),
+        );
+    }
+}
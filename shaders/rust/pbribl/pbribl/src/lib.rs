@@ -1,8 +1,11 @@
 #![cfg_attr(target_arch = "spirv", no_std)]
 
 use spirv_std::glam::{vec2, vec3, vec4, Mat4, Vec2, Vec3, Vec4};
-use spirv_std::{spirv, num_traits::Float};
+use spirv_std::{spirv, Image};
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::Float;
 use spirv_std::image::{SampledImage, Cubemap};
+use common::sh::{self, SH_COEFFICIENT_COUNT};
 
 // UBO structure for camera matrices
 #[derive(Copy, Clone)]
@@ -84,7 +87,7 @@ fn uncharted2_tonemap(x: Vec3) -> Vec3 {
 }
 
 // Normal Distribution function (GGX)
-fn d_ggx(dot_nh: f32, roughness: f32) -> f32 {
+pub fn d_ggx(dot_nh: f32, roughness: f32) -> f32 {
     let alpha = roughness * roughness;
     let alpha2 = alpha * alpha;
     let denom = dot_nh * dot_nh * (alpha2 - 1.0) + 1.0;
@@ -92,7 +95,7 @@ fn d_ggx(dot_nh: f32, roughness: f32) -> f32 {
 }
 
 // Geometric Shadowing function (Schlick-Smith GGX)
-fn g_schlicksmith_ggx(dot_nl: f32, dot_nv: f32, roughness: f32) -> f32 {
+pub fn g_schlicksmith_ggx(dot_nl: f32, dot_nv: f32, roughness: f32) -> f32 {
     let r = roughness + 1.0;
     let k = (r * r) / 8.0;
     let gl = dot_nl / (dot_nl * (1.0 - k) + k);
@@ -133,7 +136,7 @@ fn prefiltered_reflection(
 }
 
 // Calculate specular contribution from a light
-fn specular_contribution(
+pub fn specular_contribution(
     l: Vec3,
     v: Vec3,
     n: Vec3,
@@ -163,23 +166,18 @@ fn specular_contribution(
     color
 }
 
-#[spirv(fragment)]
-pub fn main_fs(
+// Direct lighting, image based ambient part, tone mapping and gamma correction
+#[allow(clippy::too_many_arguments)]
+fn shade(
     in_world_pos: Vec3,
-    in_normal: Vec3,
-    _in_uv: Vec2,
-    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
-    #[spirv(uniform, descriptor_set = 0, binding = 1)] ubo_params: &UBOParams,
-    #[spirv(push_constant)] material: &PushConstsMaterial,
-    #[spirv(descriptor_set = 0, binding = 2)] sampler_irradiance: &SampledImage<Cubemap>,
-    #[spirv(descriptor_set = 0, binding = 3)] sampler_brdf_lut: &SampledImage<spirv_std::image::Image2d>,
-    #[spirv(descriptor_set = 0, binding = 4)] prefiltered_map: &SampledImage<Cubemap>,
-    out_color: &mut Vec4,
-) {
-    let n = in_normal.normalize();
-    let v = (ubo.cam_pos - in_world_pos).normalize();
-    let r = reflect(-v, n);
-    
+    n: Vec3,
+    v: Vec3,
+    ubo_params: &UBOParams,
+    material: &PushConstsMaterial,
+    sampler_brdf_lut: &SampledImage<spirv_std::image::Image2d>,
+    irradiance: Vec3,
+    reflection: Vec3,
+) -> Vec4 {
     let metallic = material.metallic;
     let roughness = material.roughness;
     let albedo = vec3(material.r, material.g, material.b);
@@ -198,12 +196,6 @@ pub fn main_fs(
     let n_dot_v = n.dot(v).max(0.0);
     let brdf = sampler_brdf_lut.sample(vec2(n_dot_v, roughness)).truncate().truncate();
     
-    // Sample prefiltered reflection
-    let reflection = prefiltered_reflection(r, roughness, prefiltered_map);
-    
-    // Sample irradiance
-    let irradiance = sampler_irradiance.sample(n).truncate();
-    
     // Calculate diffuse
     let diffuse = irradiance * albedo;
     
@@ -230,5 +222,89 @@ pub fn main_fs(
     let inv_gamma = 1.0 / ubo_params.gamma;
     color = vec3(color.x.powf(inv_gamma), color.y.powf(inv_gamma), color.z.powf(inv_gamma));
     
-    *out_color = vec4(color.x, color.y, color.z, 1.0);
-}
\ No newline at end of file
+    vec4(color.x, color.y, color.z, 1.0)
+}
+
+#[spirv(fragment)]
+pub fn main_fs(
+    in_world_pos: Vec3,
+    in_normal: Vec3,
+    _in_uv: Vec2,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
+    #[spirv(uniform, descriptor_set = 0, binding = 1)] ubo_params: &UBOParams,
+    #[spirv(push_constant)] material: &PushConstsMaterial,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_irradiance: &SampledImage<Cubemap>,
+    #[spirv(descriptor_set = 0, binding = 3)] sampler_brdf_lut: &SampledImage<spirv_std::image::Image2d>,
+    #[spirv(descriptor_set = 0, binding = 4)] prefiltered_map: &SampledImage<Cubemap>,
+    out_color: &mut Vec4,
+) {
+    let n = in_normal.normalize();
+    let v = (ubo.cam_pos - in_world_pos).normalize();
+    let r = reflect(-v, n);
+    
+    // Sample prefiltered reflection
+    let reflection = prefiltered_reflection(r, material.roughness, prefiltered_map);
+    
+    // Sample irradiance
+    let irradiance = sampler_irradiance.sample(n).truncate();
+    
+    *out_color = shade(in_world_pos, n, v, ubo_params, material, sampler_brdf_lut, irradiance, reflection);
+}
+
+// main_fs with the irradiance evaluated from the L2 spherical harmonics of pbribl/shprojection
+// instead of the irradiance cube, compiled to pbribl_sh.frag.spv. The layout is the one of
+// main_fs plus the coefficient buffer in binding 5, binding 2 is unused.
+#[spirv(fragment)]
+pub fn pbribl_sh_fs(
+    in_world_pos: Vec3,
+    in_normal: Vec3,
+    _in_uv: Vec2,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
+    #[spirv(uniform, descriptor_set = 0, binding = 1)] ubo_params: &UBOParams,
+    #[spirv(push_constant)] material: &PushConstsMaterial,
+    #[spirv(descriptor_set = 0, binding = 3)] sampler_brdf_lut: &SampledImage<spirv_std::image::Image2d>,
+    #[spirv(descriptor_set = 0, binding = 4)] prefiltered_map: &SampledImage<Cubemap>,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 5)] sh_coefficients: &[Vec4; SH_COEFFICIENT_COUNT],
+    out_color: &mut Vec4,
+) {
+    let n = in_normal.normalize();
+    let v = (ubo.cam_pos - in_world_pos).normalize();
+    let r = reflect(-v, n);
+    
+    let reflection = prefiltered_reflection(r, material.roughness, prefiltered_map);
+    let irradiance = sh::irradiance(sh_coefficients, n);
+    
+    *out_color = shade(in_world_pos, n, v, ubo_params, material, sampler_brdf_lut, irradiance, reflection);
+}
+
+// main_fs with screen space reflections, compiled to pbribl_ssr.frag.spv. The layout is the one
+// of main_fs plus the reflection buffer of ssr/trace in binding 6 at the
+// resolution of the framebuffer. Its alpha holds the confidence of the screen space hit and
+// blends from the prefiltered map to the hit colour.
+#[spirv(fragment)]
+pub fn pbribl_ssr_fs(
+    in_world_pos: Vec3,
+    in_normal: Vec3,
+    _in_uv: Vec2,
+    #[spirv(frag_coord)] frag_coord: Vec4,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
+    #[spirv(uniform, descriptor_set = 0, binding = 1)] ubo_params: &UBOParams,
+    #[spirv(push_constant)] material: &PushConstsMaterial,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_irradiance: &SampledImage<Cubemap>,
+    #[spirv(descriptor_set = 0, binding = 3)] sampler_brdf_lut: &SampledImage<spirv_std::image::Image2d>,
+    #[spirv(descriptor_set = 0, binding = 4)] prefiltered_map: &SampledImage<Cubemap>,
+    #[spirv(descriptor_set = 0, binding = 6)] reflection_image: &Image!(2D, type=f32, sampled),
+    out_color: &mut Vec4,
+) {
+    let n = in_normal.normalize();
+    let v = (ubo.cam_pos - in_world_pos).normalize();
+    let r = reflect(-v, n);
+    
+    let env = prefiltered_reflection(r, material.roughness, prefiltered_map);
+    let ssr: Vec4 = reflection_image.fetch(frag_coord.truncate().truncate().as_ivec2());
+    let reflection = env.lerp(ssr.truncate(), ssr.w);
+    
+    let irradiance = sampler_irradiance.sample(n).truncate();
+    
+    *out_color = shade(in_world_pos, n, v, ubo_params, material, sampler_brdf_lut, irradiance, reflection);
+}
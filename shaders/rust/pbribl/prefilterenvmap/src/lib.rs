@@ -1,8 +1,11 @@
 #![cfg_attr(target_arch = "spirv", no_std)]
 
-use spirv_std::glam::{vec2, vec3, vec4, Mat4, Vec2, Vec3, Vec4, UVec2};
-use spirv_std::{spirv, num_traits::Float};
+use spirv_std::glam::{ivec3, vec2, vec3, vec4, Mat4, Vec2, Vec3, Vec4, UVec2, UVec3};
+use spirv_std::{spirv, Image};
+#[cfg(target_arch = "spirv")]
+use spirv_std::num_traits::Float;
 use spirv_std::image::{SampledImage, Cubemap};
+use common::cubemap::face_direction;
 
 
 // Push constants with padding to match GLSL layout
@@ -16,6 +19,20 @@ pub struct PushConsts {
 
 use core::f32::consts::{PI, TAU};
 
+// Mip levels of the prefiltered cubemap written by the compute path, enough for 512x512 faces
+const MAX_MIP_LEVELS: usize = 10;
+const WORKGROUP_SIZE: u32 = 64;
+
+// Compute shader push constants
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct PushConstsCompute {
+    // Face size of the first mip of the prefiltered cubemap
+    face_size: u32,
+    mip_levels: u32,
+    num_samples: u32,
+}
+
 // Vertex shader push constants
 #[derive(Copy, Clone)]
 #[repr(C)]
@@ -79,7 +96,7 @@ fn importance_sample_ggx(xi: Vec2, roughness: f32, normal: Vec3) -> Vec3 {
 }
 
 // Normal Distribution function (GGX)
-fn d_ggx(dot_nh: f32, roughness: f32) -> f32 {
+pub fn d_ggx(dot_nh: f32, roughness: f32) -> f32 {
     let alpha = roughness * roughness;
     let alpha2 = alpha * alpha;
     let denom = dot_nh * dot_nh * (alpha2 - 1.0) + 1.0;
@@ -144,4 +161,54 @@ pub fn main_fs(
     let n = in_pos.normalize();
     let result = prefilter_env_map(n, consts.roughness, consts.num_samples, sampler_env);
     *out_color = vec4(result.x, result.y, result.z, 1.0);
-}
\ No newline at end of file
+}
+
+// Prefilters all faces and mips in a single dispatch. Every mip of the output is bound as a 2D
+// array storage view with one layer per face. Along x the dispatch walks the texels of all
+// mips of a face, with each mip padded to whole workgroups so that the view index stays
+// uniform within a workgroup; z selects the face. The host dispatches
+// (sum over mips of ceil(size^2 / 64), 1, 6) workgroups.
+#[spirv(compute(threads(64)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(push_constant)] consts: &PushConstsCompute,
+    #[spirv(descriptor_set = 0, binding = 0)] sampler_env: &SampledImage<Cubemap>,
+    #[spirv(descriptor_set = 0, binding = 1)] prefiltered_mips: &[Image!(2D, format=rgba16f, sampled=false, arrayed); MAX_MIP_LEVELS],
+) {
+    // Find the mip this invocation belongs to
+    let mut index = global_id.x;
+    let mut mip = 0;
+    let mut size = consts.face_size;
+    loop {
+        if mip >= consts.mip_levels.min(MAX_MIP_LEVELS as u32) {
+            return;
+        }
+        let padded = (size * size).div_ceil(WORKGROUP_SIZE) * WORKGROUP_SIZE;
+        if index < padded {
+            break;
+        }
+        index -= padded;
+        size = (size >> 1).max(1);
+        mip += 1;
+    }
+    if index >= size * size || global_id.z >= 6 {
+        return;
+    }
+
+    let x = index % size;
+    let y = index / size;
+    let uv = (vec2(x as f32, y as f32) + 0.5) / size as f32;
+    let n = face_direction(global_id.z, uv);
+
+    // The first mip is a plain copy of the environment
+    let result = if mip == 0 {
+        sampler_env.sample_by_lod(n, 0.0).truncate()
+    } else {
+        let roughness = mip as f32 / (consts.mip_levels - 1) as f32;
+        prefilter_env_map(n, roughness, consts.num_samples, sampler_env)
+    };
+
+    unsafe {
+        prefiltered_mips[mip as usize].write(ivec3(x as i32, y as i32, global_id.z as i32), vec4(result.x, result.y, result.z, 1.0));
+    }
+}
@@ -0,0 +1,84 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Projects the environment cubemap onto L2 spherical harmonics, as a cheaper alternative to
+// pbribl/irradiancecube. Dispatched as a single workgroup that samples a SAMPLE_SIZE^2 grid on
+// every face, weighting each sample by the solid angle of its texel. The coefficients are
+// evaluated by pbribl_sh_fs of pbribl/pbribl.
+
+use spirv_std::arch::workgroup_memory_barrier_with_group_sync;
+use spirv_std::glam::{vec3, vec4, Vec3, Vec4, Vec4Swizzles};
+use spirv_std::image::{Cubemap, SampledImage};
+use spirv_std::spirv;
+use common::scan::MAX_SUBGROUPS;
+use common::sh::{accumulate, cube_sample, normalize_projection, SH_COEFFICIENT_COUNT};
+use common::subgroup;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PushConsts {
+    // Mip level of the environment map whose resolution is closest to SAMPLE_SIZE
+    pub lod: f32,
+}
+
+const WORKGROUP_SIZE: u32 = 256;
+const SAMPLE_SIZE: u32 = 32;
+// Three channels per coefficient plus the total weight
+const SUMS: usize = SH_COEFFICIENT_COUNT * 3 + 1;
+
+#[spirv(compute(threads(256)))]
+pub fn main_cs(
+    #[spirv(local_invocation_index)] local_index: u32,
+    #[spirv(subgroup_id)] subgroup_id: u32,
+    #[spirv(subgroup_local_invocation_id)] subgroup_lane: u32,
+    #[spirv(num_subgroups)] num_subgroups: u32,
+    #[spirv(descriptor_set = 0, binding = 0)] environment_map: &SampledImage<Cubemap>,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] sh_coefficients: &mut [Vec4; SH_COEFFICIENT_COUNT],
+    #[spirv(push_constant)] push_consts: &PushConsts,
+    #[spirv(workgroup)] partial_sums: &mut [f32; SUMS * MAX_SUBGROUPS],
+) {
+    let mut coefficients = [Vec3::ZERO; SH_COEFFICIENT_COUNT];
+    let mut weight_sum = 0.0;
+
+    let mut index = local_index;
+    while index < 6 * SAMPLE_SIZE * SAMPLE_SIZE {
+        let (dir, weight) = cube_sample(index, SAMPLE_SIZE);
+        let radiance = environment_map.sample_by_lod(dir, push_consts.lod).xyz();
+        accumulate(&mut coefficients, radiance, dir, weight);
+        weight_sum += weight;
+
+        index += WORKGROUP_SIZE;
+    }
+
+    // Sum over the subgroup, then over the subgroups through shared memory
+    for i in 0..SH_COEFFICIENT_COUNT {
+        let x = subgroup::reduce_add_f32(coefficients[i].x);
+        let y = subgroup::reduce_add_f32(coefficients[i].y);
+        let z = subgroup::reduce_add_f32(coefficients[i].z);
+        if subgroup_lane == 0 {
+            let base = subgroup_id as usize * SUMS + i * 3;
+            partial_sums[base] = x;
+            partial_sums[base + 1] = y;
+            partial_sums[base + 2] = z;
+        }
+    }
+    let total_weight = subgroup::reduce_add_f32(weight_sum);
+    if subgroup_lane == 0 {
+        partial_sums[subgroup_id as usize * SUMS + SUMS - 1] = total_weight;
+    }
+    unsafe {
+        workgroup_memory_barrier_with_group_sync();
+    }
+
+    if (local_index as usize) < SH_COEFFICIENT_COUNT {
+        let i = local_index as usize;
+        let mut sum = Vec3::ZERO;
+        let mut weight = 0.0;
+        for s in 0..num_subgroups as usize {
+            let base = s * SUMS;
+            sum += vec3(partial_sums[base + i * 3], partial_sums[base + i * 3 + 1], partial_sums[base + i * 3 + 2]);
+            weight += partial_sums[base + SUMS - 1];
+        }
+        let c = normalize_projection(sum, weight);
+        sh_coefficients[i] = vec4(c.x, c.y, c.z, 0.0);
+    }
+}
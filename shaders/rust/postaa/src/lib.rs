@@ -0,0 +1,391 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Post-process anti-aliasing: FXAA 3.11 (quality preset) and SMAA 1x.
+//
+// FXAA is a single fullscreen pass. SMAA 1x runs three passes:
+//   smaa_edge_fs:   luma edge detection into an RG8 target, cleared to zero as pixels
+//                   without edges are discarded (use the stencil to skip them in the next pass)
+//   smaa_weight_fs: blending weight calculation into an RGBA8 target using the precomputed
+//                   area and search textures of the reference implementation
+//   smaa_blend_fs:  neighbourhood blending of the input color with those weights
+// All passes share the fullscreen triangle vertex shader. The edges, area and search textures
+// have to be bound with linear filtering, as the searches rely on bilinear fetches.
+
+use spirv_std::glam::{vec2, vec3, vec4, Vec2, Vec3, Vec4, Vec4Swizzles};
+use spirv_std::image::SampledImage;
+use spirv_std::num_traits::Float;
+use spirv_std::{spirv, Image};
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct UBO {
+    // (1 / width, 1 / height, width, height) of the render target
+    pub rt_metrics: Vec4,
+}
+
+type Texture2D = SampledImage<Image!(2D, type=f32, sampled)>;
+
+fn luma(rgb: Vec3) -> f32 {
+    rgb.dot(vec3(0.299, 0.587, 0.114))
+}
+
+fn sample(tex: &Texture2D, uv: Vec2) -> Vec4 {
+    tex.sample_by_lod(uv, 0.0)
+}
+
+#[spirv(vertex)]
+pub fn main_vs(
+    #[spirv(vertex_index)] vert_index: i32,
+    #[spirv(position)] out_position: &mut Vec4,
+    out_uv: &mut Vec2,
+) {
+    let uv = vec2(((vert_index << 1) & 2) as f32, (vert_index & 2) as f32);
+    *out_uv = uv;
+    *out_position = vec4(uv.x * 2.0 - 1.0, uv.y * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// FXAA 3.11
+
+// Step sizes of the edge end search (quality preset 12)
+const FXAA_QUALITY_STEPS: [f32; 5] = [1.0, 1.5, 2.0, 4.0, 12.0];
+// Amount of sub-pixel aliasing removal
+const FXAA_SUBPIX: f32 = 0.75;
+// Minimum local contrast required to apply the algorithm
+const FXAA_EDGE_THRESHOLD: f32 = 0.166;
+// Trims the algorithm from processing darks
+const FXAA_EDGE_THRESHOLD_MIN: f32 = 0.0833;
+
+#[spirv(fragment)]
+pub fn fxaa_fs(
+    in_uv: Vec2,
+    #[spirv(descriptor_set = 0, binding = 0)] sampler_color: &Texture2D,
+    #[spirv(uniform, descriptor_set = 0, binding = 1)] ubo: &UBO,
+    out_frag_color: &mut Vec4,
+) {
+    let rcp = ubo.rt_metrics.xy();
+    let luma_at = |x: f32, y: f32| luma(sample(sampler_color, in_uv + vec2(x, y) * rcp).xyz());
+
+    let color_m = sample(sampler_color, in_uv);
+    let luma_m = luma(color_m.xyz());
+    let mut luma_n = luma_at(0.0, -1.0);
+    let mut luma_s = luma_at(0.0, 1.0);
+    let luma_e = luma_at(1.0, 0.0);
+    let luma_w = luma_at(-1.0, 0.0);
+
+    // Early exit on low local contrast
+    let range_max = luma_m.max(luma_n).max(luma_s).max(luma_e).max(luma_w);
+    let range_min = luma_m.min(luma_n).min(luma_s).min(luma_e).min(luma_w);
+    let range = range_max - range_min;
+    if range < FXAA_EDGE_THRESHOLD_MIN.max(range_max * FXAA_EDGE_THRESHOLD) {
+        *out_frag_color = color_m;
+        return;
+    }
+
+    let luma_nw = luma_at(-1.0, -1.0);
+    let luma_se = luma_at(1.0, 1.0);
+    let luma_ne = luma_at(1.0, -1.0);
+    let luma_sw = luma_at(-1.0, 1.0);
+
+    // Edge orientation from the 3x3 neighbourhood
+    let luma_ns = luma_n + luma_s;
+    let luma_we = luma_w + luma_e;
+    let luma_nese = luma_ne + luma_se;
+    let luma_nwne = luma_nw + luma_ne;
+    let luma_nwsw = luma_nw + luma_sw;
+    let luma_swse = luma_sw + luma_se;
+    let edge_horz = (-2.0 * luma_w + luma_nwsw).abs()
+        + (-2.0 * luma_m + luma_ns).abs() * 2.0
+        + (-2.0 * luma_e + luma_nese).abs();
+    let edge_vert = (-2.0 * luma_s + luma_swse).abs()
+        + (-2.0 * luma_m + luma_we).abs() * 2.0
+        + (-2.0 * luma_n + luma_nwne).abs();
+    let horz_span = edge_horz >= edge_vert;
+
+    let subpix_a = (luma_ns + luma_we) * 2.0 + luma_nwsw + luma_nese;
+    let subpix_b = subpix_a / 12.0 - luma_m;
+
+    // Pick the side of the edge with the steeper gradient
+    let mut length_sign = if horz_span { rcp.y } else { rcp.x };
+    if !horz_span {
+        luma_n = luma_w;
+        luma_s = luma_e;
+    }
+    let gradient_n = luma_n - luma_m;
+    let gradient_s = luma_s - luma_m;
+    let pair_n = gradient_n.abs() >= gradient_s.abs();
+    let gradient = gradient_n.abs().max(gradient_s.abs());
+    if pair_n {
+        length_sign = -length_sign;
+    }
+    let subpix_c = (subpix_b.abs() / range).clamp(0.0, 1.0);
+
+    // Start on the edge, half a pixel towards the chosen side
+    let mut pos_b = in_uv;
+    if horz_span {
+        pos_b.y += length_sign * 0.5;
+    } else {
+        pos_b.x += length_sign * 0.5;
+    }
+    let off_np = if horz_span { vec2(rcp.x, 0.0) } else { vec2(0.0, rcp.y) };
+
+    let luma_nn = if pair_n { luma_n + luma_m } else { luma_s + luma_m };
+    let gradient_scaled = gradient * 0.25;
+    let luma_m_lt_zero = luma_m - luma_nn * 0.5 < 0.0;
+
+    let mut pos_n = pos_b - off_np * FXAA_QUALITY_STEPS[0];
+    let mut pos_p = pos_b + off_np * FXAA_QUALITY_STEPS[0];
+    let mut luma_end_n = luma(sample(sampler_color, pos_n).xyz()) - luma_nn * 0.5;
+    let mut luma_end_p = luma(sample(sampler_color, pos_p).xyz()) - luma_nn * 0.5;
+    let mut done_n = luma_end_n.abs() >= gradient_scaled;
+    let mut done_p = luma_end_p.abs() >= gradient_scaled;
+
+    // Walk along the edge in both directions until its ends are found
+    let mut step = 1;
+    loop {
+        if !done_n {
+            pos_n -= off_np * FXAA_QUALITY_STEPS[step];
+        }
+        if !done_p {
+            pos_p += off_np * FXAA_QUALITY_STEPS[step];
+        }
+        step += 1;
+        if (done_n && done_p) || step == FXAA_QUALITY_STEPS.len() {
+            break;
+        }
+        if !done_n {
+            luma_end_n = luma(sample(sampler_color, pos_n).xyz()) - luma_nn * 0.5;
+            done_n = luma_end_n.abs() >= gradient_scaled;
+        }
+        if !done_p {
+            luma_end_p = luma(sample(sampler_color, pos_p).xyz()) - luma_nn * 0.5;
+            done_p = luma_end_p.abs() >= gradient_scaled;
+        }
+    }
+
+    let dst_n = if horz_span { in_uv.x - pos_n.x } else { in_uv.y - pos_n.y };
+    let dst_p = if horz_span { pos_p.x - in_uv.x } else { pos_p.y - in_uv.y };
+    let good_span = if dst_n < dst_p {
+        (luma_end_n < 0.0) != luma_m_lt_zero
+    } else {
+        (luma_end_p < 0.0) != luma_m_lt_zero
+    };
+    let pixel_offset = if good_span {
+        -dst_n.min(dst_p) / (dst_n + dst_p) + 0.5
+    } else {
+        0.0
+    };
+
+    // Sub-pixel aliasing removal for features smaller than a pixel
+    let subpix_d = (-2.0 * subpix_c + 3.0) * subpix_c * subpix_c;
+    let subpix_h = subpix_d * subpix_d * FXAA_SUBPIX;
+
+    let offset = pixel_offset.max(subpix_h) * length_sign;
+    let mut pos_m = in_uv;
+    if horz_span {
+        pos_m.y += offset;
+    } else {
+        pos_m.x += offset;
+    }
+    *out_frag_color = sample(sampler_color, pos_m);
+}
+
+// SMAA 1x
+
+const SMAA_THRESHOLD: f32 = 0.1;
+const SMAA_LOCAL_CONTRAST_ADAPTATION_FACTOR: f32 = 2.0;
+const SMAA_MAX_SEARCH_STEPS: f32 = 16.0;
+const SMAA_AREATEX_MAX_DISTANCE: f32 = 16.0;
+const SMAA_AREATEX_PIXEL_SIZE: Vec2 = Vec2::new(1.0 / 160.0, 1.0 / 560.0);
+const SMAA_SEARCHTEX_SIZE: Vec2 = Vec2::new(66.0, 33.0);
+const SMAA_SEARCHTEX_PACKED_SIZE: Vec2 = Vec2::new(64.0, 16.0);
+
+#[spirv(fragment)]
+pub fn smaa_edge_fs(
+    in_uv: Vec2,
+    #[spirv(descriptor_set = 0, binding = 0)] sampler_color: &Texture2D,
+    #[spirv(uniform, descriptor_set = 0, binding = 1)] ubo: &UBO,
+    out_edges: &mut Vec2,
+) {
+    let rcp = ubo.rt_metrics.xy();
+    let luma_at = |x: f32, y: f32| luma(sample(sampler_color, in_uv + vec2(x, y) * rcp).xyz());
+
+    let l = luma_at(0.0, 0.0);
+    let l_left = luma_at(-1.0, 0.0);
+    let l_top = luma_at(0.0, -1.0);
+
+    let delta_left = (l - l_left).abs();
+    let delta_top = (l - l_top).abs();
+    let mut edges = vec2(
+        if delta_left >= SMAA_THRESHOLD { 1.0 } else { 0.0 },
+        if delta_top >= SMAA_THRESHOLD { 1.0 } else { 0.0 },
+    );
+    if edges.x + edges.y == 0.0 {
+        spirv_std::arch::kill();
+    }
+
+    // Local contrast adaptation: drop edges that are much weaker than a neighbouring one
+    let delta_right = (l - luma_at(1.0, 0.0)).abs();
+    let delta_bottom = (l - luma_at(0.0, 1.0)).abs();
+    let delta_left_left = (l_left - luma_at(-2.0, 0.0)).abs();
+    let delta_top_top = (l_top - luma_at(0.0, -2.0)).abs();
+    let max_delta = delta_left
+        .max(delta_top)
+        .max(delta_right)
+        .max(delta_bottom)
+        .max(delta_left_left)
+        .max(delta_top_top);
+    if max_delta > SMAA_LOCAL_CONTRAST_ADAPTATION_FACTOR * delta_left {
+        edges.x = 0.0;
+    }
+    if max_delta > SMAA_LOCAL_CONTRAST_ADAPTATION_FACTOR * delta_top {
+        edges.y = 0.0;
+    }
+
+    *out_edges = edges;
+}
+
+// Length of the remaining edge from the search texture, given the bilinearly fetched edge pair
+fn smaa_search_length(search_tex: &Texture2D, e: Vec2, offset: f32) -> f32 {
+    let scale = (SMAA_SEARCHTEX_SIZE * vec2(0.5, -1.0) + vec2(-1.0, 1.0)) / SMAA_SEARCHTEX_PACKED_SIZE;
+    let bias = (SMAA_SEARCHTEX_SIZE * vec2(offset, 1.0) + vec2(0.5, -0.5)) / SMAA_SEARCHTEX_PACKED_SIZE;
+    sample(search_tex, scale * e + bias).x
+}
+
+fn smaa_search_x_left(edges_tex: &Texture2D, search_tex: &Texture2D, mut uv: Vec2, end: f32, rcp: Vec2) -> f32 {
+    let mut e = vec2(0.0, 1.0);
+    while uv.x > end && e.y > 0.8281 && e.x == 0.0 {
+        e = sample(edges_tex, uv).xy();
+        uv.x -= 2.0 * rcp.x;
+    }
+    let offset = -(255.0 / 127.0) * smaa_search_length(search_tex, e, 0.0) + 3.25;
+    rcp.x * offset + uv.x
+}
+
+fn smaa_search_x_right(edges_tex: &Texture2D, search_tex: &Texture2D, mut uv: Vec2, end: f32, rcp: Vec2) -> f32 {
+    let mut e = vec2(0.0, 1.0);
+    while uv.x < end && e.y > 0.8281 && e.x == 0.0 {
+        e = sample(edges_tex, uv).xy();
+        uv.x += 2.0 * rcp.x;
+    }
+    let offset = -(255.0 / 127.0) * smaa_search_length(search_tex, e, 0.5) + 3.25;
+    -rcp.x * offset + uv.x
+}
+
+fn smaa_search_y_up(edges_tex: &Texture2D, search_tex: &Texture2D, mut uv: Vec2, end: f32, rcp: Vec2) -> f32 {
+    let mut e = vec2(1.0, 0.0);
+    while uv.y > end && e.x > 0.8281 && e.y == 0.0 {
+        e = sample(edges_tex, uv).xy();
+        uv.y -= 2.0 * rcp.y;
+    }
+    let offset = -(255.0 / 127.0) * smaa_search_length(search_tex, vec2(e.y, e.x), 0.0) + 3.25;
+    rcp.y * offset + uv.y
+}
+
+fn smaa_search_y_down(edges_tex: &Texture2D, search_tex: &Texture2D, mut uv: Vec2, end: f32, rcp: Vec2) -> f32 {
+    let mut e = vec2(1.0, 0.0);
+    while uv.y < end && e.x > 0.8281 && e.y == 0.0 {
+        e = sample(edges_tex, uv).xy();
+        uv.y += 2.0 * rcp.y;
+    }
+    let offset = -(255.0 / 127.0) * smaa_search_length(search_tex, vec2(e.y, e.x), 0.5) + 3.25;
+    -rcp.y * offset + uv.y
+}
+
+// Coverage areas for the given edge distances and crossing edges
+fn smaa_area(area_tex: &Texture2D, dist: Vec2, e1: f32, e2: f32) -> Vec2 {
+    let crossing = vec2((4.0 * e1).round(), (4.0 * e2).round());
+    let uv = SMAA_AREATEX_MAX_DISTANCE * crossing + dist;
+    // SMAA 1x always reads the first subtexture, so there is no subsample offset
+    let uv = SMAA_AREATEX_PIXEL_SIZE * uv + 0.5 * SMAA_AREATEX_PIXEL_SIZE;
+    sample(area_tex, uv).xy()
+}
+
+#[spirv(fragment)]
+pub fn smaa_weight_fs(
+    in_uv: Vec2,
+    #[spirv(descriptor_set = 0, binding = 0)] sampler_edges: &Texture2D,
+    #[spirv(uniform, descriptor_set = 0, binding = 1)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_area: &Texture2D,
+    #[spirv(descriptor_set = 0, binding = 3)] sampler_search: &Texture2D,
+    out_weights: &mut Vec4,
+) {
+    let rcp = ubo.rt_metrics.xy();
+    let size = ubo.rt_metrics.zw();
+    let pixcoord = in_uv * size;
+
+    // Search start points and limits
+    let offset0 = vec4(-0.25, -0.125, 1.25, -0.125) * rcp.xyxy() + in_uv.xyxy();
+    let offset1 = vec4(-0.125, -0.25, -0.125, 1.25) * rcp.xyxy() + in_uv.xyxy();
+    let offset2 = vec4(-2.0, 2.0, -2.0, 2.0) * SMAA_MAX_SEARCH_STEPS * vec4(rcp.x, rcp.x, rcp.y, rcp.y)
+        + vec4(offset0.x, offset0.z, offset1.y, offset1.w);
+
+    let mut weights = Vec4::ZERO;
+    let e = sample(sampler_edges, in_uv).xy();
+
+    // Edge at north
+    if e.y > 0.0 {
+        let left = smaa_search_x_left(sampler_edges, sampler_search, offset0.xy(), offset2.x, rcp);
+        let right = smaa_search_x_right(sampler_edges, sampler_search, offset0.zw(), offset2.y, rcp);
+        let d = vec2(
+            (size.x * left - pixcoord.x).round().abs(),
+            (size.x * right - pixcoord.x).round().abs(),
+        );
+        // Crossing edges at both ends
+        let e1 = sample(sampler_edges, vec2(left, offset1.y)).x;
+        let e2 = sample(sampler_edges, vec2(right + rcp.x, offset1.y)).x;
+        let area = smaa_area(sampler_area, vec2(d.x.sqrt(), d.y.sqrt()), e1, e2);
+        weights.x = area.x;
+        weights.y = area.y;
+    }
+
+    // Edge at west
+    if e.x > 0.0 {
+        let up = smaa_search_y_up(sampler_edges, sampler_search, offset1.xy(), offset2.z, rcp);
+        let down = smaa_search_y_down(sampler_edges, sampler_search, offset1.zw(), offset2.w, rcp);
+        let d = vec2(
+            (size.y * up - pixcoord.y).round().abs(),
+            (size.y * down - pixcoord.y).round().abs(),
+        );
+        let e1 = sample(sampler_edges, vec2(offset0.x, up)).y;
+        let e2 = sample(sampler_edges, vec2(offset0.x, down + rcp.y)).y;
+        let area = smaa_area(sampler_area, vec2(d.x.sqrt(), d.y.sqrt()), e1, e2);
+        weights.z = area.x;
+        weights.w = area.y;
+    }
+
+    *out_weights = weights;
+}
+
+#[spirv(fragment)]
+pub fn smaa_blend_fs(
+    in_uv: Vec2,
+    #[spirv(descriptor_set = 0, binding = 0)] sampler_color: &Texture2D,
+    #[spirv(uniform, descriptor_set = 0, binding = 1)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_blend: &Texture2D,
+    out_frag_color: &mut Vec4,
+) {
+    let rcp = ubo.rt_metrics.xy();
+
+    // Weights of the right, bottom, left and top edges of this pixel
+    let right = sample(sampler_blend, in_uv + vec2(rcp.x, 0.0)).w;
+    let bottom = sample(sampler_blend, in_uv + vec2(0.0, rcp.y)).y;
+    let own = sample(sampler_blend, in_uv);
+    let left = own.z;
+    let top = own.x;
+
+    if right + bottom + left + top < 1e-5 {
+        *out_frag_color = sample(sampler_color, in_uv);
+        return;
+    }
+
+    // Blend along the dominant direction only
+    let horizontal = right.max(left) > bottom.max(top);
+    let (offset_a, offset_b, mut weight) = if horizontal {
+        (vec2(right * rcp.x, 0.0), vec2(-left * rcp.x, 0.0), vec2(right, left))
+    } else {
+        (vec2(0.0, bottom * rcp.y), vec2(0.0, -top * rcp.y), vec2(bottom, top))
+    };
+    weight /= weight.x + weight.y;
+
+    *out_frag_color = weight.x * sample(sampler_color, in_uv + offset_a) + weight.y * sample(sampler_color, in_uv + offset_b);
+}
@@ -39,12 +39,14 @@ pub fn main_fs(
     out_frag_color: &mut Vec4,
 ) {
     let depth = texture_color.sample(*sampler_color, in_uv).x;
-    let linearized_depth = linearize_depth(depth, ubo.z_near, ubo.z_far);
+    let linearized_depth = gl_depth_over_far(depth, ubo.z_near, ubo.z_far);
     let color_value = 1.0 - linearized_depth;
     *out_frag_color = vec4(color_value, color_value, color_value, 1.0);
 }
 
-fn linearize_depth(depth: f32, z_near: f32, z_far: f32) -> f32 {
+// View distance divided by z_far for an OpenGL style [-1, 1] depth. It only shades the debug view,
+// so the [0, 1] shadow map depth is passed as is.
+fn gl_depth_over_far(depth: f32, z_near: f32, z_far: f32) -> f32 {
     let z = depth;
     (2.0 * z_near) / (z_far + z_near - z * (z_far - z_near))
 }
\ No newline at end of file
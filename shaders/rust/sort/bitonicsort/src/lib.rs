@@ -0,0 +1,106 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Bitonic sort of small power-of-two arrays that fit into workgroup memory.
+// A single workgroup of SORT_SIZE / 2 invocations sorts the whole array, shorter
+// inputs are padded with u32::MAX keys and values so they end up at the back,
+// behind real elements with u32::MAX keys.
+
+use spirv_std::glam::UVec3;
+use spirv_std::spirv;
+use common::sort::bitonic_sort;
+#[cfg(target_arch = "spirv")]
+use spirv_std::arch::workgroup_memory_barrier_with_group_sync;
+#[cfg(not(target_arch = "spirv"))]
+use common::sim::workgroup_memory_barrier_with_group_sync;
+
+const SORT_SIZE: usize = 1024;
+const HALF_SIZE: u32 = SORT_SIZE as u32 / 2;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PushConsts {
+    pub count: u32,
+}
+
+#[spirv(compute(threads(512)))]
+pub fn main_cs(
+    #[spirv(local_invocation_id)] local_id: UVec3,
+    #[spirv(workgroup)] shared_keys: &mut [u32; SORT_SIZE],
+    #[spirv(workgroup)] shared_values: &mut [u32; SORT_SIZE],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] keys: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] values: &mut [u32],
+    #[spirv(push_constant)] consts: &PushConsts,
+) {
+    let t = local_id.x;
+
+    // Each invocation loads and stores two elements
+    for half in 0..2 {
+        let i = t + half * HALF_SIZE;
+        let in_range = i < consts.count;
+        shared_keys[i as usize] = if in_range { keys[i as usize] } else { u32::MAX };
+        shared_values[i as usize] = if in_range { values[i as usize] } else { u32::MAX };
+    }
+    unsafe {
+        workgroup_memory_barrier_with_group_sync();
+    }
+
+    bitonic_sort(t, shared_keys, shared_values);
+
+    for half in 0..2 {
+        let i = t + half * HALF_SIZE;
+        if i < consts.count {
+            keys[i as usize] = shared_keys[i as usize];
+            values[i as usize] = shared_values[i as usize];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::sim::{dispatch, random_values, Shared};
+
+    fn sort(input: &[u32]) -> (Vec<u32>, Vec<u32>) {
+        let mut keys = input.to_vec();
+        let mut values: Vec<u32> = (0..input.len() as u32).collect();
+        let shared_keys = Shared::new([0; SORT_SIZE]);
+        let shared_values = Shared::new([0; SORT_SIZE]);
+        let buffers = Shared::new((keys.as_mut_slice(), values.as_mut_slice()));
+        let consts = PushConsts { count: input.len() as u32 };
+        dispatch(HALF_SIZE, 32, |invocation| unsafe {
+            let (keys, values) = buffers.get();
+            main_cs(
+                UVec3::new(invocation.local_index, 0, 0),
+                shared_keys.get(),
+                shared_values.get(),
+                keys,
+                values,
+                &consts,
+            )
+        });
+        (keys, values)
+    }
+
+    fn check(input: &[u32]) {
+        let (keys, values) = sort(input);
+        // Equal keys are ordered by their payload, the element index here
+        let mut expected: Vec<(u32, u32)> = input.iter().copied().zip(0..).collect();
+        expected.sort();
+        assert_eq!(keys, expected.iter().map(|&(key, _)| key).collect::<Vec<_>>());
+        assert_eq!(values, expected.iter().map(|&(_, value)| value).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn sorts_random_keys() {
+        check(&random_values(SORT_SIZE, u32::MAX, 1));
+        check(&random_values(1000, u32::MAX, 2));
+        check(&random_values(1, u32::MAX, 3));
+    }
+
+    #[test]
+    fn sorts_duplicate_keys() {
+        check(&random_values(SORT_SIZE, 4, 4));
+        check(&random_values(777, 2, 5));
+        check(&[u32::MAX; 100]);
+    }
+}
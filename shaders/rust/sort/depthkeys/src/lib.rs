@@ -0,0 +1,42 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Generates sort keys for back-to-front particle drawing. The keys are the
+// inverted camera distances, so sorting them in ascending order with the
+// radix or bitonic sort puts the farthest particle first. The payload is the
+// particle index, which the draw can use to fetch particles in sorted order.
+
+use spirv_std::glam::{vec3, UVec3, Vec4, Vec4Swizzles};
+use spirv_std::spirv;
+use common::sort::sortable_f32;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct UBO {
+    pub camera_pos: Vec4,
+    pub particle_count: u32,
+    // Distance between two particles in the particle buffer, in floats
+    pub particle_stride: u32,
+    // Offset of the position inside a particle, in floats
+    pub position_offset: u32,
+}
+
+#[spirv(compute(threads(256)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] particles: &[f32],
+    #[spirv(uniform, descriptor_set = 0, binding = 1)] ubo: &UBO,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] keys: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] values: &mut [u32],
+) {
+    let index = global_id.x;
+    if index >= ubo.particle_count {
+        return;
+    }
+
+    let base = (index * ubo.particle_stride + ubo.position_offset) as usize;
+    let pos = vec3(particles[base], particles[base + 1], particles[base + 2]);
+    let dist = pos.distance(ubo.camera_pos.xyz());
+
+    keys[index as usize] = !sortable_f32(dist);
+    values[index as usize] = index;
+}
@@ -0,0 +1,183 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// 4-bit LSD radix sort of u32 keys with u32 payloads. Each of the eight digit
+// passes runs three dispatches selected by the `pass` spec constant:
+//   0: count  - one workgroup per tile builds the tile's digit histogram
+//   1: scan   - a single workgroup turns all histograms into global offsets
+//   2: scatter - every tile writes its elements to their sorted position
+// Keys and values ping-pong between the in and out buffers between digit passes.
+
+use spirv_std::glam::UVec3;
+use spirv_std::spirv;
+use common::scan::{workgroup_exclusive_add, SCAN_SCRATCH_SIZE};
+use common::sort::{radix_digit, workgroup_digit_rank, RADIX_BINS};
+
+const WORKGROUP_SIZE: u32 = 256;
+
+const PASS_COUNT: u32 = 0;
+const PASS_SCAN: u32 = 1;
+const PASS_SCATTER: u32 = 2;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PushConsts {
+    pub shift: u32,
+    pub count: u32,
+}
+
+#[spirv(compute(threads(256)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(workgroup_id)] workgroup_id: UVec3,
+    #[spirv(local_invocation_index)] local_index: u32,
+    #[spirv(subgroup_id)] subgroup_id: u32,
+    #[spirv(subgroup_local_invocation_id)] subgroup_lane: u32,
+    #[spirv(num_subgroups)] num_subgroups: u32,
+    #[spirv(workgroup)] scan_scratch: &mut [u32; SCAN_SCRATCH_SIZE],
+    #[spirv(workgroup)] digit_counts: &mut [u32; RADIX_BINS],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] keys_in: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] values_in: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 2)] keys_out: &mut [u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 3)] values_out: &mut [u32],
+    // Bin-major: histograms[digit * tile_count + tile]
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 4)] histograms: &mut [u32],
+    #[spirv(push_constant)] consts: &PushConsts,
+    #[spirv(spec_constant(id = 0, default = 0))] pass: u32,
+) {
+    let tile_count = consts.count.div_ceil(WORKGROUP_SIZE);
+
+    if pass == PASS_SCAN {
+        // Dispatched with a single workgroup, walks the histograms in chunks carrying the running sum
+        let len = tile_count * RADIX_BINS as u32;
+        let mut carry = 0;
+        let mut base = 0;
+        while base < len {
+            let i = base + local_index;
+            let count = if i < len { histograms[i as usize] } else { 0 };
+            let (prefix, total) = workgroup_exclusive_add(count, subgroup_id, subgroup_lane, num_subgroups, scan_scratch);
+            if i < len {
+                histograms[i as usize] = carry + prefix;
+            }
+            carry += total;
+            base += WORKGROUP_SIZE;
+        }
+        return;
+    }
+
+    let index = global_id.x;
+    let tile = workgroup_id.x;
+    let valid = index < consts.count;
+    let key = if valid { keys_in[index as usize] } else { 0 };
+    let digit = radix_digit(key, consts.shift);
+
+    let rank = workgroup_digit_rank(
+        digit,
+        valid,
+        local_index,
+        subgroup_id,
+        subgroup_lane,
+        num_subgroups,
+        scan_scratch,
+        digit_counts,
+    );
+
+    if pass == PASS_COUNT {
+        if local_index < RADIX_BINS as u32 {
+            histograms[(local_index * tile_count + tile) as usize] = digit_counts[local_index as usize];
+        }
+    } else if pass == PASS_SCATTER && valid {
+        // Ranks within a tile follow the input order, which keeps every digit pass stable
+        let dst = histograms[(digit * tile_count + tile) as usize] + rank;
+        keys_out[dst as usize] = key;
+        values_out[dst as usize] = values_in[index as usize];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::sort::RADIX_BITS;
+    use common::sim::{dispatch, random_values, Shared};
+
+    // Buffers of one digit pass
+    struct Buffers {
+        keys_in: Vec<u32>,
+        values_in: Vec<u32>,
+        keys_out: Vec<u32>,
+        values_out: Vec<u32>,
+        histograms: Vec<u32>,
+    }
+
+    fn run_pass(buffers: &mut Buffers, consts: &PushConsts, pass: u32, workgroups: u32) {
+        let shared = Shared::new(&mut *buffers);
+        for workgroup in 0..workgroups {
+            let scan_scratch = Shared::new([0; SCAN_SCRATCH_SIZE]);
+            let digit_counts = Shared::new([0; RADIX_BINS]);
+            dispatch(WORKGROUP_SIZE, 32, |invocation| unsafe {
+                let buffers = shared.get();
+                main_cs(
+                    UVec3::new(workgroup * WORKGROUP_SIZE + invocation.local_index, 0, 0),
+                    UVec3::new(workgroup, 0, 0),
+                    invocation.local_index,
+                    invocation.subgroup_id,
+                    invocation.subgroup_lane,
+                    invocation.num_subgroups,
+                    scan_scratch.get(),
+                    digit_counts.get(),
+                    &buffers.keys_in,
+                    &buffers.values_in,
+                    &mut buffers.keys_out,
+                    &mut buffers.values_out,
+                    &mut buffers.histograms,
+                    consts,
+                    pass,
+                )
+            });
+        }
+    }
+
+    // Runs the digit passes the way the host records them
+    fn sort(input: &[u32]) -> (Vec<u32>, Vec<u32>) {
+        let count = input.len() as u32;
+        let tile_count = count.div_ceil(WORKGROUP_SIZE);
+        let mut buffers = Buffers {
+            keys_in: input.to_vec(),
+            values_in: (0..count).collect(),
+            keys_out: vec![0; input.len()],
+            values_out: vec![0; input.len()],
+            histograms: vec![0; (tile_count as usize * RADIX_BINS).max(1)],
+        };
+        for shift in (0..32).step_by(RADIX_BITS as usize) {
+            let consts = PushConsts { shift, count };
+            run_pass(&mut buffers, &consts, PASS_COUNT, tile_count);
+            run_pass(&mut buffers, &consts, PASS_SCAN, 1);
+            run_pass(&mut buffers, &consts, PASS_SCATTER, tile_count);
+            std::mem::swap(&mut buffers.keys_in, &mut buffers.keys_out);
+            std::mem::swap(&mut buffers.values_in, &mut buffers.values_out);
+        }
+        (buffers.keys_in, buffers.values_in)
+    }
+
+    fn check(input: &[u32]) {
+        let (keys, values) = sort(input);
+        // The sort is stable, so sorting (key, index) pairs gives the expected payload order
+        let mut expected: Vec<(u32, u32)> = input.iter().copied().zip(0..).collect();
+        expected.sort();
+        assert_eq!(keys, expected.iter().map(|&(key, _)| key).collect::<Vec<_>>());
+        assert_eq!(values, expected.iter().map(|&(_, value)| value).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn sorts_random_keys() {
+        // A partial last tile and a single element
+        check(&random_values(600, u32::MAX, 1));
+        check(&random_values(1, u32::MAX, 2));
+    }
+
+    #[test]
+    fn sorts_duplicate_keys() {
+        check(&random_values(600, 4, 3));
+        check(&random_values(300, 2, 4).iter().map(|&bit| bit << 31).collect::<Vec<_>>());
+        check(&[7; 300]);
+    }
+}
@@ -1,8 +1,9 @@
 #![no_std]
 
-use spirv_std::glam::{vec3, vec4, Mat4, Vec2, Vec3, Vec4, Vec4Swizzles};
+use spirv_std::glam::{vec3, vec4, Mat4, UVec4, Vec2, Vec3, Vec4, Vec4Swizzles};
 use spirv_std::spirv;
 use spirv_std::{Image, Sampler};
+use common::packing::{position_from_depth, unpack_gbuffer};
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -13,6 +14,16 @@ pub struct UBO {
     pub ssao_blur: i32,
 }
 
+// Uniforms of composition_compact_fs, the inverse projection reconstructs view space positions
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct CompactUBO {
+    pub inverse_projection: Mat4,
+    pub ssao: i32,
+    pub ssao_only: i32,
+    pub ssao_blur: i32,
+}
+
 #[spirv(fragment)]
 pub fn main_fs(
     in_uv: Vec2,
@@ -39,24 +50,59 @@ pub fn main_fs(
         texture_ssao.sample(*sampler_ssao, in_uv).x
     };
     
+    *out_frag_color = shade(frag_pos, normal, albedo, ssao, ubo_params.ssao, ubo_params.ssao_only);
+}
+
+// Composition of the compact G-Buffer of gbuffer_compact_fs, compiled to
+// composition_compact.frag.spv. Layout:
+//   binding 0: depth attachment of the G-Buffer pass
+//   binding 1: packed rgba32ui G-Buffer, see common::packing::pack_gbuffer
+//   binding 3-4: ssao and blurred ssao as for main_fs
+//   binding 5: CompactUBO
+#[spirv(fragment)]
+pub fn composition_compact_fs(
+    in_uv: Vec2,
+    #[spirv(frag_coord)] frag_coord: Vec4,
+    #[spirv(descriptor_set = 0, binding = 0)] sampler_depth: &Sampler,
+    #[spirv(descriptor_set = 0, binding = 0)] texture_depth: &Image!(2D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 1)] gbuffer: &Image!(2D, type=u32, sampled),
+    #[spirv(descriptor_set = 0, binding = 3)] sampler_ssao: &Sampler,
+    #[spirv(descriptor_set = 0, binding = 3)] texture_ssao: &Image!(2D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 4)] sampler_ssao_blur: &Sampler,
+    #[spirv(descriptor_set = 0, binding = 4)] texture_ssao_blur: &Image!(2D, type=f32, sampled),
+    #[spirv(uniform, descriptor_set = 0, binding = 5)] ubo_params: &CompactUBO,
+    out_frag_color: &mut Vec4,
+) {
+    let depth = texture_depth.sample(*sampler_depth, in_uv).x;
+    let frag_pos = position_from_depth(in_uv, depth, ubo_params.inverse_projection);
+    let texel: UVec4 = gbuffer.fetch(frag_coord.xy().as_ivec2());
+    let (normal, albedo, _) = unpack_gbuffer(texel);
+
+    let ssao = if ubo_params.ssao_blur == 1 {
+        texture_ssao_blur.sample(*sampler_ssao_blur, in_uv).x
+    } else {
+        texture_ssao.sample(*sampler_ssao, in_uv).x
+    };
+
+    *out_frag_color = shade(frag_pos, normal, albedo, ssao, ubo_params.ssao, ubo_params.ssao_only);
+}
+
+// Lighting from a light at the camera, modulated by the occlusion
+fn shade(frag_pos: Vec3, normal: Vec3, albedo: Vec4, ssao: f32, use_ssao: i32, ssao_only: i32) -> Vec4 {
     let light_pos = Vec3::ZERO;
     let l = (light_pos - frag_pos).normalize();
     let n_dot_l = normal.dot(l).max(0.5);
     
-    if ubo_params.ssao_only == 1 {
-        *out_frag_color = vec4(ssao, ssao, ssao, 1.0);
+    if ssao_only == 1 {
+        vec4(ssao, ssao, ssao, 1.0)
     } else {
         let base_color = albedo.xyz() * n_dot_l;
         
-        if ubo_params.ssao == 1 {
-            let mut color = vec3(ssao, ssao, ssao);
-            
-            if ubo_params.ssao_only != 1 {
-                color *= base_color;
-            }
-            *out_frag_color = vec4(color.x, color.y, color.z, 1.0);
+        if use_ssao == 1 {
+            let color = vec3(ssao, ssao, ssao) * base_color;
+            vec4(color.x, color.y, color.z, 1.0)
         } else {
-            *out_frag_color = vec4(base_color.x, base_color.y, base_color.z, 1.0);
+            vec4(base_color.x, base_color.y, base_color.z, 1.0)
         }
     }
-}
\ No newline at end of file
+}
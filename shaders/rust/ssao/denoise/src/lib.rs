@@ -0,0 +1,74 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Edge aware spatial filter for the GTAO output. Neighbours only contribute if they
+// are at a similar view depth and face a similar direction, so occlusion doesn't
+// bleed across silhouettes and creases like it does with the plain box blur.
+
+use spirv_std::glam::{ivec2, vec4, IVec2, Mat4, UVec3, Vec2, Vec4, Vec4Swizzles};
+use spirv_std::{num_traits::Float, spirv, Image};
+use common::packing::position_from_depth;
+
+const FILTER_RADIUS: i32 = 2;
+// Relative view depth difference at which a neighbour's weight has dropped to 1/e
+const DEPTH_SIGMA: f32 = 0.05;
+const NORMAL_POWER: f32 = 32.0;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct UBO {
+    pub inverse_projection: Mat4,
+    pub screen_size: Vec2,
+}
+
+fn view_depth(depth_image: &Image!(2D, type=f32, sampled), coord: IVec2, ubo: &UBO) -> f32 {
+    let uv = (coord.as_vec2() + 0.5) / ubo.screen_size;
+    let depth: Vec4 = depth_image.fetch(coord);
+    -position_from_depth(uv, depth.x, ubo.inverse_projection).z
+}
+
+#[spirv(compute(threads(8, 8)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(descriptor_set = 0, binding = 0)] ao_image: &Image!(2D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 1)] depth_image: &Image!(2D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 2)] normal_image: &Image!(2D, type=f32, sampled),
+    #[spirv(uniform, descriptor_set = 0, binding = 3)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 4)] result_image: &Image!(2D, format=r32f, sampled=false),
+) {
+    let size = ubo.screen_size.as_uvec2();
+    if global_id.x >= size.x || global_id.y >= size.y {
+        return;
+    }
+    let coord = ivec2(global_id.x as i32, global_id.y as i32);
+    let max_coord = ivec2(size.x as i32 - 1, size.y as i32 - 1);
+
+    let center_depth = view_depth(depth_image, coord, ubo);
+    let center_normal: Vec4 = normal_image.fetch(coord);
+    let center_normal = (center_normal.xyz() * 2.0 - 1.0).normalize();
+
+    let mut result = 0.0;
+    let mut total_weight = 0.0;
+    for x in -FILTER_RADIUS..=FILTER_RADIUS {
+        for y in -FILTER_RADIUS..=FILTER_RADIUS {
+            let sample_coord = (coord + ivec2(x, y)).clamp(IVec2::ZERO, max_coord);
+
+            let depth = view_depth(depth_image, sample_coord, ubo);
+            let normal: Vec4 = normal_image.fetch(sample_coord);
+            let normal = (normal.xyz() * 2.0 - 1.0).normalize();
+            let ao: Vec4 = ao_image.fetch(sample_coord);
+
+            let spatial_weight = (-((x * x + y * y) as f32) / (2.0 * FILTER_RADIUS as f32 * FILTER_RADIUS as f32)).exp();
+            let depth_weight = (-(depth - center_depth).abs() / (DEPTH_SIGMA * center_depth)).exp();
+            let normal_weight = normal.dot(center_normal).max(0.0).powf(NORMAL_POWER);
+
+            let weight = spatial_weight * depth_weight * normal_weight;
+            result += ao.x * weight;
+            total_weight += weight;
+        }
+    }
+
+    // The center sample always has full weight, so total_weight can't be zero
+    unsafe {
+        result_image.write(coord, vec4(result / total_weight, 0.0, 0.0, 0.0));
+    }
+}
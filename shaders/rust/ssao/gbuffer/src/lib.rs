@@ -1,8 +1,10 @@
 #![no_std]
 
-use spirv_std::glam::{vec3, vec4, Mat3, Mat4, Vec2, Vec3, Vec4, Vec4Swizzles};
+use spirv_std::glam::{vec3, vec4, Mat3, Mat4, UVec4, Vec2, Vec3, Vec4, Vec4Swizzles};
 use spirv_std::spirv;
 use spirv_std::{Image, Sampler};
+use common::packing::pack_gbuffer;
+use common::temporal::velocity;
 
 #[repr(C)]
 #[derive(Copy, Clone)]
@@ -14,6 +16,14 @@ pub struct UBO {
     pub far_plane: f32,
 }
 
+// Unjittered transforms of the current and previous frame for the velocity target
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct MotionUBO {
+    pub mvp: Mat4,
+    pub prev_mvp: Mat4,
+}
+
 #[spirv(vertex)]
 pub fn main_vs(
     in_pos: Vec4,
@@ -61,6 +71,86 @@ pub fn main_fs(
     *out_albedo = texture_colormap.sample(*sampler_colormap, in_uv) * vec4(in_color.x, in_color.y, in_color.z, 1.0);
 }
 
+// Vertex stage of gbuffer_motion_fs, compiled to gbuffer_motion.vert.spv. The layout is the one
+// of main_vs plus the MotionUBO in binding 1, the clip space positions of both frames are passed
+// on for the velocity target.
+#[spirv(vertex)]
+pub fn gbuffer_motion_vs(
+    in_pos: Vec4,
+    in_uv: Vec2,
+    in_color: Vec3,
+    in_normal: Vec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
+    #[spirv(uniform, descriptor_set = 0, binding = 1)] motion: &MotionUBO,
+    #[spirv(position)] out_position: &mut Vec4,
+    out_normal: &mut Vec3,
+    out_uv: &mut Vec2,
+    out_color: &mut Vec3,
+    out_pos: &mut Vec3,
+    out_clip_pos: &mut Vec4,
+    out_prev_clip_pos: &mut Vec4,
+) {
+    *out_position = ubo.projection * ubo.view * ubo.model * in_pos;
+    *out_uv = in_uv;
+    
+    // Vertex position in view space
+    *out_pos = (ubo.view * ubo.model * in_pos).truncate();
+    
+    // Normal in view space
+    let normal_matrix = Mat3::from_mat4(ubo.view * ubo.model);
+    *out_normal = normal_matrix * in_normal;
+    
+    *out_color = in_color;
+    
+    *out_clip_pos = motion.mvp * in_pos;
+    *out_prev_clip_pos = motion.prev_mvp * in_pos;
+}
+
+// G-Buffer of main_fs plus a velocity target for taa/resolve, compiled to
+// gbuffer_motion.frag.spv. Bindings are the ones of main_fs.
+#[spirv(fragment)]
+pub fn gbuffer_motion_fs(
+    in_normal: Vec3,
+    in_uv: Vec2,
+    in_color: Vec3,
+    in_pos: Vec3,
+    in_clip_pos: Vec4,
+    in_prev_clip_pos: Vec4,
+    #[spirv(frag_coord)] frag_coord: Vec4,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] ubo: &UBO,
+    #[spirv(descriptor_set = 1, binding = 0)] sampler_colormap: &Sampler,
+    #[spirv(descriptor_set = 1, binding = 0)] texture_colormap: &Image!(2D, type=f32, sampled),
+    out_position: &mut Vec4,
+    out_normal: &mut Vec4,
+    out_albedo: &mut Vec4,
+    out_velocity: &mut Vec2,
+) {
+    let depth = linear_depth(frag_coord.z, ubo.near_plane, ubo.far_plane);
+    *out_position = vec4(in_pos.x, in_pos.y, in_pos.z, depth);
+    let normalized_normal = in_normal.normalize() * 0.5 + 0.5;
+    *out_normal = vec4(normalized_normal.x, normalized_normal.y, normalized_normal.z, 1.0);
+    *out_albedo = texture_colormap.sample(*sampler_colormap, in_uv) * vec4(in_color.x, in_color.y, in_color.z, 1.0);
+    *out_velocity = velocity(in_clip_pos, in_prev_clip_pos);
+}
+
+// Compact G-Buffer for ssao_compact_fs and composition_compact_fs, compiled to
+// gbuffer_compact.frag.spv. The vertex stage and bindings are the ones of main_fs, but there is
+// no position target (view space positions are reconstructed from the depth attachment) and the
+// view space normal and albedo are packed into a single rgba32ui target, see
+// common::packing::pack_gbuffer. The material channels are unused.
+#[spirv(fragment)]
+pub fn gbuffer_compact_fs(
+    in_normal: Vec3,
+    in_uv: Vec2,
+    in_color: Vec3,
+    #[spirv(descriptor_set = 1, binding = 0)] sampler_colormap: &Sampler,
+    #[spirv(descriptor_set = 1, binding = 0)] texture_colormap: &Image!(2D, type=f32, sampled),
+    out_gbuffer: &mut UVec4,
+) {
+    let albedo = texture_colormap.sample(*sampler_colormap, in_uv) * vec4(in_color.x, in_color.y, in_color.z, 1.0);
+    *out_gbuffer = pack_gbuffer(in_normal.normalize(), albedo, Vec4::ZERO);
+}
+
 fn linear_depth(depth: f32, near_plane: f32, far_plane: f32) -> f32 {
     let z = depth * 2.0 - 1.0;
     (2.0 * near_plane * far_plane) / (far_plane + near_plane - z * (far_plane - near_plane))
@@ -0,0 +1,133 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Ground truth ambient occlusion (Jimenez et al. 2016). For each of the screen space
+// slices through the pixel the two horizons are searched in the depth buffer and the
+// cosine weighted visibility between them is integrated analytically.
+
+use core::f32::consts::{FRAC_PI_2, PI};
+use spirv_std::glam::{ivec2, vec2, vec4, IVec2, Mat4, UVec3, Vec2, Vec3, Vec4, Vec4Swizzles};
+use spirv_std::{num_traits::Float, spirv, Image};
+use common::packing::position_from_depth;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct UBO {
+    pub projection: Mat4,
+    pub inverse_projection: Mat4,
+    pub screen_size: Vec2,
+    // View space radius of the horizon search
+    pub radius: f32,
+    pub slice_count: u32,
+    pub step_count: u32,
+}
+
+// Interleaved gradient noise (Jimenez 2014), decorrelates slice directions and step offsets between neighbouring pixels
+fn interleaved_gradient_noise(p: Vec2) -> f32 {
+    (52.982_918 * (0.067_110_56 * p.x + 0.005_837_15 * p.y).fract()).fract()
+}
+
+fn view_pos(depth_image: &Image!(2D, type=f32, sampled), coord: IVec2, ubo: &UBO) -> Vec3 {
+    let uv = (coord.as_vec2() + 0.5) / ubo.screen_size;
+    let depth: Vec4 = depth_image.fetch(coord);
+    position_from_depth(uv, depth.x, ubo.inverse_projection)
+}
+
+// Cosine weighted visibility of the arc between the normal and horizon angle h
+fn integrate_arc(h: f32, n: f32, cos_n: f32) -> f32 {
+    (cos_n + 2.0 * h * n.sin() - (2.0 * h - n).cos()) * 0.25
+}
+
+#[spirv(compute(threads(8, 8)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(descriptor_set = 0, binding = 0)] depth_image: &Image!(2D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 1)] normal_image: &Image!(2D, type=f32, sampled),
+    #[spirv(uniform, descriptor_set = 0, binding = 2)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 3)] ao_image: &Image!(2D, format=r32f, sampled=false),
+) {
+    let size = ubo.screen_size.as_uvec2();
+    if global_id.x >= size.x || global_id.y >= size.y {
+        return;
+    }
+    let coord = ivec2(global_id.x as i32, global_id.y as i32);
+
+    let uv = (coord.as_vec2() + 0.5) / ubo.screen_size;
+    let depth: Vec4 = depth_image.fetch(coord);
+    let p = position_from_depth(uv, depth.x, ubo.inverse_projection);
+    let normal: Vec4 = normal_image.fetch(coord);
+    let n = (normal.xyz() * 2.0 - 1.0).normalize();
+    let v = (-p).normalize();
+
+    // Project the search radius to pixels, nothing to do if it covers less than one
+    let radius_pixels = ubo.radius * ubo.projection.y_axis.y * 0.5 * ubo.screen_size.y / -p.z;
+    if radius_pixels < 1.0 {
+        unsafe {
+            ao_image.write(coord, vec4(1.0, 0.0, 0.0, 0.0));
+        }
+        return;
+    }
+    let step_pixels = radius_pixels / ubo.step_count as f32;
+
+    // Samples are faded out towards the end of the radius instead of cut off
+    let falloff_range = 0.615 * ubo.radius;
+    let falloff_mul = -1.0 / falloff_range;
+    let falloff_add = (ubo.radius - falloff_range) / falloff_range + 1.0;
+
+    let noise_slice = interleaved_gradient_noise(coord.as_vec2());
+    let noise_step = interleaved_gradient_noise(coord.as_vec2() + vec2(5.588_238, 5.588_238));
+
+    let mut visibility = 0.0;
+    for slice in 0..ubo.slice_count {
+        let phi = (slice as f32 + noise_slice) * PI / ubo.slice_count as f32;
+        let omega = vec2(phi.cos(), phi.sin());
+
+        // View space direction of the slice, taken from a point at the same depth so the
+        // result doesn't depend on the handedness of the projection
+        let dir = (position_from_depth(uv + omega / ubo.screen_size, depth.x, ubo.inverse_projection) - p).normalize();
+        let ortho_dir = dir - v * dir.dot(v);
+        let axis = ortho_dir.cross(v).normalize();
+
+        // Normal projected into the slice plane and its angle to the view vector
+        let projected_n = n - axis * n.dot(axis);
+        let projected_n_len = projected_n.length();
+        let sign_n = ortho_dir.dot(projected_n).signum();
+        let cos_n = (projected_n.dot(v) / projected_n_len).clamp(0.0, 1.0);
+        let n_angle = sign_n * cos_n.acos();
+
+        // Horizon search on both sides of the slice, starting at the tangent plane of the normal
+        let mut horizon_cos0 = (n_angle + FRAC_PI_2).cos();
+        let mut horizon_cos1 = (n_angle - FRAC_PI_2).cos();
+        let low_horizon_cos0 = horizon_cos0;
+        let low_horizon_cos1 = horizon_cos1;
+        for step in 0..ubo.step_count {
+            let offset = omega * (step as f32 + noise_step + 1.0) * step_pixels;
+            let offset = ivec2(offset.x.round() as i32, offset.y.round() as i32);
+            let max_coord = ivec2(size.x as i32 - 1, size.y as i32 - 1);
+
+            let delta0 = view_pos(depth_image, (coord + offset).clamp(IVec2::ZERO, max_coord), ubo) - p;
+            let delta1 = view_pos(depth_image, (coord - offset).clamp(IVec2::ZERO, max_coord), ubo) - p;
+            let dist0 = delta0.length();
+            let dist1 = delta1.length();
+
+            let weight0 = (dist0 * falloff_mul + falloff_add).clamp(0.0, 1.0);
+            let weight1 = (dist1 * falloff_mul + falloff_add).clamp(0.0, 1.0);
+            let cos0 = low_horizon_cos0 + (delta0.dot(v) / dist0 - low_horizon_cos0) * weight0;
+            let cos1 = low_horizon_cos1 + (delta1.dot(v) / dist1 - low_horizon_cos1) * weight1;
+            horizon_cos0 = horizon_cos0.max(cos0);
+            horizon_cos1 = horizon_cos1.max(cos1);
+        }
+
+        // Horizon angles, clamped to the hemisphere around the normal
+        let h0 = -horizon_cos1.clamp(-1.0, 1.0).acos();
+        let h1 = horizon_cos0.clamp(-1.0, 1.0).acos();
+        let h0 = n_angle + (h0 - n_angle).clamp(-FRAC_PI_2, FRAC_PI_2);
+        let h1 = n_angle + (h1 - n_angle).clamp(-FRAC_PI_2, FRAC_PI_2);
+
+        visibility += projected_n_len * (integrate_arc(h0, n_angle, cos_n) + integrate_arc(h1, n_angle, cos_n));
+    }
+    visibility /= ubo.slice_count as f32;
+
+    unsafe {
+        ao_image.write(coord, vec4(visibility, 0.0, 0.0, 0.0));
+    }
+}
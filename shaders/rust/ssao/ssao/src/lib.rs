@@ -1,8 +1,9 @@
 #![no_std]
 
-use spirv_std::glam::{vec2, vec4, Mat3, Mat4, Vec2, Vec4, Vec4Swizzles};
+use spirv_std::glam::{vec2, vec4, Mat3, Mat4, UVec4, Vec2, Vec3, Vec4, Vec4Swizzles};
 use spirv_std::spirv;
 use spirv_std::{Image, Sampler};
+use common::packing::{position_from_depth, unpack_gbuffer};
 
 const SSAO_KERNEL_SIZE: usize = 64;
 const SSAO_RADIUS: f32 = 0.5;
@@ -20,6 +21,15 @@ pub struct UBO {
     pub noise_scale: Vec2,
 }
 
+// Uniforms of ssao_compact_fs, positions are reconstructed from depth with the inverse projection
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct CompactUBO {
+    pub projection: Mat4,
+    pub inverse_projection: Mat4,
+    pub noise_scale: Vec2,
+}
+
 #[spirv(fragment)]
 pub fn main_fs(
     in_uv: Vec2,
@@ -37,8 +47,75 @@ pub fn main_fs(
     let frag_pos = texture_position_depth.sample(*sampler_position_depth, in_uv).xyz();
     let normal = (texture_normal.sample(*sampler_normal, in_uv).xyz() * 2.0 - 1.0).normalize();
     
+    *out_frag_color = kernel_occlusion(
+        frag_pos,
+        normal,
+        in_uv,
+        sampler_ssao_noise,
+        texture_ssao_noise,
+        ubo_ssao_kernel,
+        ubo.projection,
+        ubo.noise_scale,
+        |uv| -texture_position_depth.sample(*sampler_position_depth, uv).w,
+    );
+}
+
+// Occlusion for the compact G-Buffer of gbuffer_compact_fs, compiled to ssao_compact.frag.spv.
+// Layout:
+//   binding 0: depth attachment of the G-Buffer pass
+//   binding 1: packed rgba32ui G-Buffer, see common::packing::pack_gbuffer
+//   binding 2-3: noise and kernel as for main_fs
+//   binding 4: CompactUBO
+#[spirv(fragment)]
+pub fn ssao_compact_fs(
+    in_uv: Vec2,
+    #[spirv(frag_coord)] frag_coord: Vec4,
+    #[spirv(descriptor_set = 0, binding = 0)] sampler_depth: &Sampler,
+    #[spirv(descriptor_set = 0, binding = 0)] texture_depth: &Image!(2D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 1)] gbuffer: &Image!(2D, type=u32, sampled),
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_ssao_noise: &Sampler,
+    #[spirv(descriptor_set = 0, binding = 2)] texture_ssao_noise: &Image!(2D, type=f32, sampled),
+    #[spirv(uniform, descriptor_set = 0, binding = 3)] ubo_ssao_kernel: &UBOSSAOKernel,
+    #[spirv(uniform, descriptor_set = 0, binding = 4)] ubo: &CompactUBO,
+    out_frag_color: &mut f32,
+) {
+    // View space position and normal
+    let view_pos = |uv: Vec2| {
+        let depth = texture_depth.sample(*sampler_depth, uv).x;
+        position_from_depth(uv, depth, ubo.inverse_projection)
+    };
+    let texel: UVec4 = gbuffer.fetch(frag_coord.xy().as_ivec2());
+    let (normal, _, _) = unpack_gbuffer(texel);
+
+    *out_frag_color = kernel_occlusion(
+        view_pos(in_uv),
+        normal,
+        in_uv,
+        sampler_ssao_noise,
+        texture_ssao_noise,
+        ubo_ssao_kernel,
+        ubo.projection,
+        ubo.noise_scale,
+        |uv| view_pos(uv).z,
+    );
+}
+
+// Hemisphere kernel occlusion around a view space position, sample_depth returns the view space
+// z of the G-Buffer at a texture coordinate
+#[allow(clippy::too_many_arguments)]
+fn kernel_occlusion(
+    frag_pos: Vec3,
+    normal: Vec3,
+    in_uv: Vec2,
+    sampler_ssao_noise: &Sampler,
+    texture_ssao_noise: &Image!(2D, type=f32, sampled),
+    ubo_ssao_kernel: &UBOSSAOKernel,
+    projection: Mat4,
+    noise_scale: Vec2,
+    sample_depth: impl Fn(Vec2) -> f32,
+) -> f32 {
     // Get a random vector using a noise lookup
-    let noise_uv = ubo.noise_scale * in_uv;
+    let noise_uv = noise_scale * in_uv;
     let random_vec = texture_ssao_noise.sample(*sampler_ssao_noise, noise_uv).xyz() * 2.0 - 1.0;
     
     // Create TBN matrix
@@ -56,21 +133,20 @@ pub fn main_fs(
         
         // Project
         let mut offset = vec4(sample_pos.x, sample_pos.y, sample_pos.z, 1.0);
-        offset = ubo.projection * offset;
+        offset = projection * offset;
         let offset_xyz = offset.xyz() / offset.w;
         let offset_xy = vec2(
             offset_xyz.x * 0.5 + 0.5,
             offset_xyz.y * 0.5 + 0.5
         );
         
-        let sample_depth = -texture_position_depth.sample(*sampler_position_depth, offset_xy).w;
+        let sample_depth = sample_depth(offset_xy);
         
         let range_check = smoothstep(0.0, 1.0, SSAO_RADIUS / (frag_pos.z - sample_depth).abs());
         occlusion += if sample_depth >= sample_pos.z + bias { 1.0 } else { 0.0 } * range_check;
     }
     
-    occlusion = 1.0 - (occlusion / SSAO_KERNEL_SIZE as f32);
-    *out_frag_color = occlusion;
+    1.0 - (occlusion / SSAO_KERNEL_SIZE as f32)
 }
 
 fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
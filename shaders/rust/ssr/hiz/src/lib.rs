@@ -0,0 +1,71 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Builds the hierarchical depth buffer used by ssr/trace. Each dispatch reduces one level
+// into the next one, keeping the closest (minimum) depth. The first dispatch reads the
+// depth attachment and writes level 0, with src_size equal to the destination size.
+
+use spirv_std::glam::{ivec2, uvec2, vec4, IVec2, UVec2, UVec3, Vec4};
+use spirv_std::{spirv, Image};
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct PushConsts {
+    pub src_size: UVec2,
+    pub dst_size: UVec2,
+}
+
+fn fetch_depth(src: &Image!(2D, type=f32, sampled), coord: IVec2, max_coord: IVec2) -> f32 {
+    let depth: Vec4 = src.fetch(coord.min(max_coord));
+    depth.x
+}
+
+#[spirv(compute(threads(8, 8)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(descriptor_set = 0, binding = 0)] src_image: &Image!(2D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 1)] dst_image: &Image!(2D, format=r32f, sampled=false),
+    #[spirv(push_constant)] push_consts: &PushConsts,
+) {
+    let dst = uvec2(global_id.x, global_id.y);
+    if dst.x >= push_consts.dst_size.x || dst.y >= push_consts.dst_size.y {
+        return;
+    }
+    let coord = ivec2(dst.x as i32, dst.y as i32);
+    let max_coord = ivec2(push_consts.src_size.x as i32 - 1, push_consts.src_size.y as i32 - 1);
+
+    // Copy of the depth attachment
+    if push_consts.src_size == push_consts.dst_size {
+        unsafe {
+            dst_image.write(coord, vec4(fetch_depth(src_image, coord, max_coord), 0.0, 0.0, 0.0));
+        }
+        return;
+    }
+
+    let src = coord * 2;
+    let mut depth = fetch_depth(src_image, src, max_coord)
+        .min(fetch_depth(src_image, src + ivec2(1, 0), max_coord))
+        .min(fetch_depth(src_image, src + ivec2(0, 1), max_coord))
+        .min(fetch_depth(src_image, src + ivec2(1, 1), max_coord));
+
+    // With odd source sizes the last texel of a row or column also covers the one that
+    // has no partner, otherwise rays could slip through it
+    let odd_x = push_consts.src_size.x & 1 != 0 && dst.x == push_consts.dst_size.x - 1;
+    let odd_y = push_consts.src_size.y & 1 != 0 && dst.y == push_consts.dst_size.y - 1;
+    if odd_x {
+        depth = depth
+            .min(fetch_depth(src_image, src + ivec2(2, 0), max_coord))
+            .min(fetch_depth(src_image, src + ivec2(2, 1), max_coord));
+    }
+    if odd_y {
+        depth = depth
+            .min(fetch_depth(src_image, src + ivec2(0, 2), max_coord))
+            .min(fetch_depth(src_image, src + ivec2(1, 2), max_coord));
+    }
+    if odd_x && odd_y {
+        depth = depth.min(fetch_depth(src_image, src + ivec2(2, 2), max_coord));
+    }
+
+    unsafe {
+        dst_image.write(coord, vec4(depth, 0.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,229 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Hierarchical-Z screen space reflections (Uludag 2014) for the deferred G-Buffer.
+//
+// The reflected ray is projected to screen space and walked through the min depth pyramid
+// built by ssr/hiz: it moves up a level whenever it crosses a cell without touching the
+// closest depth inside it, and down a level when it reaches it, so empty space is skipped
+// in large steps. The output holds the colour of the scene at the hit in rgb and the
+// confidence of the hit in alpha. Rays that miss, leave the screen or point towards the
+// camera have zero confidence, the compositions (deferred's deferred_reflections_fs, pbribl's
+// pbribl_ssr_fs) blend from their prefiltered environment map to the hit by that alpha.
+
+use spirv_std::glam::{ivec2, uvec2, vec2, vec3, vec4, IVec2, Mat4, UVec2, UVec3, UVec4, Vec2, Vec3, Vec4, Vec4Swizzles};
+use spirv_std::image::{sample_with, ImageWithMethods, SampledImage};
+use spirv_std::{num_traits::Float, spirv, Image};
+use common::packing::{position_from_depth, unpack_gbuffer};
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct UBO {
+    pub projection: Mat4,
+    pub inverse_projection: Mat4,
+    pub view: Mat4,
+    pub screen_size: Vec2,
+    // View space length of the traced rays
+    pub max_distance: f32,
+    // View space thickness assumed for surfaces in the depth buffer
+    pub thickness: f32,
+    pub max_iterations: u32,
+    pub hiz_levels: u32,
+    // Screen space reflections fade out towards this roughness
+    pub max_roughness: f32,
+    // Width of the fade towards the screen borders in uv units
+    pub edge_fade: f32,
+}
+
+fn reflect(i: Vec3, n: Vec3) -> Vec3 {
+    i - 2.0 * n.dot(i) * n
+}
+
+// View space position to texture coordinates and depth
+fn project(p: Vec3, projection: Mat4) -> Vec3 {
+    let clip = projection * p.extend(1.0);
+    let ndc = clip.xyz() / clip.w;
+    vec3(ndc.x * 0.5 + 0.5, ndc.y * 0.5 + 0.5, ndc.z)
+}
+
+fn level_size(screen_size: UVec2, level: u32) -> Vec2 {
+    uvec2((screen_size.x >> level).max(1), (screen_size.y >> level).max(1)).as_vec2()
+}
+
+fn hiz_depth(hiz_image: &Image!(2D, type=f32, sampled), cell: Vec2, level: u32) -> f32 {
+    let depth: Vec4 = hiz_image.fetch_with(ivec2(cell.x as i32, cell.y as i32), sample_with::lod(level as i32));
+    depth.x
+}
+
+// Ray parameter at which the ray leaves the cell, nudged into the next one
+fn cell_exit(o: Vec3, d: Vec3, cell: Vec2, cells: Vec2, cross_step: Vec2, cross_offset: Vec2) -> f32 {
+    let planes = (cell + cross_step) / cells + cross_offset;
+    let t = (planes - o.xy()) / d.xy();
+    t.x.min(t.y)
+}
+
+// Reflection colour and hit confidence for the G-Buffer sample at coord, normal in world space
+#[allow(clippy::too_many_arguments)]
+fn trace(
+    coord: IVec2,
+    depth: f32,
+    world_normal: Vec3,
+    roughness: f32,
+    depth_image: &Image!(2D, type=f32, sampled),
+    hiz_image: &Image!(2D, type=f32, sampled),
+    sampler_scene: &SampledImage<Image!(2D, type=f32, sampled)>,
+    ubo: &UBO,
+) -> Vec4 {
+    let size = ubo.screen_size.as_uvec2();
+    let uv = (coord.as_vec2() + 0.5) / ubo.screen_size;
+    let p = position_from_depth(uv, depth, ubo.inverse_projection);
+    let n = (ubo.view * world_normal.extend(0.0)).xyz().normalize();
+
+    let r = reflect(p.normalize(), n);
+
+    let mut confidence = 0.0;
+    let mut hit_color = Vec3::ZERO;
+
+    // A min depth pyramid can only bound rays that move away from the camera
+    if roughness < ubo.max_roughness && r.z < 0.0 {
+        let o = project(p, ubo.projection);
+        let mut d = project(p + r * ubo.max_distance, ubo.projection) - o;
+        // Keep the cell boundary intersections finite for rays along the view direction
+        if d.x.abs() < 1e-7 {
+            d.x = 1e-7;
+        }
+        if d.y.abs() < 1e-7 {
+            d.y = 1e-7;
+        }
+
+        let cross_step = vec2(if d.x >= 0.0 { 1.0 } else { 0.0 }, if d.y >= 0.0 { 1.0 } else { 0.0 });
+        let cross_offset = vec2(d.x.signum(), d.y.signum()) * 0.01 / ubo.screen_size;
+        let max_level = ubo.hiz_levels as i32 - 1;
+
+        // Start in the neighbouring texel so the ray doesn't hit its own surface
+        let mut t = cell_exit(o, d, (o.xy() * ubo.screen_size).floor(), ubo.screen_size, cross_step, cross_offset);
+        let mut level = 0;
+        let mut iteration = 0;
+        while level >= 0 && iteration < ubo.max_iterations && t <= 1.0 {
+            let ray = o + d * t;
+            if ray.x < 0.0 || ray.x >= 1.0 || ray.y < 0.0 || ray.y >= 1.0 {
+                break;
+            }
+            let cells = level_size(size, level as u32);
+            let cell = (ray.xy() * cells).floor();
+            let min_depth = hiz_depth(hiz_image, cell, level as u32);
+
+            // Advance to the closest depth in the cell, or to its border if that comes first
+            let mut t_next = t;
+            if ray.z < min_depth {
+                t_next = (min_depth - o.z) / d.z;
+            }
+            let next_cell = ((o.xy() + d.xy() * t_next) * cells).floor();
+            if next_cell != cell {
+                t_next = cell_exit(o, d, cell, cells, cross_step, cross_offset);
+                level = (level + 2).min(max_level);
+            }
+            t = t_next;
+            level -= 1;
+            iteration += 1;
+        }
+
+        if level < 0 && t <= 1.0 {
+            let hit = o + d * t;
+            let hit_coord = (hit.xy() * ubo.screen_size).as_ivec2().clamp(IVec2::ZERO, ivec2(size.x as i32 - 1, size.y as i32 - 1));
+            let surface: Vec4 = depth_image.fetch(hit_coord);
+            let surface_depth = -position_from_depth(hit.xy(), surface.x, ubo.inverse_projection).z;
+            let ray_depth = -position_from_depth(hit.xy(), hit.z, ubo.inverse_projection).z;
+
+            // Rays passing behind a surface further than its thickness didn't hit it
+            if ray_depth - surface_depth < ubo.thickness {
+                let edge = (hit.xy().min(Vec2::ONE - hit.xy()) / ubo.edge_fade).clamp(Vec2::ZERO, Vec2::ONE);
+                let distance_fade = 1.0 - t * t;
+                let roughness_fade = 1.0 - roughness / ubo.max_roughness;
+                confidence = (edge.x.min(edge.y) * distance_fade * roughness_fade).clamp(0.0, 1.0);
+                hit_color = sampler_scene.sample_by_lod(hit.xy(), 0.0).xyz();
+            }
+        }
+    }
+
+    vec4(hit_color.x, hit_color.y, hit_color.z, confidence)
+}
+
+#[spirv(compute(threads(8, 8)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(descriptor_set = 0, binding = 0)] depth_image: &Image!(2D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 1)] hiz_image: &Image!(2D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 2)] normal_image: &Image!(2D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 3)] material_image: &Image!(2D, type=f32, sampled),
+    // Lit scene the reflections are taken from
+    #[spirv(descriptor_set = 0, binding = 4)] sampler_scene: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(uniform, descriptor_set = 0, binding = 5)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 6)] reflection_image: &Image!(2D, format=rgba16f, sampled=false),
+) {
+    let size = ubo.screen_size.as_uvec2();
+    if global_id.x >= size.x || global_id.y >= size.y {
+        return;
+    }
+    let coord = ivec2(global_id.x as i32, global_id.y as i32);
+
+    // Nothing to reflect on the background
+    let depth: Vec4 = depth_image.fetch(coord);
+    if depth.x >= 1.0 {
+        unsafe {
+            reflection_image.write(coord, Vec4::ZERO);
+        }
+        return;
+    }
+
+    let normal: Vec4 = normal_image.fetch(coord);
+    let material: Vec4 = material_image.fetch(coord);
+    let reflection = trace(
+        coord,
+        depth.x,
+        normal.xyz().normalize(),
+        material.y,
+        depth_image,
+        hiz_image,
+        sampler_scene,
+        ubo,
+    );
+    unsafe {
+        reflection_image.write(coord, reflection);
+    }
+}
+
+// Tracing for the compact G-Buffer of deferred's mrt_compact_fs, compiled to
+// trace_compact.comp.spv. The layout is the one of main_cs with the packed rgba32ui G-Buffer
+// in binding 2 in place of the normal and material targets, binding 3 is unused.
+#[spirv(compute(threads(8, 8)))]
+pub fn trace_compact_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(descriptor_set = 0, binding = 0)] depth_image: &Image!(2D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 1)] hiz_image: &Image!(2D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 2)] gbuffer: &Image!(2D, type=u32, sampled),
+    #[spirv(descriptor_set = 0, binding = 4)] sampler_scene: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(uniform, descriptor_set = 0, binding = 5)] ubo: &UBO,
+    #[spirv(descriptor_set = 0, binding = 6)] reflection_image: &Image!(2D, format=rgba16f, sampled=false),
+) {
+    let size = ubo.screen_size.as_uvec2();
+    if global_id.x >= size.x || global_id.y >= size.y {
+        return;
+    }
+    let coord = ivec2(global_id.x as i32, global_id.y as i32);
+
+    // Nothing to reflect on the background
+    let depth: Vec4 = depth_image.fetch(coord);
+    if depth.x >= 1.0 {
+        unsafe {
+            reflection_image.write(coord, Vec4::ZERO);
+        }
+        return;
+    }
+
+    let texel: UVec4 = gbuffer.fetch(coord);
+    let (normal, _, material) = unpack_gbuffer(texel);
+    let reflection = trace(coord, depth.x, normal, material.y, depth_image, hiz_image, sampler_scene, ubo);
+    unsafe {
+        reflection_image.write(coord, reflection);
+    }
+}
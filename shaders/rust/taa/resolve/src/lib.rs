@@ -0,0 +1,94 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Temporal anti-aliasing resolve. Runs after a fullscreen composition pass (e.g. hdr/composition,
+// deferred/deferred or ssao/composition) on its jittered output, reprojects last frame's resolved
+// image with the G-Buffer velocity and blends the two. The velocity target is written by the
+// *_motion entry points of the G-Buffer passes (mrt_motion_*, gbuffer_motion_*). The host copies
+// the result into the history image for the next frame.
+
+use spirv_std::glam::{ivec2, vec2, vec4, Vec2, Vec3, Vec4, Vec4Swizzles};
+use spirv_std::image::SampledImage;
+use spirv_std::{spirv, Image};
+use common::temporal::{rgb_to_ycocg, ycocg_to_rgb};
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct UBO {
+    pub texel_size: Vec2,
+    // Weight of the history, higher values give smoother but blurrier results
+    pub feedback: f32,
+    // Strength of the unsharp mask applied to the current frame
+    pub sharpness: f32,
+}
+
+#[spirv(vertex)]
+pub fn main_vs(
+    #[spirv(vertex_index)] vert_index: i32,
+    #[spirv(position)] out_position: &mut Vec4,
+    out_uv: &mut Vec2,
+) {
+    let uv = vec2(((vert_index << 1) & 2) as f32, (vert_index & 2) as f32);
+    *out_uv = uv;
+    *out_position = vec4(uv.x * 2.0 - 1.0, uv.y * 2.0 - 1.0, 0.0, 1.0);
+}
+
+#[spirv(fragment)]
+pub fn main_fs(
+    in_uv: Vec2,
+    #[spirv(descriptor_set = 0, binding = 0)] sampler_color: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(descriptor_set = 0, binding = 1)] sampler_history: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(descriptor_set = 0, binding = 2)] sampler_velocity: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(descriptor_set = 0, binding = 3)] sampler_depth: &SampledImage<Image!(2D, type=f32, sampled)>,
+    #[spirv(uniform, descriptor_set = 0, binding = 4)] ubo: &UBO,
+    #[spirv(spec_constant(id = 0, default = 0))] sharpen: u32,
+    out_frag_color: &mut Vec4,
+) {
+    // Neighbourhood of the current frame: color bounds in YCoCg and the closest depth,
+    // whose velocity is used so that edges of moving objects reproject with the object
+    let center = sampler_color.sample(in_uv).xyz();
+    let mut color_min = Vec3::splat(f32::MAX);
+    let mut color_max = Vec3::splat(f32::MIN);
+    let mut cross_sum = Vec3::ZERO;
+    let mut closest_depth = 1.0;
+    let mut closest_uv = in_uv;
+    for x in -1..=1 {
+        for y in -1..=1 {
+            let uv = in_uv + ivec2(x, y).as_vec2() * ubo.texel_size;
+            let color = sampler_color.sample(uv).xyz();
+            let ycocg = rgb_to_ycocg(color);
+            color_min = color_min.min(ycocg);
+            color_max = color_max.max(ycocg);
+            if (x == 0) != (y == 0) {
+                cross_sum += color;
+            }
+
+            let depth = sampler_depth.sample(uv).x;
+            if depth < closest_depth {
+                closest_depth = depth;
+                closest_uv = uv;
+            }
+        }
+    }
+
+    let mut current = center;
+    if sharpen != 0 {
+        // Unsharp mask against the four direct neighbours
+        current = (center + (center - cross_sum * 0.25) * ubo.sharpness).max(Vec3::ZERO);
+    }
+
+    let velocity = sampler_velocity.sample(closest_uv).xy();
+    let history_uv = in_uv - velocity;
+
+    // No history outside of the screen, e.g. for disoccluded borders
+    if history_uv.x < 0.0 || history_uv.x > 1.0 || history_uv.y < 0.0 || history_uv.y > 1.0 {
+        *out_frag_color = vec4(current.x, current.y, current.z, 1.0);
+        return;
+    }
+
+    // Clamp the history to what the current neighbourhood allows to reject stale samples
+    let history = sampler_history.sample(history_uv).xyz();
+    let history = ycocg_to_rgb(rgb_to_ycocg(history).clamp(color_min, color_max));
+
+    let result = current.lerp(history, ubo.feedback);
+    *out_frag_color = vec4(result.x, result.y, result.z, 1.0);
+}
@@ -0,0 +1,41 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Last volumetric fog pass: attenuates the lit scene by the transmittance towards each pixel
+// and adds the light scattered along the way, both read from the integrated froxel volume.
+
+use spirv_std::glam::{ivec2, vec3, vec4, UVec3, Vec4, Vec4Swizzles};
+use spirv_std::image::SampledImage;
+use spirv_std::{spirv, Image};
+use common::froxel::FroxelGrid;
+use common::packing::position_from_depth;
+
+#[spirv(compute(threads(8, 8)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] grid: &FroxelGrid,
+    #[spirv(descriptor_set = 0, binding = 1)] depth_image: &Image!(2D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 2)] color_image: &Image!(2D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 3)] sampler_volume: &SampledImage<Image!(3D, type=f32, sampled)>,
+    #[spirv(descriptor_set = 0, binding = 4)] output_image: &Image!(2D, format=rgba16f, sampled=false),
+) {
+    let size = grid.screen_size.as_uvec2();
+    if global_id.x >= size.x || global_id.y >= size.y {
+        return;
+    }
+    let coord = ivec2(global_id.x as i32, global_id.y as i32);
+    let uv = (coord.as_vec2() + 0.5) / grid.screen_size;
+
+    let depth: Vec4 = depth_image.fetch(coord);
+    let view_depth = -position_from_depth(uv, depth.x, grid.inverse_projection).z;
+
+    // Texel z holds the totals up to the far end of its slice
+    let w = grid.depth_to_w(view_depth) - 0.5 / grid.depth as f32;
+    let fog = sampler_volume.sample_by_lod(vec3(uv.x, uv.y, w), 0.0);
+
+    let color: Vec4 = color_image.fetch(coord);
+    let result = color.xyz() * fog.w + fog.xyz();
+
+    unsafe {
+        output_image.write(coord, vec4(result.x, result.y, result.z, color.w));
+    }
+}
@@ -0,0 +1,136 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// First volumetric fog pass: evaluates the participating media at the center of each froxel.
+// Writes the in-scattered light of the directional light (shadowed by the cascaded shadow map
+// of shadowmappingcascade) and the ambient light in rgb, and the extinction coefficient in alpha.
+
+use core::f32::consts::PI;
+use spirv_std::glam::{ivec3, mat4, vec2, vec3, vec4, Mat4, UVec3, Vec3, Vec4, Vec4Swizzles};
+use spirv_std::image::SampledImage;
+use spirv_std::{num_traits::Float, spirv, Image, Sampler};
+use common::cascade::cascade_index;
+use common::froxel::FroxelGrid;
+
+const SHADOW_MAP_CASCADE_COUNT: usize = 4;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct FogUBO {
+    pub cascade_matrices: [Mat4; SHADOW_MAP_CASCADE_COUNT],
+    pub cascade_splits: Vec4,
+    pub camera_pos: Vec4,
+    // Direction the light travels in, as in shadowmappingcascade/scene
+    pub light_dir: Vec4,
+    // Color in rgb, intensity in w
+    pub light_color: Vec4,
+    pub ambient: Vec4,
+    // World space offset of the noise lookup, animated to move the fog with the wind
+    pub noise_offset: Vec4,
+    pub density: f32,
+    // Exponential falloff of the density above base_height, zero gives uniform fog
+    pub height_falloff: f32,
+    pub base_height: f32,
+    // Henyey-Greenstein anisotropy, positive values scatter forward
+    pub anisotropy: f32,
+    pub noise_scale: f32,
+    // Blend between uniform density (0) and fully noise driven density (1)
+    pub noise_strength: f32,
+}
+
+fn henyey_greenstein(cos_theta: f32, g: f32) -> f32 {
+    let g2 = g * g;
+    (1.0 - g2) / (4.0 * PI * (1.0 + g2 - 2.0 * g * cos_theta).powf(1.5))
+}
+
+fn density(
+    world_pos: Vec3,
+    fog: &FogUBO,
+    noise_texture: &SampledImage<Image!(3D, type=f32, sampled)>,
+) -> f32 {
+    // Height fog
+    let height = (world_pos.y - fog.base_height).max(0.0);
+    let mut density = fog.density * (-fog.height_falloff * height).exp();
+
+    // Noise from the tiling 3D texture
+    let noise = noise_texture.sample_by_lod(world_pos * fog.noise_scale + fog.noise_offset.xyz(), 0.0).x;
+    density *= 1.0 - fog.noise_strength + fog.noise_strength * noise;
+    density
+}
+
+fn shadow(
+    world_pos: Vec3,
+    view_depth: f32,
+    fog: &FogUBO,
+    shadow_map: &Image!(2D, type=f32, sampled, arrayed),
+    shadow_sampler: &Sampler,
+) -> f32 {
+    // The splits are in view space z, which is negative in front of the camera
+    let cascade_index = cascade_index(-view_depth, fog.cascade_splits);
+    let cascade_matrix = match cascade_index {
+        0 => fog.cascade_matrices[0],
+        1 => fog.cascade_matrices[1],
+        2 => fog.cascade_matrices[2],
+        _ => fog.cascade_matrices[3],
+    };
+
+    let bias_mat = mat4(
+        vec4(0.5, 0.0, 0.0, 0.0),
+        vec4(0.0, 0.5, 0.0, 0.0),
+        vec4(0.0, 0.0, 1.0, 0.0),
+        vec4(0.5, 0.5, 0.0, 1.0),
+    );
+    let shadow_coord = bias_mat * cascade_matrix * world_pos.extend(1.0);
+    let shadow_coord = shadow_coord / shadow_coord.w;
+    if shadow_coord.z <= -1.0 || shadow_coord.z >= 1.0 {
+        return 1.0;
+    }
+    let dist = shadow_map
+        .sample_by_lod(*shadow_sampler, vec3(shadow_coord.x, shadow_coord.y, cascade_index as f32), 0.0)
+        .x;
+    if dist < shadow_coord.z - 0.005 {
+        0.0
+    } else {
+        1.0
+    }
+}
+
+#[spirv(compute(threads(8, 8, 1)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] grid: &FroxelGrid,
+    #[spirv(uniform, descriptor_set = 0, binding = 1)] fog: &FogUBO,
+    #[spirv(descriptor_set = 0, binding = 2)] shadow_map: &Image!(2D, type=f32, sampled, arrayed),
+    #[spirv(descriptor_set = 0, binding = 2)] shadow_sampler: &Sampler,
+    #[spirv(descriptor_set = 0, binding = 3)] noise_texture: &SampledImage<Image!(3D, type=f32, sampled)>,
+    #[spirv(descriptor_set = 0, binding = 4)] volume: &Image!(3D, format=rgba16f, sampled=false),
+) {
+    if global_id.x >= grid.width || global_id.y >= grid.height || global_id.z >= grid.depth {
+        return;
+    }
+
+    // Froxel center
+    let uv = vec2(
+        (global_id.x as f32 + 0.5) / grid.width as f32,
+        (global_id.y as f32 + 0.5) / grid.height as f32,
+    );
+    let w = (global_id.z as f32 + 0.5) / grid.depth as f32;
+    let world_pos = grid.world_position(uv, w);
+
+    let extinction = density(world_pos, fog, noise_texture);
+
+    let view_dir = (world_pos - fog.camera_pos.xyz()).normalize();
+    let light_dir = fog.light_dir.xyz().normalize();
+    let phase = henyey_greenstein(light_dir.dot(-view_dir), fog.anisotropy);
+    let visibility = shadow(world_pos, grid.slice_depth(w), fog, shadow_map, shadow_sampler);
+
+    // Purely scattering media, the scattering coefficient equals the extinction
+    let light = fog.light_color.xyz() * fog.light_color.w * visibility * phase + fog.ambient.xyz();
+    let scattering = light * extinction;
+
+    unsafe {
+        volume.write(
+            ivec3(global_id.x as i32, global_id.y as i32, global_id.z as i32),
+            vec4(scattering.x, scattering.y, scattering.z, extinction),
+        );
+    }
+}
@@ -0,0 +1,43 @@
+#![cfg_attr(target_arch = "spirv", no_std)]
+
+// Second volumetric fog pass: walks every froxel column front to back and accumulates the
+// in-scattered light and the transmittance (Hillaire 2015, energy conserving integration).
+// Slice z of the output holds the totals up to the far end of that slice.
+
+use spirv_std::glam::{ivec3, vec4, UVec3, Vec3, Vec4, Vec4Swizzles};
+use spirv_std::{num_traits::Float, spirv, Image};
+use common::froxel::FroxelGrid;
+
+#[spirv(compute(threads(8, 8, 1)))]
+pub fn main_cs(
+    #[spirv(global_invocation_id)] global_id: UVec3,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] grid: &FroxelGrid,
+    #[spirv(descriptor_set = 0, binding = 1)] scattering_volume: &Image!(3D, type=f32, sampled),
+    #[spirv(descriptor_set = 0, binding = 2)] integrated_volume: &Image!(3D, format=rgba16f, sampled=false),
+) {
+    if global_id.x >= grid.width || global_id.y >= grid.height {
+        return;
+    }
+
+    let mut scattering = Vec3::ZERO;
+    let mut transmittance = 1.0;
+    let mut slice_start = grid.z_near;
+    for z in 0..grid.depth {
+        let coord = ivec3(global_id.x as i32, global_id.y as i32, z as i32);
+        let froxel: Vec4 = scattering_volume.fetch(coord);
+        let slice_end = grid.slice_depth((z + 1) as f32 / grid.depth as f32);
+        let thickness = slice_end - slice_start;
+        slice_start = slice_end;
+
+        // Analytic integral of the scattered light over the slice, attenuated within it
+        let extinction = froxel.w.max(0.000001);
+        let slice_transmittance = (-extinction * thickness).exp();
+        let slice_scattering = (froxel.xyz() - froxel.xyz() * slice_transmittance) / extinction;
+        scattering += transmittance * slice_scattering;
+        transmittance *= slice_transmittance;
+
+        unsafe {
+            integrated_volume.write(coord, vec4(scattering.x, scattering.y, scattering.z, transmittance));
+        }
+    }
+}